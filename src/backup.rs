@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{BackupMode, FilesConfig};
+use crate::file_io;
+
+/// Copies the current contents of `path` aside before it is overwritten.
+pub fn backup_before_save(path: &str, files: &FilesConfig) -> io::Result<()> {
+    let source = Path::new(path);
+    if !source.is_file() {
+        return Ok(());
+    }
+    match files.backup {
+        BackupMode::None => Ok(()),
+        BackupMode::Sibling => {
+            let mut name = source.as_os_str().to_os_string();
+            name.push("~");
+            fs::copy(source, PathBuf::from(name)).map(|_| ())
+        }
+        BackupMode::Numbered => backup_numbered(path, files),
+    }
+}
+
+fn backup_numbered(path: &str, files: &FilesConfig) -> io::Result<()> {
+    let dir = file_io::expand_home(&files.backup_dir);
+    fs::create_dir_all(&dir)?;
+
+    let prefix = format!("{}.", file_io::path_key(path));
+    let mut existing = numbered_backups(&dir, &prefix)?;
+    let next = existing.last().map(|(n, _)| n + 1).unwrap_or(1);
+    let target = dir.join(format!("{}{}~", prefix, next));
+    fs::copy(path, &target)?;
+    existing.push((next, target));
+
+    let keep = files.backup_count.max(1) as usize;
+    if existing.len() > keep {
+        // The new backup is already made, so a pruning failure only leaves extras behind.
+        for (_, old) in existing.drain(..existing.len() - keep) {
+            if let Err(err) = fs::remove_file(&old) {
+                eprintln!("Failed to remove old backup {}: {}", old.display(), err);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Returns `(number, path)` pairs for `<prefix><number>~` files, oldest first.
+fn numbered_backups(dir: &Path, prefix: &str) -> io::Result<Vec<(u32, PathBuf)>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+        let number = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix('~'))
+            .and_then(|n| n.parse::<u32>().ok());
+        if let Some(n) = number {
+            found.push((n, entry.path()));
+        }
+    }
+    found.sort_by_key(|(n, _)| *n);
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("skrift-backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files(backup: BackupMode, backup_dir: &Path, backup_count: i32) -> FilesConfig {
+        FilesConfig {
+            backup,
+            backup_dir: backup_dir.display().to_string(),
+            backup_count,
+            history: false,
+            history_max_age_days: 0,
+            search_root: String::new(),
+        }
+    }
+
+    /// The contents of the numbered backups of `path`, oldest first.
+    fn numbered(dir: &Path, path: &Path) -> Vec<(u32, String)> {
+        let prefix = format!("{}.", file_io::path_key(&path.to_string_lossy()));
+        numbered_backups(dir, &prefix)
+            .unwrap()
+            .into_iter()
+            .map(|(n, backup)| (n, fs::read_to_string(backup).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn sibling_and_none() {
+        let dir = temp_dir("sibling");
+        let path = dir.join("notes.txt");
+        let path_str = path.to_string_lossy();
+        fs::write(&path, "v1").unwrap();
+
+        backup_before_save(&path_str, &files(BackupMode::None, &dir, 5)).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        backup_before_save(&path_str, &files(BackupMode::Sibling, &dir, 5)).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("notes.txt~")).unwrap(), "v1");
+        // A file that isn't there yet has nothing to back up.
+        backup_before_save(&dir.join("new.txt").to_string_lossy(), &files(BackupMode::Sibling, &dir, 5)).unwrap();
+        assert!(!dir.join("new.txt~").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn numbered_backups_count_up_and_keep_the_newest() {
+        let dir = temp_dir("numbered");
+        let backups = dir.join("backups");
+        let (path, other) = (dir.join("notes.txt"), dir.join("other.txt"));
+        let config = files(BackupMode::Numbered, &backups, 2);
+        fs::write(&other, "other").unwrap();
+        backup_before_save(&other.to_string_lossy(), &config).unwrap();
+        for version in ["v1", "v2", "v3"] {
+            fs::write(&path, version).unwrap();
+            backup_before_save(&path.to_string_lossy(), &config).unwrap();
+        }
+        assert_eq!(numbered(&backups, &path), [(2, "v2".to_string()), (3, "v3".to_string())]);
+        assert_eq!(numbered(&backups, &other), [(1, "other".to_string())], "other files keep their own");
+
+        // At least one backup is always kept.
+        fs::write(&path, "v4").unwrap();
+        backup_before_save(&path.to_string_lossy(), &files(BackupMode::Numbered, &backups, 0)).unwrap();
+        assert_eq!(numbered(&backups, &path), [(4, "v4".to_string())]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
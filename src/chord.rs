@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use fltk::app;
+
+use crate::config::Binding;
+use crate::key_combo::{self, KeyCombo, KeySequence};
+
+/// What a key press did to the chord being typed.
+#[derive(Debug, PartialEq)]
+pub enum ChordStep {
+    /// The key finished a binding.
+    Run(Binding),
+    /// The key started or continued a chord; more keys are needed.
+    Wait,
+    /// The key didn't continue the pending chord, which is dropped along with the key.
+    Cancel,
+    /// The key has nothing to do with any binding.
+    Pass,
+}
+
+/// The keys typed so far of a multi-key binding such as `"Ctrl+K Ctrl+C"`.
+pub struct Chord {
+    pending: Vec<KeyCombo>,
+    timeout: Option<app::TimeoutHandle>,
+}
+
+impl Chord {
+    pub fn new() -> Self {
+        Chord { pending: Vec::new(), timeout: None }
+    }
+
+    /// The keys typed so far, as they would be written in a binding.
+    pub fn prefix(&self) -> String {
+        KeySequence::from_combos(self.pending.clone()).to_string()
+    }
+
+    /// Takes the key being handled. A binding the key completes wins over longer ones it
+    /// would continue, so a key bound on its own never waits.
+    pub fn press(&mut self, bindings: &BTreeMap<Binding, Vec<KeySequence>>) -> ChordStep {
+        if key_combo::is_modifier(app::event_key()) {
+            return ChordStep::Pass;
+        }
+        let step = self.step(bindings, KeyCombo::matches_event);
+        if matches!(step, ChordStep::Run(_) | ChordStep::Cancel) {
+            self.cancel();
+        }
+        step
+    }
+
+    /// `press` for a key that is the combinations `matches` accepts, leaving the timeout
+    /// of a chord it ends to the caller.
+    fn step(&mut self, bindings: &BTreeMap<Binding, Vec<KeySequence>>, matches: impl Fn(&KeyCombo) -> bool) -> ChordStep {
+        let typed = self.pending.len();
+        let mut next = None;
+        for (binding, sequence) in every_sequence(bindings) {
+            let combos = sequence.combos();
+            if combos.len() <= typed || combos[..typed] != self.pending[..] || !matches(&combos[typed]) {
+                continue;
+            }
+            if combos.len() == typed + 1 {
+                self.pending.clear();
+                return ChordStep::Run(binding.clone());
+            }
+            next = Some(combos[typed]);
+        }
+        match next {
+            Some(combo) => {
+                self.pending.push(combo);
+                ChordStep::Wait
+            }
+            None if typed > 0 => {
+                self.pending.clear();
+                ChordStep::Cancel
+            }
+            None => ChordStep::Pass,
+        }
+    }
+
+    /// The rest of every binding the pending keys start, with what it runs, in key order.
+    pub fn continuations(&self, bindings: &BTreeMap<Binding, Vec<KeySequence>>) -> Vec<(String, Binding)> {
+        let typed = self.pending.len();
+        let mut rest: Vec<(String, Binding)> = every_sequence(bindings)
+            .filter(|(_, sequence)| {
+                let combos = sequence.combos();
+                combos.len() > typed && combos[..typed] == self.pending[..]
+            })
+            .map(|(binding, sequence)| {
+                let rest = KeySequence::from_combos(sequence.combos()[typed..].to_vec());
+                (rest.to_string(), binding.clone())
+            })
+            .collect();
+        rest.sort_by(|a, b| a.0.cmp(&b.0));
+        rest
+    }
+
+    /// Gives up the pending chord when `handle` fires, replacing any earlier timeout.
+    pub fn set_timeout(&mut self, handle: app::TimeoutHandle) {
+        if let Some(old) = self.timeout.replace(handle) {
+            app::remove_timeout3(old);
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(handle) = self.reset() {
+            app::remove_timeout3(handle);
+        }
+    }
+
+    /// Drops the pending keys, handing back the timeout that was waiting to drop them.
+    fn reset(&mut self) -> Option<app::TimeoutHandle> {
+        self.pending.clear();
+        self.timeout.take()
+    }
+}
+
+fn every_sequence(bindings: &BTreeMap<Binding, Vec<KeySequence>>) -> impl Iterator<Item = (&Binding, &KeySequence)> {
+    bindings.iter().flat_map(|(binding, sequences)| sequences.iter().map(move |sequence| (binding, sequence)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(specs: &[(Binding, &str)]) -> BTreeMap<Binding, Vec<KeySequence>> {
+        specs
+            .iter()
+            .map(|(binding, spec)| (binding.clone(), vec![KeySequence::parse(spec).unwrap()]))
+            .collect()
+    }
+
+    fn press(chord: &mut Chord, bindings: &BTreeMap<Binding, Vec<KeySequence>>, key: &str) -> ChordStep {
+        let pressed = KeyCombo::parse(key).unwrap();
+        chord.step(bindings, |combo| *combo == pressed)
+    }
+
+    #[test]
+    fn chords_wait_then_run() {
+        let bindings = bindings(&[
+            (Binding::Find, "Ctrl+K Ctrl+F"),
+            (Binding::Replace, "Ctrl+K Ctrl+R"),
+            (Binding::CloseBuffer, "Ctrl+W"),
+        ]);
+        let mut chord = Chord::new();
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+W"), ChordStep::Run(Binding::CloseBuffer));
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+K"), ChordStep::Wait);
+        assert_eq!(chord.prefix(), "Ctrl+K");
+        assert_eq!(
+            chord.continuations(&bindings),
+            [("Ctrl+F".to_string(), Binding::Find), ("Ctrl+R".to_string(), Binding::Replace)]
+        );
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+R"), ChordStep::Run(Binding::Replace));
+        assert_eq!(chord.prefix(), "", "running a binding ends the chord");
+    }
+
+    #[test]
+    fn other_keys_cancel_or_pass() {
+        let bindings = bindings(&[(Binding::Find, "Ctrl+K Ctrl+F"), (Binding::CloseBuffer, "Ctrl+W")]);
+        let mut chord = Chord::new();
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+F"), ChordStep::Pass);
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+K"), ChordStep::Wait);
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+W"), ChordStep::Cancel, "bindings don't run mid-chord");
+        assert_eq!(chord.prefix(), "");
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+F"), ChordStep::Pass);
+    }
+
+    #[test]
+    fn a_timed_out_chord_starts_over() {
+        let bindings = bindings(&[(Binding::Find, "Ctrl+K Ctrl+K Ctrl+F"), (Binding::Replace, "Ctrl+F")]);
+        let mut chord = Chord::new();
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+K"), ChordStep::Wait);
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+K"), ChordStep::Wait);
+        assert_eq!(chord.prefix(), "Ctrl+K Ctrl+K");
+        // A stand-in for the handle of the timeout that drops the chord when it fires.
+        chord.timeout = Some(std::ptr::NonNull::dangling().as_ptr());
+        assert!(chord.reset().is_some());
+        assert_eq!((chord.prefix(), chord.timeout), (String::new(), None));
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+F"), ChordStep::Run(Binding::Replace));
+    }
+
+    #[test]
+    fn a_complete_binding_wins_over_a_longer_one() {
+        let bindings = bindings(&[(Binding::Find, "Ctrl+K"), (Binding::Replace, "Ctrl+K Ctrl+R")]);
+        let mut chord = Chord::new();
+        assert_eq!(press(&mut chord, &bindings, "Ctrl+K"), ChordStep::Run(Binding::Find));
+        assert_eq!(chord.prefix(), "");
+    }
+}
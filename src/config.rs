@@ -1,33 +1,128 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
-use fltk::{
-    enums::{Color, Key, Shortcut},
-    app
-};
+use fltk::enums::Color;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+use crate::key_combo::KeySequence;
+
+/// An action keys can be bound to. Where keys from the config don't settle a clash between
+/// two actions, the one declared first keeps the keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Binding {
     Save,
+    SaveAs,
     Quit,
     Reload,
     MoveLineUp,
     MoveLineDown,
     OpenFile,
+    ConvertEncoding,
+    ConvertLineEndings,
+    FileHistory,
+    Undo,
+    Redo,
+    UndoTree,
+    Find,
+    Replace,
+    FindNext,
+    FindPrevious,
+    ReplaceNext,
+    ReplaceAll,
+    ToggleRegex,
+    ToggleCase,
+    ToggleWholeWord,
+    ToggleInSelection,
+    ProjectSearch,
+    NextBuffer,
+    PreviousBuffer,
+    CloseBuffer,
+    BufferSwitcher,
+    SplitHorizontal,
+    SplitVertical,
+    ClosePane,
+    NextPane,
+    PreviousPane,
 }
 
 impl Binding {
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "save" => Some(Binding::Save),
-            "quit" => Some(Binding::Quit),
-            "reload" => Some(Binding::Reload),
-            "move_line_up" => Some(Binding::MoveLineUp),
-            "move_line_down" => Some(Binding::MoveLineDown),
-            "open_file" => Some(Binding::OpenFile),
-            _ => None,
+    pub const ALL: [Binding; 33] = [
+        Binding::Save,
+        Binding::SaveAs,
+        Binding::Quit,
+        Binding::Reload,
+        Binding::MoveLineUp,
+        Binding::MoveLineDown,
+        Binding::OpenFile,
+        Binding::ConvertEncoding,
+        Binding::ConvertLineEndings,
+        Binding::FileHistory,
+        Binding::Undo,
+        Binding::Redo,
+        Binding::UndoTree,
+        Binding::Find,
+        Binding::Replace,
+        Binding::FindNext,
+        Binding::FindPrevious,
+        Binding::ReplaceNext,
+        Binding::ReplaceAll,
+        Binding::ToggleRegex,
+        Binding::ToggleCase,
+        Binding::ToggleWholeWord,
+        Binding::ToggleInSelection,
+        Binding::ProjectSearch,
+        Binding::NextBuffer,
+        Binding::PreviousBuffer,
+        Binding::CloseBuffer,
+        Binding::BufferSwitcher,
+        Binding::SplitHorizontal,
+        Binding::SplitVertical,
+        Binding::ClosePane,
+        Binding::NextPane,
+        Binding::PreviousPane,
+    ];
+
+    /// The name the binding goes by in the config, such as `split_horizontal`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Binding::Save => "save",
+            Binding::SaveAs => "save_as",
+            Binding::Quit => "quit",
+            Binding::Reload => "reload",
+            Binding::MoveLineUp => "move_line_up",
+            Binding::MoveLineDown => "move_line_down",
+            Binding::OpenFile => "open_file",
+            Binding::ConvertEncoding => "convert_encoding",
+            Binding::ConvertLineEndings => "convert_line_endings",
+            Binding::FileHistory => "file_history",
+            Binding::Undo => "undo",
+            Binding::Redo => "redo",
+            Binding::UndoTree => "undo_tree",
+            Binding::Find => "find",
+            Binding::Replace => "replace",
+            Binding::FindNext => "find_next",
+            Binding::FindPrevious => "find_previous",
+            Binding::ReplaceNext => "replace_next",
+            Binding::ReplaceAll => "replace_all",
+            Binding::ToggleRegex => "toggle_regex",
+            Binding::ToggleCase => "toggle_case",
+            Binding::ToggleWholeWord => "toggle_whole_word",
+            Binding::ToggleInSelection => "toggle_in_selection",
+            Binding::ProjectSearch => "project_search",
+            Binding::NextBuffer => "next_buffer",
+            Binding::PreviousBuffer => "previous_buffer",
+            Binding::CloseBuffer => "close_buffer",
+            Binding::BufferSwitcher => "buffer_switcher",
+            Binding::SplitHorizontal => "split_horizontal",
+            Binding::SplitVertical => "split_vertical",
+            Binding::ClosePane => "close_pane",
+            Binding::NextPane => "next_pane",
+            Binding::PreviousPane => "previous_pane",
         }
     }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Binding::ALL.iter().find(|binding| binding.name() == s).cloned()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -69,6 +164,7 @@ impl Cursor {
 pub enum ScrollBarStyle {
     Boxy,
     Rounded,
+    Minimap,
 }
 
 impl ScrollBarStyle {
@@ -76,6 +172,7 @@ impl ScrollBarStyle {
         match s.to_lowercase().as_str() {
             "boxy" => ScrollBarStyle::Boxy,
             "rounded" => ScrollBarStyle::Rounded,
+            "minimap" => ScrollBarStyle::Minimap,
             _ => ScrollBarStyle::Rounded,
         }
     }
@@ -86,15 +183,53 @@ impl Into<crate::widgets::scrollbar::ScrollBarStyle> for ScrollBarStyle {
         match self {
             ScrollBarStyle::Boxy => crate::widgets::scrollbar::ScrollBarStyle::Boxy,
             ScrollBarStyle::Rounded => crate::widgets::scrollbar::ScrollBarStyle::Rounded,
+            ScrollBarStyle::Minimap => crate::widgets::scrollbar::ScrollBarStyle::Minimap,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackupMode {
+    None,
+    Sibling,
+    Numbered,
+}
+
+impl BackupMode {
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "none" => BackupMode::None,
+            "sibling" => BackupMode::Sibling,
+            "numbered" => BackupMode::Numbered,
+            _ => BackupMode::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineNumbers {
+    Absolute,
+    Relative,
+    Hybrid,
+}
+
+impl LineNumbers {
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "absolute" => LineNumbers::Absolute,
+            "relative" => LineNumbers::Relative,
+            "hybrid" => LineNumbers::Hybrid,
+            _ => LineNumbers::Absolute,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub bindings: HashMap<Binding, String>,
+    pub bindings: BTreeMap<Binding, Vec<KeySequence>>,
     pub theme: Theme,
     pub editor: EditorConfig,
+    pub files: FilesConfig,
 }
 
 #[derive(Debug, Clone)]
@@ -107,13 +242,52 @@ pub struct Theme {
     pub cursor_flash: bool,
     pub cursor_flash_interval: f64,
     pub negative_color: String,
+    pub modified_color: String,
+    pub match_color: String,
+    pub line_number_color: String,
+    pub current_line_number_color: String,
     pub cursor_style: Cursor,
     pub scrollbar_style: ScrollBarStyle,
+    pub scrollbar_hover_color: String,
+    pub scrollbar_active_color: String,
+    pub scrollbar_auto_hide: bool,
+    pub minimap_view_color: String,
+    pub search_marker_color: String,
+    pub syntax: SyntaxTheme,
+}
+
+/// Token colours for syntax highlighting, from `[theme.syntax]`.
+#[derive(Debug, Clone)]
+pub struct SyntaxTheme {
+    pub comment: String,
+    pub string: String,
+    pub number: String,
+    pub keyword: String,
+    pub type_name: String,
+    pub function: String,
+    pub constant: String,
+    pub operator: String,
+    pub attribute: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct EditorConfig {
     pub scroll_multiplier: i32,
+    pub autosave_interval: f64,
+    pub large_file_threshold: f64,
+    pub persistent_undo: bool,
+    pub line_numbers: LineNumbers,
+    pub chord_timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilesConfig {
+    pub backup: BackupMode,
+    pub backup_dir: String,
+    pub backup_count: i32,
+    pub history: bool,
+    pub history_max_age_days: i32,
+    pub search_root: String,
 }
 
 impl Config {
@@ -135,19 +309,15 @@ impl Config {
     fn from_toml(value: toml::Value) -> Self {
         let default = Config::default();
 
-        let bindings = value.get("bindings")
-            .and_then(|v| v.as_table())
-            .map(|tbl| {
-                tbl.iter()
-                    .filter_map(|(k, v)| {
-                        Binding::from_str(k).map(|b| (b, v.as_str().unwrap_or("").to_string()))
-                    })
-                    .collect()
-            })
-            .unwrap_or_else(|| default.bindings.clone());
+        let bindings = match value.get("bindings").and_then(|v| v.as_table()) {
+            Some(tbl) => resolve_bindings(&user_bindings(tbl), &default.bindings),
+            None => default.bindings.clone(),
+        };
 
         let theme = value.get("theme").and_then(|t| t.as_table());
+        let syntax = theme.and_then(|t| t.get("syntax")).and_then(|t| t.as_table());
         let editor = value.get("editor").and_then(|t| t.as_table());
+        let files = value.get("files").and_then(|t| t.as_table());
 
         fn get_str(tbl: Option<&toml::value::Table>, key: &str, default: &str) -> String {
             tbl.and_then(|t| t.get(key)).and_then(|v| v.as_str()).unwrap_or(default).to_string()
@@ -164,6 +334,12 @@ impl Config {
         fn get_cursor(tbl: Option<&toml::value::Table>, key: &str, default: Cursor) -> Cursor {
             tbl.and_then(|t| t.get(key)).and_then(|v| v.as_str()).map(Cursor::from_str).unwrap_or(default)
         }
+        fn get_line_numbers(tbl: Option<&toml::value::Table>, key: &str, default: LineNumbers) -> LineNumbers {
+            tbl.and_then(|t| t.get(key)).and_then(|v| v.as_str()).map(LineNumbers::from_str).unwrap_or(default)
+        }
+        fn get_backup_mode(tbl: Option<&toml::value::Table>, key: &str, default: BackupMode) -> BackupMode {
+            tbl.and_then(|t| t.get(key)).and_then(|v| v.as_str()).map(BackupMode::from_str).unwrap_or(default)
+        }
         fn get_scrollbar_style(tbl: Option<&toml::value::Table>, key: &str, default: ScrollBarStyle) -> ScrollBarStyle {
             tbl.and_then(|t| t.get(key)).and_then(|v| v.as_str()).map(ScrollBarStyle::from_str).unwrap_or(default)
         }
@@ -179,25 +355,88 @@ impl Config {
                 cursor_flash: get_bool(theme, "cursor_flash", default.theme.cursor_flash),
                 cursor_flash_interval: get_f64(theme, "cursor_flash_interval", default.theme.cursor_flash_interval),
                 negative_color: get_str(theme, "negative_color", &default.theme.negative_color),
+                modified_color: get_str(theme, "modified_color", &default.theme.modified_color),
+                match_color: get_str(theme, "match_color", &default.theme.match_color),
+                line_number_color: get_str(theme, "line_number_color", &default.theme.line_number_color),
+                current_line_number_color: get_str(theme, "current_line_number_color", &default.theme.current_line_number_color),
                 cursor_style: get_cursor(theme, "cursor_style", default.theme.cursor_style),
                 scrollbar_style: get_scrollbar_style(theme, "scrollbar_style", default.theme.scrollbar_style),
+                scrollbar_hover_color: get_str(theme, "scrollbar_hover_color", &default.theme.scrollbar_hover_color),
+                scrollbar_active_color: get_str(theme, "scrollbar_active_color", &default.theme.scrollbar_active_color),
+                scrollbar_auto_hide: get_bool(theme, "scrollbar_auto_hide", default.theme.scrollbar_auto_hide),
+                minimap_view_color: get_str(theme, "minimap_view_color", &default.theme.minimap_view_color),
+                search_marker_color: get_str(theme, "search_marker_color", &default.theme.search_marker_color),
+                syntax: SyntaxTheme {
+                    comment: get_str(syntax, "comment", &default.theme.syntax.comment),
+                    string: get_str(syntax, "string", &default.theme.syntax.string),
+                    number: get_str(syntax, "number", &default.theme.syntax.number),
+                    keyword: get_str(syntax, "keyword", &default.theme.syntax.keyword),
+                    type_name: get_str(syntax, "type", &default.theme.syntax.type_name),
+                    function: get_str(syntax, "function", &default.theme.syntax.function),
+                    constant: get_str(syntax, "constant", &default.theme.syntax.constant),
+                    operator: get_str(syntax, "operator", &default.theme.syntax.operator),
+                    attribute: get_str(syntax, "attribute", &default.theme.syntax.attribute),
+                },
             },
             editor: EditorConfig {
                 scroll_multiplier: get_i32(editor, "scroll_multiplier", default.editor.scroll_multiplier),
+                autosave_interval: get_f64(editor, "autosave_interval", default.editor.autosave_interval),
+                large_file_threshold: get_f64(editor, "large_file_threshold", default.editor.large_file_threshold),
+                persistent_undo: get_bool(editor, "persistent_undo", default.editor.persistent_undo),
+                line_numbers: get_line_numbers(editor, "line_numbers", default.editor.line_numbers),
+                chord_timeout: get_f64(editor, "chord_timeout", default.editor.chord_timeout),
+            },
+            files: FilesConfig {
+                backup: get_backup_mode(files, "backup", default.files.backup),
+                backup_dir: get_str(files, "backup_dir", &default.files.backup_dir),
+                backup_count: get_i32(files, "backup_count", default.files.backup_count),
+                history: get_bool(files, "history", default.files.history),
+                history_max_age_days: get_i32(files, "history_max_age_days", default.files.history_max_age_days),
+                search_root: get_str(files, "search_root", &default.files.search_root),
             },
         }
     }
 
     pub fn default() -> Self {
         Self {
-            bindings: HashMap::from([
-                (Binding::Save, "Ctrl+S".into()),
-                (Binding::Quit, "Ctrl+Q".into()),
-                (Binding::Reload, "Ctrl+R".into()),
-                (Binding::MoveLineUp, "Alt+Up".into()),
-                (Binding::MoveLineDown, "Alt+Down".into()),
-                (Binding::OpenFile, "Ctrl+O".into()),
-            ]),
+            bindings: [
+                (Binding::Save, "Ctrl+S"),
+                (Binding::SaveAs, "Ctrl+Shift+S"),
+                (Binding::Quit, "Ctrl+Q"),
+                (Binding::Reload, "Ctrl+R"),
+                (Binding::MoveLineUp, "Alt+Up"),
+                (Binding::MoveLineDown, "Alt+Down"),
+                (Binding::OpenFile, "Ctrl+O"),
+                (Binding::ConvertEncoding, "Ctrl+Shift+E"),
+                (Binding::ConvertLineEndings, "Ctrl+Shift+L"),
+                (Binding::FileHistory, "Ctrl+Shift+H"),
+                (Binding::Undo, "Ctrl+Z"),
+                (Binding::Redo, "Ctrl+Shift+Z"),
+                (Binding::UndoTree, "Ctrl+Shift+U"),
+                (Binding::Find, "Ctrl+F"),
+                (Binding::Replace, "Ctrl+H"),
+                (Binding::FindNext, "Ctrl+G"),
+                (Binding::FindPrevious, "Ctrl+Shift+G"),
+                (Binding::ReplaceNext, "Ctrl+Shift+R"),
+                (Binding::ReplaceAll, "Ctrl+Alt+R"),
+                (Binding::ToggleRegex, "Alt+R"),
+                (Binding::ToggleCase, "Alt+C"),
+                (Binding::ToggleWholeWord, "Alt+W"),
+                (Binding::ToggleInSelection, "Alt+S"),
+                (Binding::ProjectSearch, "Ctrl+Shift+F"),
+                (Binding::NextBuffer, "Alt+Right"),
+                (Binding::PreviousBuffer, "Alt+Left"),
+                (Binding::CloseBuffer, "Ctrl+W"),
+                (Binding::BufferSwitcher, "Ctrl+P"),
+                (Binding::SplitHorizontal, "Ctrl+Alt+H"),
+                (Binding::SplitVertical, "Ctrl+Alt+V"),
+                (Binding::ClosePane, "Ctrl+Alt+W"),
+                (Binding::NextPane, "Ctrl+Alt+N"),
+                (Binding::PreviousPane, "Ctrl+Alt+P"),
+            ]
+            .into_iter()
+            .map(|(binding, spec)| (binding, vec![KeySequence::parse(spec).expect("default bindings parse")]))
+            .collect(),
             theme: Theme {
                 background: "#1e1e1e".into(),
                 foreground: "#c0c0c0".into(),
@@ -207,74 +446,116 @@ impl Config {
                 cursor_flash: true,
                 cursor_flash_interval: 0.5,
                 negative_color: "#FF0000".into(),
+                modified_color: "#E5C07B".into(),
+                match_color: "#3E4451".into(),
+                line_number_color: "#5C6370".into(),
+                current_line_number_color: "#C0C0C0".into(),
                 cursor_style: Cursor::Simple,
                 scrollbar_style: ScrollBarStyle::Rounded,
+                scrollbar_hover_color: "#D8D8D8".into(),
+                scrollbar_active_color: "#FFFFFF".into(),
+                scrollbar_auto_hide: false,
+                minimap_view_color: "#2C313A".into(),
+                search_marker_color: "#E5C07B".into(),
+                syntax: SyntaxTheme {
+                    comment: "#5C6370".into(),
+                    string: "#98C379".into(),
+                    number: "#D19A66".into(),
+                    keyword: "#C678DD".into(),
+                    type_name: "#E5C07B".into(),
+                    function: "#61AFEF".into(),
+                    constant: "#56B6C2".into(),
+                    operator: "#ABB2BF".into(),
+                    attribute: "#E06C75".into(),
+                },
             },
             editor: EditorConfig {
                 scroll_multiplier: 3,
+                autosave_interval: 30.0,
+                large_file_threshold: 50.0,
+                persistent_undo: false,
+                line_numbers: LineNumbers::Absolute,
+                chord_timeout: 2.0,
+            },
+            files: FilesConfig {
+                backup: BackupMode::None,
+                backup_dir: "~/.local/share/skrift/backup".into(),
+                backup_count: 5,
+                history: true,
+                history_max_age_days: 30,
+                search_root: "".into(),
             },
         }
     }
+}
 
-    pub fn shortcut_matches(shortcut: &str) -> bool {
-        let parts: Vec<&str> = shortcut.split('+').collect();
-        let mut ctrl = false;
-        let mut alt = false;
-        let mut shift = false;
-        let mut key: Option<Key> = None;
-        let mut char_key: Option<char> = None;
-
-        for part in parts {
-            match part {
-                "Ctrl" => ctrl = true,
-                "Alt" => alt = true,
-                "Shift" => shift = true,
-                "Left" => key = Some(Key::Left),
-                "Right" => key = Some(Key::Right),
-                "Up" => key = Some(Key::Up),
-                "Down" => key = Some(Key::Down),
-                k if k.len() == 1 => {
-                    char_key = Some(k.chars().next().unwrap().to_ascii_lowercase());
+/// Reads `[bindings]`. Each action takes a key spec or a list of them, and `"none"` unbinds
+/// it. Actions left out keep their default keys, as do ones where no spec could be read.
+fn user_bindings(tbl: &toml::value::Table) -> BTreeMap<Binding, Vec<KeySequence>> {
+    let mut bindings = BTreeMap::new();
+    for (name, value) in tbl {
+        let Some(binding) = Binding::from_str(name) else {
+            eprintln!("Ignoring unknown binding \"{}\"", name);
+            continue;
+        };
+        let specs: Vec<&str> = match value {
+            toml::Value::String(spec) => vec![spec.as_str()],
+            toml::Value::Array(specs) => specs.iter().filter_map(|v| v.as_str()).collect(),
+            _ => {
+                eprintln!("Ignoring binding {}: expected a key spec or a list of them", name);
+                continue;
+            }
+        };
+        let mut sequences = Vec::new();
+        let mut failed = false;
+        for spec in specs.iter().filter(|spec| !spec.trim().eq_ignore_ascii_case("none")) {
+            match KeySequence::parse(spec) {
+                Ok(sequence) if !sequences.contains(&sequence) => sequences.push(sequence),
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("Ignoring binding {}: {}", name, err);
+                    failed = true;
                 }
-                _ => {}
             }
         }
-
-        let event_key = app::event_key();
-        let event_state = app::event_state();
-
-        if matches!(
-            event_key,
-            Key::ControlL | Key::ControlR | Key::AltL | Key::AltR | Key::ShiftL | Key::ShiftR
-        ) {
-            return false;
+        if sequences.is_empty() && failed {
+            continue;
         }
+        bindings.insert(binding, sequences);
+    }
+    bindings
+}
 
-        if let Some(k) = key {
-            return event_key == k
-                && ctrl == event_state.contains(Shortcut::Ctrl)
-                && alt == event_state.contains(Shortcut::Alt)
-                && shift == event_state.contains(Shortcut::Shift);
-        }
+/// Puts `user` over `defaults`, dropping and reporting every key sequence that clashes
+/// with one already taken: the same keys, or keys that start the other's chord. Keys from
+/// the config are taken first, then the defaults, each in the order actions are declared.
+fn resolve_bindings(
+    user: &BTreeMap<Binding, Vec<KeySequence>>,
+    defaults: &BTreeMap<Binding, Vec<KeySequence>>,
+) -> BTreeMap<Binding, Vec<KeySequence>> {
+    let from_user = user.iter().flat_map(|(binding, sequences)| sequences.iter().map(move |s| (binding, s)));
+    let from_defaults = defaults
+        .iter()
+        .filter(|(binding, _)| !user.contains_key(binding))
+        .flat_map(|(binding, sequences)| sequences.iter().map(move |s| (binding, s)));
 
-        if let Some(c) = char_key {
-            if matches!(
-                event_key,
-                Key::Left | Key::Right | Key::Up | Key::Down
-                    | Key::F1 | Key::F2 | Key::F3 | Key::F4 | Key::F5 | Key::F6
-                    | Key::F7 | Key::F8 | Key::F9 | Key::F10 | Key::F11 | Key::F12
-            ) {
-                return false;
-            }
-            let event_char = event_key.to_char().map(|ch| ch.to_ascii_lowercase());
-            return event_char == Some(c)
-                && ctrl == event_state.contains(Shortcut::Ctrl)
-                && alt == event_state.contains(Shortcut::Alt)
-                && shift == event_state.contains(Shortcut::Shift);
+    let mut resolved: BTreeMap<Binding, Vec<KeySequence>> = BTreeMap::new();
+    let mut taken: Vec<(&Binding, &KeySequence)> = Vec::new();
+    for (binding, sequence) in from_user.chain(from_defaults) {
+        if let Some((other, kept)) = taken.iter().find(|(_, kept)| kept.overlaps(sequence)) {
+            eprintln!(
+                "Key conflict: \"{}\" for {} clashes with \"{}\" for {}, which wins",
+                sequence,
+                binding.name(),
+                kept,
+                other.name()
+            );
+            continue;
         }
-
-        false
+        taken.push((binding, sequence));
+        resolved.entry(binding.clone()).or_default().push(sequence.clone());
     }
+    resolved
 }
 
 impl Theme {
@@ -283,3 +564,79 @@ impl Theme {
         Color::from_hex(u32::from_str_radix(hex, 16).unwrap_or(0xFFFFFF))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_names_round_trip() {
+        for binding in Binding::ALL {
+            assert_eq!(Binding::from_str(binding.name()), Some(binding.clone()));
+        }
+        assert_eq!(Binding::SplitHorizontal.name(), "split_horizontal");
+        assert_eq!(Binding::from_str("SplitHorizontal"), None);
+    }
+
+    fn resolved(config: &str) -> BTreeMap<Binding, Vec<KeySequence>> {
+        Config::from_toml(toml::from_str(config).unwrap()).bindings
+    }
+
+    fn keys(bindings: &BTreeMap<Binding, Vec<KeySequence>>, binding: Binding) -> Vec<String> {
+        bindings.get(&binding).map(|sequences| sequences.iter().map(|s| s.to_string()).collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn none_removes_a_default() {
+        let bindings = resolved("[bindings]\nsave = \"none\"\nquit = [\"None\"]\n");
+        assert!(keys(&bindings, Binding::Save).is_empty());
+        assert!(keys(&bindings, Binding::Quit).is_empty());
+        assert_eq!(keys(&bindings, Binding::SaveAs), ["Ctrl+Shift+S"]);
+    }
+
+    #[test]
+    fn lists_of_keys() {
+        let bindings = resolved("[bindings]\nundo = [\"Ctrl+Z\", \"Alt+Backspace\", \"ctrl+z\"]\n");
+        assert_eq!(keys(&bindings, Binding::Undo), ["Ctrl+Z", "Alt+Backspace"]);
+    }
+
+    #[test]
+    fn unreadable_keys_keep_the_default() {
+        let bindings = resolved("[bindings]\nsave = \"Hyper+S\"\nno_such_action = \"Ctrl+J\"\nfind = 5\n");
+        assert_eq!(keys(&bindings, Binding::Save), ["Ctrl+S"]);
+        assert_eq!(keys(&bindings, Binding::Find), ["Ctrl+F"]);
+        assert_eq!(bindings, Config::default().bindings);
+    }
+
+    #[test]
+    fn user_keys_beat_defaults() {
+        let bindings = resolved("[bindings]\nfind = \"Ctrl+S\"\n");
+        assert_eq!(keys(&bindings, Binding::Find), ["Ctrl+S"]);
+        assert!(keys(&bindings, Binding::Save).is_empty(), "the default loses its only key");
+        assert!(!bindings.values().flatten().any(|s| s.to_string() == "Ctrl+F"), "the replaced default is unbound");
+    }
+
+    #[test]
+    fn clashing_user_keys_go_to_the_action_declared_first() {
+        for config in [
+            "[bindings]\nquit = \"Ctrl+J\"\nsave = \"Ctrl+J\"\n",
+            "[bindings]\nsave = \"Ctrl+J\"\nquit = \"Ctrl+J\"\n",
+        ] {
+            let bindings = resolved(config);
+            assert_eq!(keys(&bindings, Binding::Save), ["Ctrl+J"]);
+            assert!(keys(&bindings, Binding::Quit).is_empty());
+        }
+    }
+
+    #[test]
+    fn chords_clash_with_their_prefixes() {
+        let bindings = resolved("[bindings]\nreload = \"Ctrl+K Ctrl+R\"\nopen_file = [\"Ctrl+K\", \"Ctrl+J\"]\n");
+        assert_eq!(keys(&bindings, Binding::Reload), ["Ctrl+K Ctrl+R"]);
+        assert_eq!(keys(&bindings, Binding::OpenFile), ["Ctrl+J"]);
+
+        // A user chord starting with a default key takes it from the default.
+        let taken = resolved("[bindings]\nsplit_vertical = \"Ctrl+S Ctrl+V\"\n");
+        assert_eq!(keys(&taken, Binding::SplitVertical), ["Ctrl+S Ctrl+V"]);
+        assert!(keys(&taken, Binding::Save).is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::Theme;
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    enums::{CallbackTrigger, Event, FrameType, Key},
+    input::Input,
+    prelude::*,
+    window::Window,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Scores `candidate` against `query` when every query character appears in it in
+/// order. Consecutive characters and characters at the start of a word score higher.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_ascii_lowercase();
+        let found = (pos..candidate.len()).find(|&i| candidate[i].to_ascii_lowercase() == q)?;
+        let word_start = found == 0 || !candidate[found - 1].is_alphanumeric();
+        score += match last {
+            Some(prev) if prev + 1 == found => 8,
+            _ if word_start => 6,
+            _ => 1,
+        };
+        score -= (found - pos).min(8) as i32;
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Indices of `entries` that match `query`, best first.
+fn filter(entries: &[(String, String)], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i32, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (name, path))| {
+            let by_name = fuzzy_score(query, name).map(|s| s + 4);
+            let by_path = fuzzy_score(query, path);
+            by_name.max(by_path).map(|s| (s, i))
+        })
+        .collect();
+    scored.sort_by_key(|&(score, i)| (-score, i));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Lists the open buffers as `(name, path)` pairs, narrowed down as the user types,
+/// and returns the index of the one picked.
+pub fn buffer_switcher(entries: &[(String, String)], active: usize, theme: &Theme) -> Option<usize> {
+    let background = theme.color_from_str(&theme.background);
+    let foreground = theme.color_from_str(&theme.foreground);
+
+    let mut wind = Window::default().with_size(560, 360).with_label("Switch buffer");
+    wind.set_color(background);
+
+    let mut input = Input::new(0, 0, 560, 30, "");
+    input.set_trigger(CallbackTrigger::Changed);
+    input.set_color(background.lighter());
+    input.set_text_color(foreground);
+    input.set_cursor_color(foreground);
+    input.set_text_size(theme.font_size);
+    input.set_frame(FrameType::FlatBox);
+
+    let mut browser = HoldBrowser::new(0, 30, 560, 330, "");
+    browser.set_color(background);
+    browser.set_selection_color(theme.color_from_str(&theme.selection_color));
+    browser.set_frame(FrameType::FlatBox);
+    browser.set_text_size(theme.font_size);
+
+    let shown: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(Vec::new()));
+    let chosen: Rc<RefCell<Option<usize>>> = Rc::new(RefCell::new(None));
+
+    let refill = {
+        let browser = browser.clone();
+        let shown = shown.clone();
+        let entries = entries.to_vec();
+        move |query: &str| {
+            let mut browser = browser.clone();
+            browser.clear();
+            let mut order = filter(&entries, query);
+            if query.trim().is_empty() {
+                // Switching to the current buffer does nothing, so it goes last.
+                order.retain(|&i| i != active);
+                order.push(active);
+            }
+            for &i in &order {
+                let (name, path) = &entries[i];
+                browser.add(&format!("@C{}@.{}    {}", foreground.bits(), name, path));
+            }
+            if !order.is_empty() {
+                browser.select(1);
+            }
+            *shown.borrow_mut() = order;
+        }
+    };
+    refill("");
+
+    let choose = {
+        let wind = wind.clone();
+        let browser = browser.clone();
+        let shown = shown.clone();
+        let chosen = chosen.clone();
+        move || {
+            let line = browser.value();
+            if line > 0 {
+                *chosen.borrow_mut() = shown.borrow().get(line as usize - 1).copied();
+                wind.clone().hide();
+            }
+        }
+    };
+
+    input.set_callback({
+        let refill = refill.clone();
+        move |i| refill(&i.value())
+    });
+    input.handle({
+        let mut wind = wind.clone();
+        let mut browser = browser.clone();
+        let choose = choose.clone();
+        move |_, ev| {
+            if ev != Event::KeyDown {
+                return false;
+            }
+            let line = browser.value();
+            match app::event_key() {
+                Key::Up if line > 1 => browser.select(line - 1),
+                Key::Down if line < browser.size() => browser.select(line + 1),
+                Key::Up | Key::Down => {}
+                Key::Enter | Key::KPEnter => choose(),
+                Key::Escape => wind.hide(),
+                _ => return false,
+            }
+            true
+        }
+    });
+    browser.set_callback(move |_| {
+        if app::event_clicks() {
+            choose();
+        }
+    });
+
+    wind.resizable(&browser);
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+    let _ = input.take_focus();
+    while wind.shown() {
+        app::wait();
+    }
+    chosen.borrow_mut().take()
+}
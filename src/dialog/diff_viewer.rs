@@ -0,0 +1,68 @@
+use crate::config::Theme;
+use fltk::{
+    app,
+    button::Button,
+    enums::{Color, Font},
+    prelude::*,
+    text::{StyleTableEntry, TextBuffer, TextDisplay},
+    window::Window,
+};
+
+/// Fills `display` with a unified diff, coloring added, removed and hunk header lines.
+pub fn set_diff_text(display: &mut TextDisplay, diff: &str, theme: &Theme) {
+    let foreground = theme.color_from_str(&theme.foreground);
+    let font = Font::by_name(&theme.font_family);
+
+    let mut text = TextBuffer::default();
+    let mut style = TextBuffer::default();
+    let body = if diff.is_empty() { "No differences.\n" } else { diff };
+    text.set_text(body);
+    let mut styles = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        let code = match line.as_bytes().first() {
+            Some(b'+') => 'B',
+            Some(b'-') => 'C',
+            Some(b'@') => 'D',
+            _ => 'A',
+        };
+        styles.extend(std::iter::repeat_n(code, line.len()));
+    }
+    style.set_text(&styles);
+
+    let entry = |color: Color| StyleTableEntry { color, font, size: theme.font_size };
+    display.set_buffer(text);
+    display.set_highlight_data(
+        style,
+        vec![
+            entry(foreground),
+            entry(Color::Green),
+            entry(theme.color_from_str(&theme.negative_color)),
+            entry(theme.color_from_str(&theme.selection_color)),
+        ],
+    );
+}
+
+pub fn show_diff(title: &str, diff: &str, theme: &Theme) {
+    let background = theme.color_from_str(&theme.background);
+
+    let mut wind = Window::default().with_size(720, 480).with_label(title);
+    wind.set_color(background);
+    let mut display = TextDisplay::new(0, 0, 720, 440, "");
+    display.set_color(background);
+    display.set_frame(fltk::enums::FrameType::FlatBox);
+    set_diff_text(&mut display, diff, theme);
+
+    let mut close = Button::new(620, 448, 90, 24, "Close");
+    close.set_callback({
+        let mut wind = wind.clone();
+        move |_| wind.hide()
+    });
+
+    wind.resizable(&display);
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+    while wind.shown() {
+        app::wait();
+    }
+}
@@ -0,0 +1,36 @@
+use fltk::dialog;
+
+pub enum ExternalChangeChoice {
+    Reload,
+    KeepMine,
+    ShowDiff,
+}
+
+pub fn external_change_prompt(file_name: &str) -> ExternalChangeChoice {
+    let message = format!(
+        "{} was changed on disk and has unsaved changes in Skrift.",
+        file_name
+    );
+    match dialog::choice2_default(&message, "Keep Mine", "Reload", "Show Diff") {
+        Some(1) => ExternalChangeChoice::Reload,
+        Some(2) => ExternalChangeChoice::ShowDiff,
+        _ => ExternalChangeChoice::KeepMine,
+    }
+}
+
+pub fn overwrite_prompt(file_name: &str) -> bool {
+    let message = format!(
+        "{} was changed on disk since it was opened. Overwrite it?",
+        file_name
+    );
+    matches!(dialog::choice2_default(&message, "Cancel", "Overwrite", ""), Some(1))
+}
+
+/// Asks before saving over a file whose bytes didn't all survive being read.
+pub fn lossy_overwrite_prompt(file_name: &str) -> bool {
+    let message = format!(
+        "{} has bytes that could not be read as text. Saving replaces them. Save anyway?",
+        file_name
+    );
+    matches!(dialog::choice2_default(&message, "Cancel", "Save", ""), Some(1))
+}
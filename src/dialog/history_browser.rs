@@ -0,0 +1,102 @@
+use crate::config::Theme;
+use crate::diff;
+use crate::dialog::diff_viewer::set_diff_text;
+use crate::history::Snapshot;
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    button::Button,
+    enums::FrameType,
+    prelude::*,
+    text::TextDisplay,
+    window::Window,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Lists the saved snapshots of a file next to a diff against the current buffer.
+/// Returns the contents of the snapshot the user chose to restore.
+pub fn history_browser(file_name: &str, snapshots: &[Snapshot], current: &str, theme: &Theme) -> Option<String> {
+    let background = theme.color_from_str(&theme.background);
+    let foreground = theme.color_from_str(&theme.foreground);
+
+    let mut wind = Window::default()
+        .with_size(960, 540)
+        .with_label(&format!("History - {}", file_name));
+    wind.set_color(background);
+
+    let mut browser = HoldBrowser::new(0, 0, 280, 500, "");
+    browser.set_color(background);
+    browser.set_selection_color(theme.color_from_str(&theme.selection_color));
+    browser.set_frame(FrameType::FlatBox);
+    browser.set_text_size(theme.font_size);
+    for snapshot in snapshots {
+        browser.add(&format!("@C{}@.{}", foreground.bits(), snapshot.label()));
+    }
+
+    let mut display = TextDisplay::new(280, 0, 680, 500, "");
+    display.set_color(background);
+    display.set_frame(FrameType::FlatBox);
+    set_diff_text(&mut display, "", theme);
+
+    let selected: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    let restored: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+    browser.set_callback({
+        let mut display = display.clone();
+        let selected = selected.clone();
+        let snapshots = snapshots.to_vec();
+        let current = current.to_string();
+        let theme = theme.clone();
+        move |b| {
+            let line = b.value();
+            if line < 1 {
+                return;
+            }
+            let Some(snapshot) = snapshots.get(line as usize - 1) else { return };
+            match snapshot.read() {
+                Ok(text) => {
+                    set_diff_text(&mut display, &diff::unified(&text, &current, 3), &theme);
+                    *selected.borrow_mut() = Some(text);
+                }
+                Err(err) => {
+                    eprintln!("Failed to read snapshot {}: {}", snapshot.path.display(), err);
+                    *selected.borrow_mut() = None;
+                }
+            }
+        }
+    });
+
+    let mut restore = Button::new(760, 508, 90, 24, "Restore");
+    restore.set_callback({
+        let mut wind = wind.clone();
+        let selected = selected.clone();
+        let restored = restored.clone();
+        move |_| {
+            if let Some(text) = selected.borrow_mut().take() {
+                *restored.borrow_mut() = Some(text);
+                wind.hide();
+            }
+        }
+    });
+
+    let mut close = Button::new(860, 508, 90, 24, "Close");
+    close.set_callback({
+        let mut wind = wind.clone();
+        move |_| wind.hide()
+    });
+
+    if !snapshots.is_empty() {
+        browser.select(1);
+        browser.do_callback();
+    }
+
+    wind.resizable(&display);
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+    while wind.shown() {
+        app::wait();
+    }
+    restored.borrow_mut().take()
+}
@@ -0,0 +1,8 @@
+use fltk::{app, menu::MenuItem};
+
+pub fn popup_choice(choices: &[&'static str]) -> Option<usize> {
+    let menu = MenuItem::new(choices);
+    let picked = menu.popup(app::event_x(), app::event_y())?;
+    let label = picked.label()?;
+    choices.iter().position(|c| *c == label)
+}
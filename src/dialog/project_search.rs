@@ -0,0 +1,284 @@
+use crate::config::Theme;
+use crate::diff;
+use crate::dialog::diff_viewer::show_diff;
+use crate::project_search::{self, FileMatches, FileReplace};
+use crate::search::{Search, SearchOptions};
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    button::{Button, CheckButton},
+    dialog,
+    enums::{Align, CallbackTrigger, FrameType},
+    frame::Frame,
+    input::Input,
+    prelude::*,
+    window::Window,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub enum ProjectAction {
+    /// Open the file at the byte range of a match.
+    Open(PathBuf, usize, usize),
+    Replace(Vec<FileReplace>),
+    UndoReplace,
+}
+
+/// A file and the byte range of one match in it.
+type Target = (PathBuf, usize, usize);
+
+/// What was typed into the panel last time, so reopening it picks up where it left off.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectQuery {
+    pub query: String,
+    pub replace: String,
+    pub options: SearchOptions,
+}
+
+/// Searches the files under `root` and lists the matches grouped by file. `open` is the
+/// path and text of the buffer in the editor. `can_undo` enables undoing the last replace.
+pub fn project_search(
+    root: &Path,
+    open: Option<(&Path, &str)>,
+    last: &mut ProjectQuery,
+    can_undo: bool,
+    theme: &Theme,
+) -> Option<ProjectAction> {
+    let background = theme.color_from_str(&theme.background);
+    let foreground = theme.color_from_str(&theme.foreground);
+
+    let mut wind = Window::default()
+        .with_size(960, 600)
+        .with_label(&format!("Search - {}", root.display()));
+    wind.set_color(background);
+
+    let mut query = Input::new(80, 8, 560, 24, "Find");
+    query.set_value(&last.query);
+    query.set_trigger(CallbackTrigger::EnterKey);
+    let mut regex = CheckButton::new(650, 8, 70, 24, "Regex");
+    regex.set_value(last.options.regex);
+    let mut case_sensitive = CheckButton::new(720, 8, 70, 24, "Case");
+    case_sensitive.set_value(last.options.case_sensitive);
+    let mut whole_word = CheckButton::new(790, 8, 70, 24, "Word");
+    whole_word.set_value(last.options.whole_word);
+    let mut search_button = Button::new(862, 8, 90, 24, "Search");
+
+    let mut replace = Input::new(80, 40, 560, 24, "Replace");
+    replace.set_value(&last.replace);
+    let mut preview = Button::new(650, 40, 90, 24, "Preview");
+    let mut replace_all = Button::new(750, 40, 100, 24, "Replace all");
+    let mut undo = Button::new(862, 40, 90, 24, "Undo last");
+    if !can_undo {
+        undo.deactivate();
+    }
+    for label in [&mut regex, &mut case_sensitive, &mut whole_word] {
+        label.set_label_color(foreground);
+    }
+    for input in [&mut query, &mut replace] {
+        input.set_label_color(foreground);
+        input.set_color(background.lighter());
+        input.set_text_color(foreground);
+        input.set_cursor_color(foreground);
+        input.set_frame(FrameType::FlatBox);
+    }
+
+    let mut browser = HoldBrowser::new(0, 72, 960, 488, "");
+    browser.set_color(background);
+    browser.set_selection_color(theme.color_from_str(&theme.selection_color));
+    browser.set_frame(FrameType::FlatBox);
+    browser.set_text_size(theme.font_size);
+
+    let mut status = Frame::new(8, 568, 740, 24, "");
+    status.set_align(Align::Left | Align::Inside);
+    status.set_label_color(foreground);
+    let mut close = Button::new(862, 568, 90, 24, "Close");
+
+    let results: Rc<RefCell<Vec<FileMatches>>> = Rc::new(RefCell::new(Vec::new()));
+    // The match behind each browser line; file headings have none.
+    let targets: Rc<RefCell<Vec<Option<Target>>>> = Rc::new(RefCell::new(Vec::new()));
+    let chosen: Rc<RefCell<Option<ProjectAction>>> = Rc::new(RefCell::new(None));
+    let open_file = open.map(|(path, text)| (path.to_path_buf(), text.to_string()));
+
+    let current_search = {
+        let query = query.clone();
+        let regex = regex.clone();
+        let case_sensitive = case_sensitive.clone();
+        let whole_word = whole_word.clone();
+        let status = status.clone();
+        move || -> Option<Search> {
+            let options = SearchOptions {
+                regex: regex.value(),
+                case_sensitive: case_sensitive.value(),
+                whole_word: whole_word.value(),
+                in_selection: false,
+            };
+            if query.value().is_empty() {
+                return None;
+            }
+            match Search::new(&query.value(), options) {
+                Ok(search) => Some(search),
+                Err(err) => {
+                    status.clone().set_label(&err);
+                    None
+                }
+            }
+        }
+    };
+
+    let run_search: Rc<dyn Fn()> = Rc::new({
+        let browser = browser.clone();
+        let status = status.clone();
+        let results = results.clone();
+        let targets = targets.clone();
+        let open_file = open_file.clone();
+        let current_search = current_search.clone();
+        let root = root.to_path_buf();
+        move || {
+            let (mut browser, mut status) = (browser.clone(), status.clone());
+            browser.clear();
+            targets.borrow_mut().clear();
+            results.borrow_mut().clear();
+            let Some(search) = current_search() else { return };
+            status.set_label("Searching...");
+            app::flush();
+            let open = open_file.as_ref().map(|(path, text)| (path.as_path(), text.as_str()));
+            let (found, cut_short) = project_search::search(&root, &search, open);
+            let mut targets = targets.borrow_mut();
+            let mut count = 0;
+            for file in &found {
+                let name = file.path.strip_prefix(&root).unwrap_or(&file.path).display();
+                browser.add(&format!("@C{}@b@.{} ({})", foreground.bits(), name, file.matches.len()));
+                targets.push(None);
+                for m in &file.matches {
+                    browser.add(&format!("@C{}@.    {}: {}", foreground.bits(), m.line + 1, m.preview));
+                    targets.push(Some((file.path.clone(), m.start, m.end)));
+                }
+                count += file.matches.len();
+            }
+            let mut summary = match (count, found.len()) {
+                (0, _) => "No results".to_string(),
+                (count, 1) => format!("{} matches in 1 file", count),
+                (count, files) => format!("{} matches in {} files", count, files),
+            };
+            if cut_short {
+                summary.push_str(" (search stopped early; results are incomplete)");
+            }
+            status.set_label(&summary);
+            *results.borrow_mut() = found;
+        }
+    });
+
+    let plan = {
+        let results = results.clone();
+        let replace = replace.clone();
+        let open_file = open_file.clone();
+        let current_search = current_search.clone();
+        move || -> Vec<FileReplace> {
+            let Some(search) = current_search() else { return Vec::new() };
+            let open = open_file.as_ref().map(|(path, text)| (path.as_path(), text.as_str()));
+            project_search::plan_replace(&results.borrow(), &search, &replace.value(), open)
+        }
+    };
+
+    query.set_callback({
+        let run_search = run_search.clone();
+        move |_| run_search()
+    });
+    search_button.set_callback({
+        let run_search = run_search.clone();
+        move |_| run_search()
+    });
+
+    browser.set_callback({
+        let mut wind = wind.clone();
+        let targets = targets.clone();
+        let chosen = chosen.clone();
+        move |b| {
+            let line = b.value();
+            if line < 1 {
+                return;
+            }
+            if let Some(Some((path, start, end))) = targets.borrow().get(line as usize - 1) {
+                *chosen.borrow_mut() = Some(ProjectAction::Open(path.clone(), *start, *end));
+                wind.hide();
+            }
+        }
+    });
+
+    preview.set_callback({
+        let plan = plan.clone();
+        let root = root.to_path_buf();
+        let theme = theme.clone();
+        move |_| {
+            let files = plan();
+            if files.is_empty() {
+                return;
+            }
+            let mut text = String::new();
+            for file in &files {
+                let name = file.path.strip_prefix(&root).unwrap_or(&file.path).display();
+                text.push_str(&format!("=== {} ({} replacements)\n", name, file.count));
+                text.push_str(&diff::unified(&file.original, &file.replaced, 2));
+                text.push('\n');
+            }
+            show_diff("Replace preview", &text, &theme);
+        }
+    });
+
+    replace_all.set_callback({
+        let mut wind = wind.clone();
+        let chosen = chosen.clone();
+        move |_| {
+            let files = plan();
+            if files.is_empty() {
+                return;
+            }
+            let count: usize = files.iter().map(|file| file.count).sum();
+            let message = format!("Replace {} matches in {} files?", count, files.len());
+            if matches!(dialog::choice2_default(&message, "Cancel", "Replace", ""), Some(1)) {
+                *chosen.borrow_mut() = Some(ProjectAction::Replace(files));
+                wind.hide();
+            }
+        }
+    });
+
+    undo.set_callback({
+        let mut wind = wind.clone();
+        let chosen = chosen.clone();
+        move |_| {
+            *chosen.borrow_mut() = Some(ProjectAction::UndoReplace);
+            wind.hide();
+        }
+    });
+
+    close.set_callback({
+        let mut wind = wind.clone();
+        move |_| wind.hide()
+    });
+
+    if !last.query.is_empty() {
+        run_search();
+    }
+
+    wind.resizable(&browser);
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+    let _ = query.take_focus();
+    while wind.shown() {
+        app::wait();
+    }
+
+    *last = ProjectQuery {
+        query: query.value(),
+        replace: replace.value(),
+        options: SearchOptions {
+            regex: regex.value(),
+            case_sensitive: case_sensitive.value(),
+            whole_word: whole_word.value(),
+            in_selection: false,
+        },
+    };
+    chosen.borrow_mut().take()
+}
@@ -0,0 +1,19 @@
+use fltk::dialog;
+
+pub enum RecoverChoice {
+    Recover,
+    Delete,
+    Ignore,
+}
+
+pub fn recover_swap_prompt(file_name: &str) -> RecoverChoice {
+    let message = format!(
+        "Skrift found unsaved changes to {} from a previous session.",
+        file_name
+    );
+    match dialog::choice2_default(&message, "Ignore", "Recover", "Delete") {
+        Some(1) => RecoverChoice::Recover,
+        Some(2) => RecoverChoice::Delete,
+        _ => RecoverChoice::Ignore,
+    }
+}
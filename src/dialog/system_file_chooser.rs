@@ -1,7 +1,24 @@
 use rfd::FileDialog;
+use std::path::Path;
 
 pub fn system_file_chooser() -> Option<String> {
     FileDialog::new()
         .pick_file()
         .map(|path| path.display().to_string())
-}
\ No newline at end of file
+}
+
+pub fn system_save_file_chooser(current_path: &str) -> Option<String> {
+    let current = Path::new(current_path);
+    let mut dialog = FileDialog::new();
+    let dir = std::fs::canonicalize(current)
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        .or_else(|| current.parent().filter(|d| d.is_dir()).map(|d| d.to_path_buf()));
+    if let Some(dir) = dir {
+        dialog = dialog.set_directory(dir);
+    }
+    if let Some(name) = current.file_name().and_then(|n| n.to_str()) {
+        dialog = dialog.set_file_name(name);
+    }
+    dialog.save_file().map(|path| path.display().to_string())
+}
@@ -0,0 +1,83 @@
+use crate::config::Theme;
+use fltk::{
+    app,
+    browser::HoldBrowser,
+    button::Button,
+    enums::FrameType,
+    prelude::*,
+    window::Window,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Shows the undo tree as an indented list, one line per step, and returns the
+/// id of the step the user wants to go to.
+pub fn undo_tree_browser(entries: &[(usize, usize, String)], theme: &Theme) -> Option<usize> {
+    let background = theme.color_from_str(&theme.background);
+    let foreground = theme.color_from_str(&theme.foreground);
+
+    let mut wind = Window::default().with_size(520, 480).with_label("Undo tree");
+    wind.set_color(background);
+
+    let mut browser = HoldBrowser::new(0, 0, 520, 440, "");
+    browser.set_color(background);
+    browser.set_selection_color(theme.color_from_str(&theme.selection_color));
+    browser.set_frame(FrameType::FlatBox);
+    browser.set_text_size(theme.font_size);
+    let mut current_line = 0;
+    for (line, (_, depth, label)) in entries.iter().enumerate() {
+        if label.starts_with('>') {
+            current_line = line as i32 + 1;
+        }
+        browser.add(&format!("@C{}@.{}{}", foreground.bits(), "  ".repeat(*depth), label));
+    }
+    if current_line > 0 {
+        browser.select(current_line);
+        browser.make_visible(current_line);
+    }
+
+    let chosen: Rc<RefCell<Option<usize>>> = Rc::new(RefCell::new(None));
+    let ids: Vec<usize> = entries.iter().map(|(id, _, _)| *id).collect();
+
+    let mut go_to = Button::new(320, 448, 90, 24, "Go to");
+    go_to.set_callback({
+        let mut wind = wind.clone();
+        let browser = browser.clone();
+        let chosen = chosen.clone();
+        let ids = ids.clone();
+        move |_| {
+            let line = browser.value();
+            if line > 0 {
+                *chosen.borrow_mut() = ids.get(line as usize - 1).copied();
+                wind.hide();
+            }
+        }
+    });
+
+    browser.set_callback({
+        let mut wind = wind.clone();
+        let chosen = chosen.clone();
+        move |b| {
+            let line = b.value();
+            if app::event_clicks() && line > 0 {
+                *chosen.borrow_mut() = ids.get(line as usize - 1).copied();
+                wind.hide();
+            }
+        }
+    });
+
+    let mut close = Button::new(420, 448, 90, 24, "Close");
+    close.set_callback({
+        let mut wind = wind.clone();
+        move |_| wind.hide()
+    });
+
+    wind.resizable(&browser);
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+    while wind.shown() {
+        app::wait();
+    }
+    chosen.borrow_mut().take()
+}
@@ -0,0 +1,16 @@
+use fltk::dialog;
+
+pub enum UnsavedChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+pub fn unsaved_changes_prompt(file_name: &str) -> UnsavedChoice {
+    let message = format!("{} has unsaved changes.", file_name);
+    match dialog::choice2_default(&message, "Cancel", "Save", "Discard") {
+        Some(1) => UnsavedChoice::Save,
+        Some(2) => UnsavedChoice::Discard,
+        _ => UnsavedChoice::Cancel,
+    }
+}
@@ -0,0 +1,303 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffLine<'a> {
+    pub kind: DiffKind,
+    pub text: &'a str,
+    pub old_line: usize,
+    pub new_line: usize,
+}
+
+/// Line-based Myers diff. Line numbers in the result are zero-based.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Trimming the common prefix and suffix keeps the quadratic worst case away
+    // from the usual "a few lines changed" situation.
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut out = Vec::with_capacity(a.len().max(b.len()));
+    for (i, text) in a.iter().take(prefix).enumerate() {
+        out.push(DiffLine { kind: DiffKind::Equal, text, old_line: i, new_line: i });
+    }
+    for (kind, i, j) in myers(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]) {
+        let (old_line, new_line) = (prefix + i, prefix + j);
+        let text = if kind == DiffKind::Insert { b[new_line] } else { a[old_line] };
+        out.push(DiffLine { kind, text, old_line, new_line });
+    }
+    for k in 0..suffix {
+        let (i, j) = (a.len() - suffix + k, b.len() - suffix + k);
+        out.push(DiffLine { kind: DiffKind::Equal, text: a[i], old_line: i, new_line: j });
+    }
+    out
+}
+
+/// Edits a stretch may need before it is shown as replaced whole, so very different
+/// texts still diff quickly.
+const MAX_COST: usize = 2048;
+
+/// Furthest-reaching paths per diagonal `k`, which runs from `-d` to `d`.
+struct Diagonals {
+    offset: isize,
+    x: Vec<usize>,
+}
+
+impl Diagonals {
+    fn new(max_d: usize) -> Self {
+        Diagonals { offset: max_d as isize, x: vec![0; 2 * max_d + 2] }
+    }
+}
+
+impl std::ops::Index<isize> for Diagonals {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+fn myers(a: &[&str], b: &[&str]) -> Vec<(DiffKind, usize, usize)> {
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    compare(a, b, (0, 0), &mut ops);
+
+    // Within each run of changes, list the removed lines before the ones replacing them.
+    let mut i = 0;
+    while i < ops.len() {
+        let start = i;
+        while i < ops.len() && ops[i].0 != DiffKind::Equal {
+            i += 1;
+        }
+        let (_, x, y) = ops[start];
+        let deletes = ops[start..i].iter().filter(|op| op.0 == DiffKind::Delete).count();
+        for (n, op) in ops[start..i].iter_mut().enumerate() {
+            *op = if n < deletes {
+                (DiffKind::Delete, x + n, y)
+            } else {
+                (DiffKind::Insert, x + deletes, y + n - deletes)
+            };
+        }
+        i += 1;
+    }
+    ops
+}
+
+/// Diffs `a` against `b`, which sit at `at` in the whole texts, by splitting both where
+/// a shortest edit script crosses its middle. Unlike keeping every step of the search,
+/// this needs memory linear in the length of the texts.
+fn compare(a: &[&str], b: &[&str], at: (usize, usize), ops: &mut Vec<(DiffKind, usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for i in 0..prefix {
+        ops.push((DiffKind::Equal, at.0 + i, at.1 + i));
+    }
+
+    let (x, y) = (at.0 + prefix, at.1 + prefix);
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let split = if a_mid.is_empty() || b_mid.is_empty() { None } else { middle(a_mid, b_mid) };
+    match split {
+        Some((i, j)) => {
+            compare(&a_mid[..i], &b_mid[..j], (x, y), ops);
+            compare(&a_mid[i..], &b_mid[j..], (x + i, y + j), ops);
+        }
+        _ => {
+            for i in 0..a_mid.len() {
+                ops.push((DiffKind::Delete, x + i, y));
+            }
+            for j in 0..b_mid.len() {
+                ops.push((DiffKind::Insert, x + a_mid.len(), y + j));
+            }
+        }
+    }
+
+    let (x, y) = (x + a_mid.len(), y + b_mid.len());
+    for i in 0..suffix {
+        ops.push((DiffKind::Equal, x + i, y + i));
+    }
+}
+
+/// Where a shortest edit script from `a` to `b` crosses its middle, found by searching
+/// from both ends at once. `None` when the texts differ by more than `MAX_COST` edits.
+fn middle(a: &[&str], b: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    let max_d = ((n + m).div_ceil(2) + 1).min(MAX_COST);
+    let mut forward = Diagonals::new(max_d);
+    let mut backward = Diagonals::new(max_d);
+
+    for d in 0..max_d as isize {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += a[x..].iter().zip(&b[y..]).take_while(|(p, q)| p == q).count();
+            }
+            forward[k] = x;
+            if odd && (k - delta).abs() < d && forward[k] + backward[-(k - delta)] >= n {
+                return Some((x0, y0));
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let same = a[..n - x].iter().rev().zip(b[..m - y].iter().rev()).take_while(|(p, q)| p == q).count();
+                x += same;
+                y += same;
+            }
+            backward[k] = x;
+            if !odd && (k - delta).abs() <= d && backward[k] + forward[-(k - delta)] >= n {
+                return Some((n - x, m - y));
+            }
+        }
+    }
+    None
+}
+
+/// Renders a unified diff with `context` unchanged lines around every change.
+pub fn unified(old: &str, new: &str, context: usize) -> String {
+    let lines = diff_lines(old, new);
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.kind != DiffKind::Equal)
+        .map(|(i, _)| i)
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // Merge changes whose context windows touch into a single hunk.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_count = hunk.iter().filter(|l| l.kind != DiffKind::Insert).count();
+        let new_count = hunk.iter().filter(|l| l.kind != DiffKind::Delete).count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk[0].old_line + 1,
+            old_count,
+            hunk[0].new_line + 1,
+            new_count
+        ));
+        for line in hunk {
+            let marker = match line.kind {
+                DiffKind::Equal => ' ',
+                DiffKind::Insert => '+',
+                DiffKind::Delete => '-',
+            };
+            out.push(marker);
+            out.push_str(line.text);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies the diff to the old text, checking that it produces the new one.
+    fn check(old: &str, new: &str) -> usize {
+        let lines = diff_lines(old, new);
+        let (mut old_line, mut new_line) = (0, 0);
+        let mut rebuilt = Vec::new();
+        for line in &lines {
+            assert_eq!((line.old_line, line.new_line), (old_line, new_line));
+            match line.kind {
+                DiffKind::Equal => {
+                    assert_eq!(line.text, old.lines().nth(old_line).unwrap());
+                    rebuilt.push(line.text);
+                    old_line += 1;
+                    new_line += 1;
+                }
+                DiffKind::Delete => old_line += 1,
+                DiffKind::Insert => {
+                    rebuilt.push(line.text);
+                    new_line += 1;
+                }
+            }
+        }
+        assert_eq!(old_line, old.lines().count());
+        assert_eq!(rebuilt, new.lines().collect::<Vec<_>>());
+        lines.iter().filter(|l| l.kind != DiffKind::Equal).count()
+    }
+
+    #[test]
+    fn shortest_edits() {
+        assert_eq!(check("", ""), 0);
+        assert_eq!(check("a\nb\nc", "a\nb\nc"), 0);
+        assert_eq!(check("", "a\nb"), 2);
+        assert_eq!(check("a\nb", ""), 2);
+        assert_eq!(check("a\nb\nc", "a\nx\nc"), 2);
+        assert_eq!(check("a\nb\nc\na\nb\nb\na", "c\nb\na\nb\na\nc"), 5);
+        assert_eq!(check("x\na\ny\nb\nz", "a\nq\nb"), 4);
+    }
+
+    #[test]
+    fn scattered_changes() {
+        let old: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (0..2000)
+            .filter(|i| i % 7 != 0)
+            .map(|i| if i % 5 == 0 { format!("changed {}\n", i) } else { format!("line {}\n", i) })
+            .chain(std::iter::once("tail\n".to_string()))
+            .collect();
+        check(&old, &new);
+    }
+
+    #[test]
+    fn texts_with_nothing_in_common() {
+        let old: String = (0..20_000).map(|i| format!("old {}\n", i)).collect();
+        let new: String = (0..20_000).map(|i| format!("new {}\n", i)).collect();
+        assert_eq!(check(&old, &new), 40_000);
+    }
+
+    #[test]
+    fn unified_hunks() {
+        let diff = unified("a\nb\nc\nd\ne\nf\ng\nh", "a\nB\nc\nd\ne\nf\ng\nH", 1);
+        assert_eq!(diff, "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -7,2 +7,2 @@\n g\n-h\n+H\n");
+    }
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+use crate::encoding::{self, Encoding};
+use crate::line_ending::{LineEnding, LineEndingCounts};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl DiskStamp {
+    pub fn of(path: &str) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(DiskStamp {
+            modified: meta.modified().ok()?,
+            len: meta.len(),
+        })
+    }
+}
+
+pub struct Document {
+    pub path: String,
+    pub modified: bool,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub mixed_line_endings: bool,
+    pub disk_stamp: Option<DiskStamp>,
+    pub swap_pending: bool,
+    pub large: bool,
+    /// The text read isn't the file byte for byte, so saving over it needs confirming.
+    pub lossy: bool,
+}
+
+impl Document {
+    pub fn new(path: &str) -> Self {
+        Document {
+            path: path.to_string(),
+            modified: false,
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+            mixed_line_endings: false,
+            disk_stamp: DiskStamp::of(path),
+            swap_pending: false,
+            large: false,
+            lossy: false,
+        }
+    }
+
+    /// Reads the file at `path` a chunk at a time, remembering its encoding and line
+    /// endings, and hands `sink` the text with line endings turned into `\n` for the
+    /// editor buffer. A file with mixed line endings is kept as it is, so saving it
+    /// changes none of them. NULs, which the buffer would cut the text short at, are
+    /// handed over as U+FFFD and the document marked lossy.
+    pub fn read_into(&mut self, mut sink: impl FnMut(&str)) -> io::Result<()> {
+        let path = Path::new(&self.path);
+        let encoding = encoding::detect_file(path)?;
+        let mut counts = LineEndingCounts::default();
+        encoding::decode_file(path, encoding, |text| counts.add(text))?;
+        let (line_ending, mixed) = counts.result();
+        let lossy = encoding::decode_file(path, encoding, |text| {
+            let text = if mixed { Cow::Borrowed(text) } else { Cow::Owned(line_ending.to_buffer(text)) };
+            if text.contains('\0') {
+                sink(&text.replace('\0', "\u{fffd}"));
+            } else {
+                sink(&text);
+            }
+        })?;
+        self.disk_stamp = DiskStamp::of(&self.path);
+        self.encoding = encoding;
+        self.line_ending = line_ending;
+        self.mixed_line_endings = mixed;
+        self.lossy = lossy;
+        Ok(())
+    }
+
+    /// Reads the whole file as `read_into` does.
+    pub fn read(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+        self.read_into(|chunk| text.push_str(chunk))?;
+        Ok(text)
+    }
+
+    /// Switches large-file mode on when the file on disk is at least `threshold_mb` megabytes.
+    pub fn update_large(&mut self, threshold_mb: f64) {
+        let len = self.disk_stamp.map(|stamp| stamp.len).unwrap_or(0);
+        self.large = threshold_mb > 0.0 && len as f64 >= threshold_mb * 1024.0 * 1024.0;
+    }
+
+    /// True when the file on disk no longer matches what was last read or saved.
+    pub fn disk_changed(&self) -> bool {
+        DiskStamp::of(&self.path) != self.disk_stamp
+    }
+
+    pub fn record_disk_stamp(&mut self) {
+        self.disk_stamp = DiskStamp::of(&self.path);
+    }
+
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        if self.mixed_line_endings {
+            return self.encoding.encode(text);
+        }
+        self.encoding.encode(&self.line_ending.apply(text))
+    }
+
+    pub fn file_name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("untitled")
+            .to_string()
+    }
+
+    pub fn abs_path(&self) -> String {
+        std::fs::canonicalize(&self.path)
+            .map(|p| p.display().to_string())
+            .unwrap_or(self.path.clone())
+    }
+
+    pub fn file_info(&self) -> String {
+        let line_ending = if self.mixed_line_endings {
+            format!("Mixed ({})", self.line_ending.label())
+        } else {
+            self.line_ending.label().to_string()
+        };
+        let info = if self.large {
+            format!("Large file   {}   {}", self.encoding.label(), line_ending)
+        } else {
+            format!("{}   {}", self.encoding.label(), line_ending)
+        };
+        if self.lossy { format!("{}   Not read exactly", info) } else { info }
+    }
+
+    pub fn window_title(&self) -> String {
+        if self.modified {
+            format!("Skrift - {} *", self.file_name())
+        } else {
+            format!("Skrift - {}", self.file_name())
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes read from a file at a time.
+const CHUNK: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16LeBom,
+    Utf16Be,
+    Utf16BeBom,
+    Latin1,
+    Windows1252,
+}
+
+// Windows-1252 code points for bytes 0x80..=0x9F; `None` marks the five undefined bytes.
+const WINDOWS_1252_HIGH: [Option<char>; 32] = [
+    Some('\u{20AC}'), None, Some('\u{201A}'), Some('\u{0192}'),
+    Some('\u{201E}'), Some('\u{2026}'), Some('\u{2020}'), Some('\u{2021}'),
+    Some('\u{02C6}'), Some('\u{2030}'), Some('\u{0160}'), Some('\u{2039}'),
+    Some('\u{0152}'), None, Some('\u{017D}'), None,
+    None, Some('\u{2018}'), Some('\u{2019}'), Some('\u{201C}'),
+    Some('\u{201D}'), Some('\u{2022}'), Some('\u{2013}'), Some('\u{2014}'),
+    Some('\u{02DC}'), Some('\u{2122}'), Some('\u{0161}'), Some('\u{203A}'),
+    Some('\u{0153}'), None, Some('\u{017E}'), Some('\u{0178}'),
+];
+
+impl Encoding {
+    pub const ALL: [Encoding; 8] = [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16LeBom,
+        Encoding::Utf16Be,
+        Encoding::Utf16BeBom,
+        Encoding::Latin1,
+        Encoding::Windows1252,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 BOM",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16LeBom => "UTF-16 LE BOM",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Utf16BeBom => "UTF-16 BE BOM",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    /// Whether this is a UTF-16 encoding, and if so whether it is little-endian.
+    fn utf16_little_endian(self) -> Option<bool> {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16LeBom => Some(true),
+            Encoding::Utf16Be | Encoding::Utf16BeBom => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn encode(self, text: &str) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf8Bom => {
+                let mut out = vec![0xEF, 0xBB, 0xBF];
+                out.extend_from_slice(text.as_bytes());
+                Ok(out)
+            }
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let mut out = Vec::with_capacity(text.len() * 2 + 2);
+                let bom = matches!(self, Encoding::Utf16LeBom | Encoding::Utf16BeBom).then_some(0xFEFF);
+                for unit in bom.into_iter().chain(text.encode_utf16()) {
+                    if self.utf16_little_endian() == Some(true) {
+                        out.extend_from_slice(&unit.to_le_bytes());
+                    } else {
+                        out.extend_from_slice(&unit.to_be_bytes());
+                    }
+                }
+                Ok(out)
+            }
+            Encoding::Latin1 | Encoding::Windows1252 => text
+                .chars()
+                .map(|c| self.encode_single_byte(c).ok_or_else(|| self.unencodable(c)))
+                .collect(),
+        }
+    }
+
+    fn encode_single_byte(self, c: char) -> Option<u8> {
+        if self == Encoding::Windows1252 {
+            if let Some(i) = WINDOWS_1252_HIGH.iter().position(|&m| m == Some(c)) {
+                return Some(0x80 + i as u8);
+            }
+            let code = c as u32;
+            if (0x80..=0x9F).contains(&code) && WINDOWS_1252_HIGH[(code - 0x80) as usize].is_some() {
+                return None;
+            }
+        }
+        u8::try_from(c as u32).ok()
+    }
+
+    fn unencodable(self, c: char) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("'{}' (U+{:04X}) cannot be encoded as {}", c, c as u32, self.label()),
+        )
+    }
+}
+
+/// Works out a file's encoding from its bytes, fed a chunk at a time.
+pub struct Detector {
+    start: Vec<u8>,
+    len: usize,
+    even_zeros: usize,
+    odd_zeros: usize,
+    /// Whether the bytes so far are UTF-8, apart from `utf8_tail`.
+    utf8: bool,
+    /// The start of a character cut off at the end of the last chunk.
+    utf8_tail: Vec<u8>,
+    /// Whether any byte falls in 0x80..=0x9F, which Windows-1252 uses but ISO-8859-1 doesn't.
+    c1_bytes: bool,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Detector {
+            start: Vec::new(),
+            len: 0,
+            even_zeros: 0,
+            odd_zeros: 0,
+            utf8: true,
+            utf8_tail: Vec::new(),
+            c1_bytes: false,
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if self.start.len() < 3 {
+            let take = (3 - self.start.len()).min(bytes.len());
+            self.start.extend_from_slice(&bytes[..take]);
+        }
+        for (i, &b) in bytes.iter().enumerate() {
+            if b == 0 {
+                if (self.len + i).is_multiple_of(2) {
+                    self.even_zeros += 1;
+                } else {
+                    self.odd_zeros += 1;
+                }
+            }
+        }
+        self.c1_bytes |= bytes.iter().any(|b| (0x80..=0x9F).contains(b));
+        if self.utf8 {
+            let joined;
+            let data = if self.utf8_tail.is_empty() {
+                bytes
+            } else {
+                joined = [&self.utf8_tail[..], bytes].concat();
+                &joined[..]
+            };
+            match std::str::from_utf8(data) {
+                Ok(_) => self.utf8_tail.clear(),
+                Err(err) if err.error_len().is_none() => self.utf8_tail = data[err.valid_up_to()..].to_vec(),
+                Err(_) => self.utf8 = false,
+            }
+        }
+        self.len += bytes.len();
+    }
+
+    pub fn finish(&self) -> Encoding {
+        if self.start.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Encoding::Utf8Bom;
+        }
+        if self.start.starts_with(&[0xFF, 0xFE]) {
+            return Encoding::Utf16LeBom;
+        }
+        if self.start.starts_with(&[0xFE, 0xFF]) {
+            return Encoding::Utf16BeBom;
+        }
+        if let Some(encoding) = self.utf16_without_bom() {
+            return encoding;
+        }
+        if self.utf8 && self.utf8_tail.is_empty() {
+            return Encoding::Utf8;
+        }
+        if self.c1_bytes { Encoding::Windows1252 } else { Encoding::Latin1 }
+    }
+
+    // Mostly-ASCII UTF-16 text has a zero in every other byte.
+    fn utf16_without_bom(&self) -> Option<Encoding> {
+        if self.len < 2 || !self.len.is_multiple_of(2) {
+            return None;
+        }
+        let pairs = self.len / 2;
+        if self.odd_zeros * 10 >= pairs * 4 && self.even_zeros * 10 < pairs {
+            Some(Encoding::Utf16Le)
+        } else if self.even_zeros * 10 >= pairs * 4 && self.odd_zeros * 10 < pairs {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decodes text a chunk at a time, carrying a character split between two chunks over
+/// to the next one.
+pub struct Decoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    /// Whether a byte order mark may still be waiting at the start of the output.
+    bom: bool,
+    /// Set once the text can't be written back as the same bytes: something didn't
+    /// decode and became U+FFFD, or it holds NULs, which the editor buffer can't keep.
+    pub lossy: bool,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Self {
+        let bom = matches!(encoding, Encoding::Utf8Bom | Encoding::Utf16LeBom | Encoding::Utf16BeBom);
+        Decoder { encoding, pending: Vec::new(), bom, lossy: false }
+    }
+
+    /// Decodes the next chunk; `last` flushes whatever is left over as replacement characters.
+    pub fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let mut text = self.decode_chunk(bytes, last);
+        // The mark is dropped once decoded, so it may be split across chunks.
+        if self.bom && !text.is_empty() {
+            self.bom = false;
+            if text.starts_with('\u{feff}') {
+                text.remove(0);
+            }
+        }
+        self.lossy |= text.contains('\0');
+        text
+    }
+
+    fn decode_chunk(&mut self, bytes: &[u8], last: bool) -> String {
+        let joined;
+        let data = if self.pending.is_empty() {
+            bytes
+        } else {
+            joined = [&self.pending[..], bytes].concat();
+            &joined[..]
+        };
+        self.pending.clear();
+
+        match self.encoding {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                let mut out = String::with_capacity(data.len());
+                let mut chunks = data.utf8_chunks().peekable();
+                while let Some(chunk) = chunks.next() {
+                    out.push_str(chunk.valid());
+                    if chunk.invalid().is_empty() {
+                        continue;
+                    }
+                    if !last && chunks.peek().is_none() {
+                        // Possibly a character the next chunk completes.
+                        self.pending = chunk.invalid().to_vec();
+                    } else {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        self.lossy = true;
+                    }
+                }
+                out
+            }
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let little_endian = self.encoding.utf16_little_endian() == Some(true);
+                let mut units: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        if little_endian {
+                            u16::from_le_bytes([pair[0], pair[1]])
+                        } else {
+                            u16::from_be_bytes([pair[0], pair[1]])
+                        }
+                    })
+                    .collect();
+                let odd_byte = last && data.len() % 2 == 1;
+                if !last {
+                    let mut keep = data.len() % 2;
+                    if units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                        units.pop();
+                        keep += 2;
+                    }
+                    self.pending = data[data.len() - keep..].to_vec();
+                }
+                let mut out: String = char::decode_utf16(units)
+                    .map(|c| {
+                        c.unwrap_or_else(|_| {
+                            self.lossy = true;
+                            char::REPLACEMENT_CHARACTER
+                        })
+                    })
+                    .collect();
+                if odd_byte {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                    self.lossy = true;
+                }
+                out
+            }
+            Encoding::Latin1 => data.iter().map(|&b| b as char).collect(),
+            Encoding::Windows1252 => data
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize].unwrap_or(b as char),
+                    _ => b as char,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Calls `f` with each chunk of the file at `path`, and whether it is the last.
+fn for_each_chunk(path: &Path, mut f: impl FnMut(&[u8], bool)) -> io::Result<()> {
+    let mut file = fs::File::open(path)?;
+    let mut chunk = Vec::with_capacity(CHUNK);
+    loop {
+        chunk.clear();
+        file.by_ref().take(CHUNK as u64).read_to_end(&mut chunk)?;
+        let last = chunk.len() < CHUNK;
+        f(&chunk, last);
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+/// Works out the encoding of the file at `path`, reading it a chunk at a time.
+pub fn detect_file(path: &Path) -> io::Result<Encoding> {
+    let mut detector = Detector::new();
+    for_each_chunk(path, |bytes, _| detector.feed(bytes))?;
+    Ok(detector.finish())
+}
+
+/// Decodes the file at `path` a chunk at a time, handing each piece of text to `sink`.
+/// No piece ends in `\r` unless the file does, so a `\r\n` is never split in two.
+/// Returns whether the text lost anything on the way, as `Decoder::lossy` tells.
+pub fn decode_file(path: &Path, encoding: Encoding, mut sink: impl FnMut(&str)) -> io::Result<bool> {
+    let mut decoder = Decoder::new(encoding);
+    let mut held_cr = false;
+    for_each_chunk(path, |bytes, last| {
+        let mut text = decoder.decode(bytes, last);
+        if held_cr {
+            text.insert(0, '\r');
+        }
+        held_cr = !last && text.ends_with('\r');
+        if held_cr {
+            text.pop();
+        }
+        if !text.is_empty() {
+            sink(&text);
+        }
+    })?;
+    Ok(decoder.lossy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(bytes: &[u8]) -> Encoding {
+        let mut detector = Detector::new();
+        detector.feed(bytes);
+        detector.finish()
+    }
+
+    fn decode(encoding: Encoding, bytes: &[u8]) -> String {
+        Decoder::new(encoding).decode(bytes, true)
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(detect(b""), Encoding::Utf8);
+        assert_eq!(detect("h\u{e9}".as_bytes()), Encoding::Utf8);
+        assert_eq!(detect(b"\xEF\xBB\xBFhi"), Encoding::Utf8Bom);
+        assert_eq!(detect(b"\xFF\xFEh\0i\0"), Encoding::Utf16LeBom);
+        assert_eq!(detect(b"\xFE\xFF\0h\0i"), Encoding::Utf16BeBom);
+        assert_eq!(detect(b"h\0i\0!\0"), Encoding::Utf16Le);
+        assert_eq!(detect(b"\0h\0i\0!"), Encoding::Utf16Be);
+        assert_eq!(detect(b"caf\xE9"), Encoding::Latin1);
+        assert_eq!(detect(b"\x93quoted\x94"), Encoding::Windows1252);
+        // A UTF-8 character cut off at the very end isn't UTF-8.
+        assert_eq!(detect(b"ab\xC3"), Encoding::Latin1);
+    }
+
+    #[test]
+    fn detection_in_chunks() {
+        let text = "tr\u{e8}s \u{20AC} ".repeat(100);
+        let mut detector = Detector::new();
+        for chunk in text.as_bytes().chunks(7) {
+            detector.feed(chunk);
+        }
+        assert_eq!(detector.finish(), Encoding::Utf8);
+    }
+
+    #[test]
+    fn round_trip() {
+        let files: [&[u8]; 8] = [
+            "h\u{e9}llo".as_bytes(),
+            b"\xEF\xBB\xBFhi",
+            b"h\0i\0!\0",
+            b"\xFF\xFEh\0i\0",
+            b"\0h\0i\0!",
+            b"\xFE\xFF\0h\0i",
+            b"caf\xE9",
+            b"\x93quoted\x94",
+        ];
+        for bytes in files {
+            let encoding = detect(bytes);
+            assert_eq!(encoding.encode(&decode(encoding, bytes)).unwrap(), bytes, "{}", encoding.label());
+        }
+    }
+
+    #[test]
+    fn decoding_in_chunks() {
+        let text = "a\u{e9}\u{20AC}\u{1F600}b".repeat(50);
+        for encoding in [Encoding::Utf8, Encoding::Utf8Bom, Encoding::Utf16LeBom, Encoding::Utf16Be] {
+            let bytes = encoding.encode(&text).unwrap();
+            for size in [1, 2, 3, 5] {
+                let mut decoder = Decoder::new(encoding);
+                let chunks: Vec<&[u8]> = bytes.chunks(size).collect();
+                let decoded: String =
+                    chunks.iter().enumerate().map(|(i, c)| decoder.decode(c, i + 1 == chunks.len())).collect();
+                assert_eq!(decoded, text, "{} in chunks of {}", encoding.label(), size);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(decode(Encoding::Utf8, b"a\xFFb\xE2\x82"), "a\u{FFFD}b\u{FFFD}");
+        let mut decoder = Decoder::new(Encoding::Utf8);
+        assert_eq!(decoder.decode(b"a\xE2\x82", false), "a");
+        assert_eq!(decoder.decode(b"", true), "\u{FFFD}");
+        // A lone surrogate or an odd trailing byte doesn't stop decoding, and neither is dropped.
+        assert_eq!(decode(Encoding::Utf16Le, &[0x3D, 0xD8, b'a', 0, 0x7F]), "\u{FFFD}a\u{FFFD}");
+    }
+
+    #[test]
+    fn lossy_decoding_is_flagged() {
+        let lossy = |encoding, bytes: &[u8]| {
+            let mut decoder = Decoder::new(encoding);
+            decoder.decode(bytes, true);
+            decoder.lossy
+        };
+        assert!(!lossy(Encoding::Utf8, "caf\u{e9} \u{FFFD}".as_bytes()), "a real U+FFFD is kept as it is");
+        assert!(lossy(Encoding::Utf8, b"a\xFFb"));
+        assert!(lossy(Encoding::Utf8, b"a\0b"));
+        assert!(lossy(Encoding::Latin1, b"a\0b"));
+        assert!(!lossy(Encoding::Utf16Le, b"a\0b\0"));
+        assert!(lossy(Encoding::Utf16Le, b"a\0b"));
+        assert!(lossy(Encoding::Utf16Be, &[0xDC, 0x00]));
+
+        // A character split across chunks isn't lost.
+        let mut decoder = Decoder::new(Encoding::Utf16Le);
+        decoder.decode(&[b'a', 0, 0x3D], false);
+        decoder.decode(&[0xD8, 0x00, 0xDE], true);
+        assert!(!decoder.lossy);
+    }
+
+    #[test]
+    fn utf16_surrogate_pairs() {
+        let bytes = Encoding::Utf16Le.encode("\u{1F600}").unwrap();
+        assert_eq!(bytes, [0x3D, 0xD8, 0x00, 0xDE]);
+        assert_eq!(decode(Encoding::Utf16Le, &bytes), "\u{1F600}");
+    }
+
+    #[test]
+    fn unencodable_characters() {
+        assert!(Encoding::Latin1.encode("\u{20AC}").is_err());
+        assert_eq!(Encoding::Windows1252.encode("\u{20AC}").unwrap(), [0x80]);
+        assert!(Encoding::Windows1252.encode("\u{81}").is_ok());
+        assert!(Encoding::Windows1252.encode("\u{80}").is_err());
+    }
+}
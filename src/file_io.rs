@@ -0,0 +1,148 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // Write through symlinks instead of replacing the link with a regular file.
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let parent = match target.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if !parent.exists() {
+        fs::create_dir_all(&parent)?;
+    }
+
+    let original = fs::metadata(&target).ok();
+    let (mut file, temp_path) = create_temp(&target, &parent)?;
+
+    let result = (|| {
+        file.write_all(contents)?;
+        if let Some(meta) = &original {
+            fs::set_permissions(&temp_path, meta.permissions())?;
+            copy_ownership(&temp_path, meta);
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, &target)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Persist the rename itself; not every platform allows syncing a directory.
+    if let Ok(dir) = File::open(&parent) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Creates a temp file next to `target` under a name nothing else uses, skipping any
+/// left behind by a save that crashed.
+fn create_temp(target: &Path, parent: &Path) -> io::Result<(File, PathBuf)> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("untitled");
+    let mut attempt = 0;
+    loop {
+        let path = parent.join(format!(".{}.skrift-{}-{}.tmp", name, std::process::id(), attempt));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn copy_ownership(path: &Path, meta: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    // Only root can hand a file to another user, so a failure here is expected and harmless.
+    let _ = std::os::unix::fs::chown(path, Some(meta.uid()), Some(meta.gid()));
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_path: &Path, _meta: &fs::Metadata) {}
+
+// FNV-1a, because `DefaultHasher` output is not stable between Rust releases
+// and names derived from it have to survive an upgrade.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn absolute_path(path: &str) -> String {
+    let p = Path::new(path);
+    if let Ok(canonical) = fs::canonicalize(p) {
+        return canonical.display().to_string();
+    }
+    if p.is_absolute() {
+        return path.to_string();
+    }
+    std::env::current_dir()
+        .map(|cwd| cwd.join(p).display().to_string())
+        .unwrap_or(path.to_string())
+}
+
+/// A file name that identifies `path` inside a flat data directory, e.g. `notes.txt.1f2e3d4c5b6a7988`.
+pub fn path_key(path: &str) -> String {
+    let absolute = absolute_path(path);
+    let name = Path::new(&absolute)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("untitled");
+    format!("{}.{:016x}", name, fnv1a(absolute.as_bytes()))
+}
+
+pub fn data_dir(sub: &str) -> PathBuf {
+    let mut dir = dirs::data_dir().unwrap_or_else(|| {
+        let mut home = dirs::home_dir().unwrap_or(PathBuf::from("."));
+        home.push(".local/share");
+        home
+    });
+    dir.push("skrift");
+    dir.push(sub);
+    dir
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or(PathBuf::from(".")).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+pub fn describe_error(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        io::ErrorKind::NotFound => "The directory does not exist".to_string(),
+        io::ErrorKind::StorageFull => "The disk is full".to_string(),
+        io::ErrorKind::ReadOnlyFilesystem => "The file system is read-only".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_temp_files_do_not_block_saves() {
+        let dir = std::env::temp_dir().join(format!("skrift-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        let stale = dir.join(format!(".notes.txt.skrift-{}-0.tmp", std::process::id()));
+        fs::write(&stale, "left by a crash").unwrap();
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(fs::read(&stale).unwrap(), b"left by a crash");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "no temp files of our own are left");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
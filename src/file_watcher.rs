@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use notify::{Watcher, RecommendedWatcher, RecursiveMode, EventKind};
+
+pub type WatchedFile = (String, RecommendedWatcher);
+
+// Watches the parent directory rather than the file itself, so tools that
+// replace the file through a rename (git, formatters, our own saves) are still seen.
+pub fn start_file_watcher(path: &Path, tx: Sender<()>) -> Option<RecommendedWatcher> {
+    let target = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = target.file_name()?.to_os_string();
+    let dir = match target.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut watcher: RecommendedWatcher = RecommendedWatcher::new(
+        move |res: Result<notify::Event, notify::Error>| {
+            if let Ok(event) = res {
+                let relevant = matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                );
+                if relevant && event.paths.iter().any(|p| p.file_name() == Some(name.as_os_str())) {
+                    let _ = tx.send(());
+                }
+            }
+        },
+        notify::Config::default(),
+    ).ok()?;
+
+    watcher.watch(&dir, RecursiveMode::NonRecursive).ok()?;
+    Some(watcher)
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+struct Rule {
+    base: PathBuf,
+    pattern: String,
+    negate: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+        Some(Rule {
+            base: base.to_path_buf(),
+            anchored: line.contains('/'),
+            pattern: line.strip_prefix('/').unwrap_or(line).to_string(),
+            negate,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else { return false };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let subject = if self.anchored {
+            relative.as_str()
+        } else {
+            relative.rsplit('/').next().unwrap_or_default()
+        };
+        glob(self.pattern.as_bytes(), subject.as_bytes())
+    }
+}
+
+/// The `.gitignore` rules in effect while walking a directory tree. Rules are
+/// added as directories are entered and truncated again when they are left.
+#[derive(Debug, Clone, Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    /// Rules from the repository's `.git/info/exclude` and the `.gitignore` files in the
+    /// ancestors of `root` up to the repository it belongs to, so searching a
+    /// subdirectory honors them too.
+    pub fn for_root(root: &Path) -> Self {
+        let mut ignore = Gitignore::default();
+        let Some(repo) = root.ancestors().find(|dir| dir.join(".git").exists()) else { return ignore };
+        // Added first so any `.gitignore` can override it, as in git.
+        if let Ok(text) = fs::read_to_string(repo.join(".git/info/exclude")) {
+            ignore.add_rules(repo, &text);
+        }
+        let ancestors: Vec<&Path> = root.ancestors().skip(1).take_while(|dir| dir.starts_with(repo)).collect();
+        for dir in ancestors.iter().rev() {
+            ignore.add_dir(dir);
+        }
+        ignore
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Adds the rules of the `.gitignore` in `dir`, if it has one.
+    pub fn add_dir(&mut self, dir: &Path) {
+        if let Ok(text) = fs::read_to_string(dir.join(".gitignore")) {
+            self.add_rules(dir, &text);
+        }
+    }
+
+    /// Adds the rules in `text`, with patterns relative to `base`.
+    fn add_rules(&mut self, base: &Path, text: &str) {
+        self.rules.extend(text.lines().filter_map(|line| Rule::parse(base, line)));
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.rules.truncate(len);
+    }
+
+    /// The last matching rule decides, so later `!pattern` lines can re-include paths.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path, is_dir))
+            .is_some_and(|rule| !rule.negate)
+    }
+}
+
+/// Gitignore-style glob: `*` and `?` stay within one path segment, `**` crosses them.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            match rest.strip_prefix(b"/") {
+                // `**/` matches zero or more whole directories.
+                Some(rest) => {
+                    glob(rest, text)
+                        || text
+                            .iter()
+                            .enumerate()
+                            .any(|(i, &c)| c == b'/' && glob(rest, &text[i + 1..]))
+                }
+                None => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+            }
+        }
+        Some(b'*') => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(&pattern[1..], &text[i..])),
+        Some(b'?') => text.first().is_some_and(|&c| c != b'/') && glob(&pattern[1..], &text[1..]),
+        Some(b'[') => {
+            let Some(&c) = text.first() else { return false };
+            let negate = matches!(pattern.get(1), Some(b'!' | b'^'));
+            let start = if negate { 2 } else { 1 };
+            let close = pattern
+                .get(start + 1..)
+                .and_then(|rest| rest.iter().position(|&b| b == b']'))
+                .map(|i| i + start + 1);
+            let Some(close) = close else {
+                return c == b'[' && glob(&pattern[1..], &text[1..]);
+            };
+            let set = &pattern[start..close];
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            c != b'/' && found != negate && glob(&pattern[close + 1..], &text[1..])
+        }
+        Some(b'\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && glob(&pattern[2..], &text[1..])
+        }
+        Some(&p) => text.first() == Some(&p) && glob(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> Gitignore {
+        let mut ignore = Gitignore::default();
+        ignore.add_rules(Path::new("/repo"), text);
+        ignore
+    }
+
+    fn ignored(ignore: &Gitignore, path: &str) -> bool {
+        let is_dir = path.ends_with('/');
+        ignore.is_ignored(&Path::new("/repo").join(path.trim_end_matches('/')), is_dir)
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob(b"*.rs", b"main.rs"));
+        assert!(!glob(b"*.rs", b"src/main.rs"));
+        assert!(glob(b"src/**/*.rs", b"src/main.rs"));
+        assert!(glob(b"src/**/*.rs", b"src/a/b/main.rs"));
+        assert!(glob(b"**/target", b"a/b/target"));
+        assert!(glob(b"file?.txt", b"file1.txt"));
+        assert!(!glob(b"file?.txt", b"file/.txt"));
+        assert!(glob(b"[a-c]x", b"bx"));
+        assert!(!glob(b"[!a-c]x", b"bx"));
+        assert!(glob(b"\\*", b"*"));
+        assert!(!glob(b"\\*", b"a"));
+    }
+
+    #[test]
+    fn rules_and_negation() {
+        let ignore = rules("# comment\n\n*.log\n!keep.log\nbuild/\n/top.txt\ndocs/*.html\n");
+        assert!(ignored(&ignore, "debug.log"));
+        assert!(ignored(&ignore, "deep/down/debug.log"));
+        assert!(!ignored(&ignore, "keep.log"));
+        assert!(ignored(&ignore, "build/"));
+        assert!(ignored(&ignore, "src/build/"));
+        assert!(!ignored(&ignore, "build"), "a directory-only rule skips files");
+        assert!(ignored(&ignore, "top.txt"));
+        assert!(!ignored(&ignore, "sub/top.txt"), "a leading slash anchors the rule");
+        assert!(ignored(&ignore, "docs/index.html"));
+        assert!(!ignored(&ignore, "docs/api/index.html"));
+        assert!(ignored(&ignore, ".git/"));
+        assert!(!ignored(&ignore, "main.rs"));
+    }
+
+    #[test]
+    fn leaving_a_directory_drops_its_rules() {
+        let mut ignore = rules("*.tmp\n");
+        let saved = ignore.rule_count();
+        ignore.add_rules(Path::new("/repo/sub"), "*.rs\n");
+        assert!(ignore.is_ignored(Path::new("/repo/sub/main.rs"), false));
+        assert!(!ignore.is_ignored(Path::new("/repo/main.rs"), false));
+        ignore.truncate(saved);
+        assert!(!ignore.is_ignored(Path::new("/repo/sub/main.rs"), false));
+        assert!(ignore.is_ignored(Path::new("/repo/sub/a.tmp"), false));
+    }
+
+    #[test]
+    fn info_exclude_and_parent_gitignores() {
+        let repo = std::env::temp_dir().join(format!("skrift-gitignore-{}", std::process::id()));
+        let sub = repo.join("sub");
+        fs::create_dir_all(repo.join(".git/info")).unwrap();
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(repo.join(".git/info/exclude"), "*.secret\nlocal.txt\n").unwrap();
+        fs::write(repo.join(".gitignore"), "*.log\n!local.txt\n").unwrap();
+
+        let ignore = Gitignore::for_root(&sub);
+        assert!(ignore.is_ignored(&sub.join("key.secret"), false));
+        assert!(ignore.is_ignored(&sub.join("debug.log"), false));
+        assert!(!ignore.is_ignored(&sub.join("local.txt"), false), ".gitignore overrides info/exclude");
+        assert!(!ignore.is_ignored(&sub.join("main.rs"), false));
+
+        let mut ignore = Gitignore::for_root(&repo);
+        assert!(ignore.is_ignored(&repo.join("key.secret"), false));
+        ignore.add_dir(&repo);
+        assert!(ignore.is_ignored(&repo.join("debug.log"), false));
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
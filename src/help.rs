@@ -4,20 +4,15 @@ pub fn print_help() {
     println!("Skrift - Minimal FLTK Text Editor");
     println!();
     println!("Usage:");
-    println!("  skript [file]           Open file for editing (default: out.txt)");
+    println!("  skript [files...]       Open files for editing, one tab each (default: out.txt)");
     println!("  skript -h | --help      Show this help message");
     println!();
     println!("Shortcuts:");
     let cfg = Config::load();
-    for binding in [
-        Binding::Save,
-        Binding::Quit,
-        Binding::Reload,
-        Binding::MoveLineUp,
-        Binding::MoveLineDown,
-    ] {
-        if let Some(shortcut) = cfg.bindings.get(&binding) {
-            println!("  {:<14} {}", format!("{:?}:", binding), shortcut);
+    for binding in Binding::ALL {
+        if let Some(sequences) = cfg.bindings.get(&binding).filter(|sequences| !sequences.is_empty()) {
+            let keys: Vec<String> = sequences.iter().map(|sequence| sequence.to_string()).collect();
+            println!("  {:<21} {}", format!("{}:", binding.name()), keys.join(", "));
         }
     }
     println!();
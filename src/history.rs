@@ -0,0 +1,207 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_io;
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub timestamp: u64,
+    pub hash: u64,
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn read(&self) -> io::Result<String> {
+        fs::read_to_string(&self.path)
+    }
+
+    pub fn label(&self) -> String {
+        let secs = self.timestamp / 1000;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(secs);
+        format!("{}  ({})", format_utc(secs), format_age(now.saturating_sub(secs)))
+    }
+}
+
+fn history_dir(path: &str) -> PathBuf {
+    file_io::data_dir("history").join(file_io::path_key(path))
+}
+
+/// Stores `text` as the newest snapshot of `path`, unless it matches the newest one,
+/// and drops snapshots older than `max_age_days`.
+pub fn record(path: &str, text: &str, max_age_days: i32) -> io::Result<()> {
+    record_in(&history_dir(path), text, max_age_days)
+}
+
+fn record_in(dir: &Path, text: &str, max_age_days: i32) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let hash = file_io::fnv1a(text.as_bytes());
+    let snapshots = list_in(dir);
+    if snapshots.first().map(|s| s.hash) != Some(hash) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let target = dir.join(format!("{}-{:016x}.snap", timestamp, hash));
+        file_io::write_atomic(&target, text.as_bytes())?;
+    }
+    prune(dir, max_age_days);
+    Ok(())
+}
+
+/// Returns the snapshots of `path`, newest first.
+pub fn list(path: &str) -> Vec<Snapshot> {
+    list_in(&history_dir(path))
+}
+
+fn list_in(dir: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let (timestamp, hash) = name.to_str()?.strip_suffix(".snap")?.split_once('-')?;
+            Some(Snapshot {
+                timestamp: timestamp.parse().ok()?,
+                hash: u64::from_str_radix(hash, 16).ok()?,
+                path: entry.path(),
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.timestamp));
+    snapshots
+}
+
+/// Drops snapshots older than `max_age_days`. The new snapshot is already stored, so a
+/// snapshot that can't be removed is only reported.
+fn prune(dir: &Path, max_age_days: i32) {
+    if max_age_days <= 0 {
+        return;
+    }
+    let max_age = Duration::from_secs(max_age_days as u64 * 24 * 60 * 60);
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    // The newest snapshot is always kept so a file never loses its whole history.
+    for snapshot in list_in(dir).into_iter().skip(1) {
+        if snapshot.timestamp < cutoff
+            && let Err(err) = fs::remove_file(&snapshot.path)
+        {
+            eprintln!("Failed to remove old snapshot {}: {}", snapshot.path.display(), err);
+        }
+    }
+}
+
+fn format_utc(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("skrift-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn contents(dir: &Path) -> Vec<String> {
+        list_in(dir).iter().map(|s| s.read().unwrap()).collect()
+    }
+
+    #[test]
+    fn unchanged_text_is_not_stored_again() {
+        let dir = temp_dir("dedup");
+        record_in(&dir, "one", 30).unwrap();
+        record_in(&dir, "one", 30).unwrap();
+        assert_eq!(contents(&dir), ["one"]);
+        std::thread::sleep(Duration::from_millis(2));
+        record_in(&dir, "two", 30).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        record_in(&dir, "one", 30).unwrap();
+        assert_eq!(contents(&dir), ["one", "two", "one"], "newest first, and only the newest is compared");
+        let snapshots = list_in(&dir);
+        assert_eq!(snapshots[0].hash, file_io::fnv1a(b"one"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned() {
+        let dir = temp_dir("prune");
+        fs::create_dir_all(&dir).unwrap();
+        let day_ms = 24 * 60 * 60 * 1000;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        for (age_days, text) in [(40, "oldest"), (35, "old"), (2, "recent")] {
+            let hash = file_io::fnv1a(text.as_bytes());
+            fs::write(dir.join(format!("{}-{:016x}.snap", now - age_days * day_ms, hash)), text).unwrap();
+        }
+        fs::write(dir.join("not-a-snapshot.txt"), "ignored").unwrap();
+
+        record_in(&dir, "newest", 30).unwrap();
+        assert_eq!(contents(&dir), ["newest", "recent"]);
+        // Turned off, nothing is pruned.
+        record_in(&dir, "newest", 0).unwrap();
+        assert_eq!(contents(&dir), ["newest", "recent"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_newest_snapshot_is_kept_however_old() {
+        let dir = temp_dir("keep");
+        fs::create_dir_all(&dir).unwrap();
+        let hash = file_io::fnv1a(b"ancient");
+        fs::write(dir.join(format!("1000-{:016x}.snap", hash)), "ancient").unwrap();
+        record_in(&dir, "ancient", 1).unwrap();
+        assert_eq!(contents(&dir), ["ancient"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ages_and_dates() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(125), "2 min ago");
+        assert_eq!(format_age(7200), "2 h ago");
+        assert_eq!(format_age(3 * 86400 + 5), "3 days ago");
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_827_696), "2000-02-29 12:34:56 UTC");
+    }
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+
+use fltk::{
+    app,
+    enums::{Key, Shortcut},
+};
+
+/// Named keys, with their aliases after them. The first name for a key is the one shown.
+const NAMED_KEYS: &[(&str, Key)] = &[
+    ("Enter", Key::Enter),
+    ("Return", Key::Enter),
+    ("Tab", Key::Tab),
+    ("Escape", Key::Escape),
+    ("Esc", Key::Escape),
+    ("Backspace", Key::BackSpace),
+    ("Delete", Key::Delete),
+    ("Del", Key::Delete),
+    ("Insert", Key::Insert),
+    ("Ins", Key::Insert),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PgUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("PgDn", Key::PageDown),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Pause", Key::Pause),
+    ("ScrollLock", Key::ScrollLock),
+    ("PrintScreen", Key::Print),
+    ("Print", Key::Print),
+    ("Menu", Key::Menu),
+    ("Help", Key::Help),
+    ("NumLock", Key::NumLock),
+    ("CapsLock", Key::CapsLock),
+    ("KPEnter", Key::KPEnter),
+];
+
+/// Names for keys that are awkward to write as themselves.
+const NAMED_CHARS: &[(&str, char)] = &[
+    ("Space", ' '),
+    ("Plus", '+'),
+    ("Minus", '-'),
+    ("Equal", '='),
+    ("Comma", ','),
+    ("Period", '.'),
+    ("Slash", '/'),
+    ("Backslash", '\\'),
+    ("Semicolon", ';'),
+    ("Quote", '\''),
+    ("Backquote", '`'),
+    ("BracketLeft", '['),
+    ("BracketRight", ']'),
+];
+
+/// The highest function key a binding can name. FLTK numbers them up to F34.
+const MAX_FUNCTION_KEY: i32 = 24;
+
+/// The key in a key combination, apart from its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    /// A key that doesn't type anything, such as `Tab`, `F5` or a keypad key.
+    Named(Key),
+    /// A key that types a character. Letters are kept in lower case.
+    Char(char),
+}
+
+/// A key with the modifiers held down with it, parsed from a binding such as
+/// `"Ctrl+Shift+PageDown"`, `"F5"`, `"Super+K"` or `"Ctrl++"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+    pub key: KeyCode,
+}
+
+impl KeyCombo {
+    /// Parses a key spec: any number of `Ctrl`, `Alt`, `Shift` and `Meta` (or `Super`,
+    /// `Cmd`, `Win`) joined by `+`, then a key. Names are case-insensitive.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut combo = KeyCombo { ctrl: false, alt: false, shift: false, meta: false, key: KeyCode::Char(' ') };
+        let mut rest = spec.trim();
+        // The key itself may be `+`, so only split where something follows.
+        while let Some((head, tail)) = rest.split_once('+')
+            && !tail.is_empty()
+        {
+            let held = match head.trim().to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut combo.ctrl,
+                "alt" | "option" => &mut combo.alt,
+                "shift" => &mut combo.shift,
+                "meta" | "super" | "cmd" | "command" | "win" => &mut combo.meta,
+                _ => return Err(format!("unknown modifier \"{}\" in \"{}\"", head.trim(), spec)),
+            };
+            if *held {
+                return Err(format!("\"{}\" is repeated in \"{}\"", head.trim(), spec));
+            }
+            *held = true;
+            rest = tail;
+        }
+        combo.key = match parse_key(rest.trim()) {
+            Some(key) => key,
+            None if rest.trim().is_empty() => return Err(format!("no key in \"{}\"", spec)),
+            None => return Err(format!("unknown key \"{}\" in \"{}\"", rest.trim(), spec)),
+        };
+        Ok(combo)
+    }
+
+    /// Whether the key event being handled is this combination.
+    pub fn matches_event(&self) -> bool {
+        let event_key = app::event_key();
+        if is_modifier(event_key) {
+            return false;
+        }
+        let state = app::event_state();
+        let others_match = self.ctrl == state.contains(Shortcut::Ctrl)
+            && self.alt == state.contains(Shortcut::Alt)
+            && self.meta == state.contains(Shortcut::Meta);
+        let shift_matches = self.shift == state.contains(Shortcut::Shift);
+
+        match self.key {
+            KeyCode::Named(key) => event_key == key && others_match && shift_matches,
+            KeyCode::Char(c) => {
+                if key_char(event_key) == Some(c) {
+                    return others_match && shift_matches;
+                }
+                // A symbol without Shift also matches the character the layout typed, so
+                // "Ctrl+?" works wherever `?` is and needs whatever it takes to type it.
+                !self.shift
+                    && !c.is_alphabetic()
+                    && others_match
+                    && app::event_text().chars().next().filter(|ch| !ch.is_control()) == Some(c)
+            }
+        }
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl"), (self.alt, "Alt"), (self.shift, "Shift"), (self.meta, "Meta")] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.key {
+            KeyCode::Named(key) => {
+                if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, k)| *k == key) {
+                    write!(f, "{}", name)
+                } else if Key::is_fn_key(key) {
+                    write!(f, "F{}", key.bits() - Key::F1.bits() + 1)
+                } else {
+                    let c = char::from_u32((key.bits() - Key::KP.bits()) as u32).unwrap_or('?');
+                    write!(f, "KP{}", c)
+                }
+            }
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_uppercase()),
+        }
+    }
+}
+
+/// Key combinations pressed one after another, like `"Ctrl+K Ctrl+C"`. Most bindings are
+/// a single combination.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(Vec<KeyCombo>);
+
+impl KeySequence {
+    /// Parses key specs separated by spaces, each as `KeyCombo::parse` reads them.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let combos = spec.split_whitespace().map(KeyCombo::parse).collect::<Result<Vec<_>, _>>()?;
+        if combos.is_empty() {
+            return Err("no key given".to_string());
+        }
+        Ok(KeySequence(combos))
+    }
+
+    pub fn from_combos(combos: Vec<KeyCombo>) -> Self {
+        KeySequence(combos)
+    }
+
+    pub fn combos(&self) -> &[KeyCombo] {
+        &self.0
+    }
+
+    /// Whether one sequence starts the other, so both can't be bound at once.
+    pub fn overlaps(&self, other: &KeySequence) -> bool {
+        let shared = self.0.len().min(other.0.len());
+        self.0[..shared] == other.0[..shared]
+    }
+
+    /// The combination, when the sequence is just one.
+    pub fn single(&self) -> Option<&KeyCombo> {
+        match self.0.as_slice() {
+            [combo] => Some(combo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, combo) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", combo)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `key` is a modifier on its own, which never completes a combination.
+pub fn is_modifier(key: Key) -> bool {
+    matches!(
+        key,
+        Key::ControlL | Key::ControlR | Key::AltL | Key::AltR | Key::ShiftL | Key::ShiftR | Key::MetaL | Key::MetaR
+    )
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c.to_lowercase().next().unwrap_or(c)));
+    }
+    let lower = name.to_ascii_lowercase();
+    if let Some(&(_, key)) = NAMED_KEYS.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(KeyCode::Named(key));
+    }
+    if let Some(&(_, c)) = NAMED_CHARS.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(KeyCode::Char(c));
+    }
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<i32>().ok())
+        && (1..=MAX_FUNCTION_KEY).contains(&n)
+    {
+        return Some(KeyCode::Named(Key::fn_key(n)));
+    }
+    // Keypad keys are FLTK's keypad base plus the character they type: KP0 to KP9,
+    // KP+, KP-, KP*, KP/, KP. and KP=.
+    if let Some(c) = lower.strip_prefix("kp").and_then(|rest| {
+        let mut chars = rest.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    }) && (c.is_ascii_digit() || "+-*/.=".contains(c))
+    {
+        return Some(KeyCode::Named(Key::from_i32(Key::KP.bits() + c as i32)));
+    }
+    None
+}
+
+/// The character a non-special key types without modifiers, in lower case.
+fn key_char(key: Key) -> Option<char> {
+    // FLTK's special keys all sit above the Unicode range keys type.
+    if key.bits() >= 0xfe00 {
+        return None;
+    }
+    char::from_u32(key.bits() as u32).map(|c| c.to_lowercase().next().unwrap_or(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo(spec: &str) -> KeyCombo {
+        KeyCombo::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn parsing() {
+        let c = combo("Ctrl+Shift+PageDown");
+        assert!(c.ctrl && c.shift && !c.alt && !c.meta);
+        assert_eq!(c.key, KeyCode::Named(Key::PageDown));
+        assert_eq!(combo("ctrl+s"), combo("Control+S"));
+        assert_eq!(combo("Super+K"), combo("cmd+k"));
+        assert_eq!(combo("F5").key, KeyCode::Named(Key::F5));
+        assert_eq!(combo("Ctrl++").key, KeyCode::Char('+'));
+        assert_eq!(combo("Ctrl+Plus"), combo("Ctrl++"));
+        assert_eq!(combo(" Alt + Space ").key, KeyCode::Char(' '));
+        assert_eq!(combo("Esc"), combo("Escape"));
+        assert_eq!(combo("KP5").key, KeyCode::Named(Key::from_i32(Key::KP.bits() + '5' as i32)));
+    }
+
+    #[test]
+    fn bad_specs() {
+        for spec in ["", "Ctrl+", "Hyper+K", "Ctrl+Ctrl+K", "F25", "F0", "KP", "KPx", "Nothing"] {
+            assert!(KeyCombo::parse(spec).is_err(), "{:?} should not parse", spec);
+        }
+        assert!(KeySequence::parse("  ").is_err());
+        assert!(KeySequence::parse("Ctrl+K Bogus").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for spec in ["Ctrl+Shift+PageDown", "F5", "Ctrl++", "Alt+Space", "Meta+K", "KP5", "Enter", "Ctrl+K Ctrl+C"] {
+            let sequence = KeySequence::parse(spec).unwrap();
+            assert_eq!(sequence.to_string(), spec);
+            assert_eq!(KeySequence::parse(&sequence.to_string()).unwrap(), sequence);
+        }
+        assert_eq!(combo("return").to_string(), "Enter");
+        assert_eq!(combo("ctrl+s").to_string(), "Ctrl+S");
+    }
+
+    #[test]
+    fn sequences() {
+        let chord = KeySequence::parse("Ctrl+K Ctrl+C").unwrap();
+        assert_eq!(chord.combos().len(), 2);
+        assert!(chord.single().is_none());
+        assert_eq!(KeySequence::parse("Ctrl+K").unwrap().single(), Some(&combo("Ctrl+K")));
+        assert!(chord.overlaps(&KeySequence::parse("Ctrl+K").unwrap()));
+        assert!(chord.overlaps(&KeySequence::parse("Ctrl+K Ctrl+C Ctrl+D").unwrap()));
+        assert!(!chord.overlaps(&KeySequence::parse("Ctrl+K Ctrl+D").unwrap()));
+    }
+}
@@ -0,0 +1,132 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 3] = [LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Cr => "CR",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// Converts the line breaks of a file that uses `self` throughout to the `\n` the
+    /// editor buffer works with. A `\r` on its own only breaks lines in a `Cr` file.
+    pub fn to_buffer(self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::Crlf => text.replace("\r\n", "\n"),
+            LineEnding::Cr => text.replace('\r', "\n"),
+        }
+    }
+
+    /// Converts every line ending in `text` to `\n`, lone `\r`s included.
+    pub fn normalize(text: &str) -> String {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    }
+
+    /// Writes the buffer's line breaks as `self`. A `\r\n` pasted into the buffer is one
+    /// break; any other `\r` is kept as it is.
+    pub fn apply(self, text: &str) -> String {
+        let text = text.replace("\r\n", "\n");
+        match self {
+            LineEnding::Lf => text,
+            _ => text.replace('\n', self.as_str()),
+        }
+    }
+}
+
+/// Counts of each line ending in text fed a piece at a time. A `\r\n` must not be split
+/// between two pieces.
+#[derive(Debug, Default)]
+pub struct LineEndingCounts {
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+}
+
+impl LineEndingCounts {
+    pub fn add(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    self.crlf += 1;
+                    i += 1;
+                }
+                b'\r' => self.cr += 1,
+                b'\n' => self.lf += 1,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// The most common line ending and whether more than one kind occurs.
+    pub fn result(&self) -> (LineEnding, bool) {
+        let (lf, crlf, cr) = (self.lf, self.crlf, self.cr);
+        let kinds = [lf, crlf, cr].iter().filter(|&&n| n > 0).count();
+        let dominant = if crlf > lf && crlf >= cr {
+            LineEnding::Crlf
+        } else if cr > lf && cr > crlf {
+            LineEnding::Cr
+        } else {
+            LineEnding::Lf
+        };
+        (dominant, kinds > 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(text: &str) -> (LineEnding, bool) {
+        let mut counts = LineEndingCounts::default();
+        counts.add(text);
+        counts.result()
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(detect(""), (LineEnding::Lf, false));
+        assert_eq!(detect("a\nb\n"), (LineEnding::Lf, false));
+        assert_eq!(detect("a\r\nb\r\n"), (LineEnding::Crlf, false));
+        assert_eq!(detect("a\rb\r"), (LineEnding::Cr, false));
+        assert_eq!(detect("a\r\nb\r\nc\n"), (LineEnding::Crlf, true));
+        assert_eq!(detect("a\r\nb\n"), (LineEnding::Lf, true));
+        assert_eq!(detect("a\rb\n"), (LineEnding::Lf, true));
+    }
+
+    #[test]
+    fn round_trip() {
+        for text in ["a\nb\n", "a\r\nb\r\n", "a\rb\r", "no break"] {
+            let (ending, mixed) = detect(text);
+            assert!(!mixed);
+            assert_eq!(ending.apply(&ending.to_buffer(text)), text);
+        }
+    }
+
+    #[test]
+    fn lone_carriage_returns_are_kept() {
+        assert_eq!(LineEnding::Crlf.to_buffer("a\rb\r\n"), "a\rb\n");
+        assert_eq!(LineEnding::Crlf.apply("a\rb\n"), "a\rb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\r\nb\r"), "a\nb\r");
+        assert_eq!(LineEnding::normalize("a\rb\r\nc\n"), "a\nb\nc\n");
+    }
+}
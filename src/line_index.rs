@@ -0,0 +1,222 @@
+/// Lines per block of the index. An edit rewrites the starts of its own block and moves
+/// the bases of the blocks after it, so it costs about this plus the number of blocks.
+const BLOCK: usize = 1024;
+
+/// Byte offsets of every line start in a buffer, kept up to date from modify
+/// callbacks so line lookups don't have to scan the text.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    blocks: Vec<Block>,
+    len: i32,
+}
+
+/// A run of consecutive line starts, stored relative to `base`.
+#[derive(Debug, Clone)]
+struct Block {
+    base: i32,
+    /// The number of lines in the blocks before this one.
+    first_line: usize,
+    starts: Vec<i32>,
+}
+
+impl Block {
+    fn first_start(&self) -> i32 {
+        self.base + self.starts[0]
+    }
+}
+
+impl LineIndex {
+    pub fn build(text: &str) -> Self {
+        let mut index = LineIndex { blocks: vec![Block { base: 0, first_line: 0, starts: vec![0] }], len: 0 };
+        index.insert(0, text);
+        index
+    }
+
+    /// The block holding the line that contains byte offset `pos`.
+    fn block_at(&self, pos: i32) -> usize {
+        self.blocks.partition_point(|b| b.first_start() <= pos).saturating_sub(1)
+    }
+
+    /// The block holding `line`.
+    fn block_of_line(&self, line: usize) -> usize {
+        self.blocks.partition_point(|b| b.first_line <= line).saturating_sub(1)
+    }
+
+    /// Records `text` being inserted at byte offset `pos`.
+    pub fn insert(&mut self, pos: i32, text: &str) {
+        let len = text.len() as i32;
+        if len == 0 {
+            return;
+        }
+        self.len += len;
+        let at = self.block_at(pos);
+        let block = &mut self.blocks[at];
+        let split = block.starts.partition_point(|&s| s <= pos - block.base);
+        for start in &mut block.starts[split..] {
+            *start += len;
+        }
+        let (base, before) = (block.base, block.starts.len());
+        let new_starts = text.match_indices('\n').map(|(i, _)| pos + i as i32 + 1 - base);
+        block.starts.splice(split..split, new_starts);
+        let added = block.starts.len() - before;
+        for later in &mut self.blocks[at + 1..] {
+            later.base += len;
+            later.first_line += added;
+        }
+
+        if self.blocks[at].starts.len() > 2 * BLOCK {
+            let block = self.blocks.remove(at);
+            let pieces = block.starts.chunks(BLOCK).enumerate().map(|(i, starts)| Block {
+                base: block.base,
+                first_line: block.first_line + i * BLOCK,
+                starts: starts.to_vec(),
+            });
+            self.blocks.splice(at..at, pieces);
+        }
+    }
+
+    /// Records `len` bytes being removed at byte offset `pos`.
+    pub fn delete(&mut self, pos: i32, len: i32) {
+        if len <= 0 {
+            return;
+        }
+        self.len -= len;
+        let at = self.block_at(pos);
+        let mut removed = 0;
+        for block in &mut self.blocks[at..] {
+            block.first_line -= removed;
+            if block.first_start() > pos + len {
+                block.base -= len;
+                continue;
+            }
+            let first = block.starts.partition_point(|&s| s <= pos - block.base);
+            let last = block.starts.partition_point(|&s| s <= pos + len - block.base);
+            block.starts.drain(first..last);
+            for start in &mut block.starts[first..] {
+                *start -= len;
+            }
+            removed += last - first;
+        }
+        // The first line always starts at 0, so the first block never empties.
+        self.blocks.retain(|b| !b.starts.is_empty());
+    }
+
+    pub fn line_count(&self) -> i32 {
+        let last = &self.blocks[self.blocks.len() - 1];
+        (last.first_line + last.starts.len()) as i32
+    }
+
+    /// Zero-based line containing byte offset `pos`.
+    pub fn line_of(&self, pos: i32) -> i32 {
+        let block = &self.blocks[self.block_at(pos)];
+        let within = block.starts.partition_point(|&s| s <= pos - block.base).saturating_sub(1);
+        (block.first_line + within) as i32
+    }
+
+    pub fn line_start(&self, line: i32) -> i32 {
+        let line = line.clamp(0, self.line_count() - 1) as usize;
+        let block = &self.blocks[self.block_of_line(line)];
+        block.base + block.starts[line - block.first_line]
+    }
+
+    /// Byte offset of the end of `line`, before its newline.
+    pub fn line_end(&self, line: i32) -> i32 {
+        if line + 1 < self.line_count() {
+            self.line_start(line + 1) - 1
+        } else {
+            self.len
+        }
+    }
+}
+
+/// One-based char column and tab-expanded display column of the position right
+/// after `line_prefix`, which is the text between a line start and the cursor.
+pub fn columns(line_prefix: &str, tab_width: i32) -> (usize, usize) {
+    let tab_width = tab_width.max(1) as usize;
+    let mut chars = 0;
+    let mut display = 0;
+    for c in line_prefix.chars() {
+        chars += 1;
+        display = if c == '\t' {
+            (display / tab_width + 1) * tab_width
+        } else {
+            display + 1
+        };
+    }
+    (chars + 1, display + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks every lookup against the line starts worked out from `text` directly.
+    fn check(index: &LineIndex, text: &str) {
+        let starts: Vec<i32> =
+            std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i as i32 + 1)).collect();
+        assert_eq!(index.line_count(), starts.len() as i32);
+        for (line, &start) in starts.iter().enumerate() {
+            assert_eq!(index.line_start(line as i32), start);
+            let end = starts.get(line + 1).map_or(text.len() as i32, |next| next - 1);
+            assert_eq!(index.line_end(line as i32), end);
+        }
+        for pos in 0..=text.len() as i32 {
+            let line = starts.partition_point(|&s| s <= pos) - 1;
+            assert_eq!(index.line_of(pos), line as i32, "line of {}", pos);
+        }
+    }
+
+    #[test]
+    fn build() {
+        for text in ["", "one line", "a\nb", "a\n", "\n\n\n", "x\n\ny\nz\n"] {
+            check(&LineIndex::build(text), text);
+        }
+    }
+
+    #[test]
+    fn edits() {
+        let mut text = String::from("alpha\nbeta\ngamma");
+        let mut index = LineIndex::build(&text);
+        let edits: [(usize, usize, &str); 8] = [
+            (0, 0, "start\n"),
+            (text.len() + 6, 0, "\nend"),
+            (3, 0, "x"),
+            (8, 4, ""),
+            (0, 6, "\n\n"),
+            (2, 3, "q\nr\ns"),
+            (0, 0, ""),
+            (1, 1, "\n"),
+        ];
+        for (pos, removed, inserted) in edits {
+            let removed = removed.min(text.len() - pos);
+            text.replace_range(pos..pos + removed, inserted);
+            index.delete(pos as i32, removed as i32);
+            index.insert(pos as i32, inserted);
+            check(&index, &text);
+        }
+    }
+
+    #[test]
+    fn edits_across_blocks() {
+        let mut text: String = (0..5000).map(|i| format!("{}\n", i)).collect();
+        let mut index = LineIndex::build(&text);
+        check(&index, &text);
+
+        // Join a few thousand lines spanning several blocks, then split some again.
+        let (from, to) = (text.find("1000\n").unwrap(), text.find("3500\n").unwrap());
+        text.replace_range(from..to, "joined ");
+        index.delete(from as i32, (to - from) as i32);
+        index.insert(from as i32, "joined ");
+        check(&index, &text);
+
+        let pos = text.find("joined").unwrap();
+        let lines = "new\n".repeat(3000);
+        text.insert_str(pos, &lines);
+        index.insert(pos as i32, &lines);
+        check(&index, &text);
+
+        index.delete(0, text.len() as i32);
+        text.clear();
+        check(&index, &text);
+    }
+}
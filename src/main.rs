@@ -1,28 +1,98 @@
+mod backup;
+mod chord;
 mod config;
 mod config_watcher;
+mod diff;
+mod document;
+mod encoding;
+mod file_io;
+mod file_watcher;
 mod help;
+mod history;
+mod key_combo;
+mod line_ending;
+mod gitignore;
+mod line_index;
+mod panes;
+mod project_search;
+mod regex;
+mod search;
 mod status_dot;
+mod swap;
+mod syntax {
+    pub mod grammar;
+    pub mod highlighter;
+    pub mod registry;
+    pub mod rules;
+}
+mod tabs;
+mod undo;
+mod viewport;
 mod widgets {
     pub mod dot;
+    pub mod find_bar;
+    pub mod gutter;
+    pub mod key_hint;
+    pub mod minimap;
     pub mod scrollbar;
+    pub mod tab_bar;
 }
 mod dialog {
+    pub mod buffer_switcher;
+    pub mod diff_viewer;
+    pub mod external_change;
+    pub mod history_browser;
+    pub mod popup_choice;
+    pub mod project_search;
+    pub mod recover_swap;
     pub mod system_file_chooser;
+    pub mod undo_tree;
+    pub mod unsaved_changes;
 }
 use config::{Config, Binding};
+use chord::{Chord, ChordStep};
+use tabs::{Parked, Tabs};
 use status_dot::{StatusDotState, update_status_dot, show_status_dot_timed, refresh_status_dot};
-use dialog::system_file_chooser::system_file_chooser;
+use dialog::buffer_switcher::buffer_switcher;
+use dialog::system_file_chooser::{system_file_chooser, system_save_file_chooser};
+use dialog::unsaved_changes::{UnsavedChoice, unsaved_changes_prompt};
+use dialog::popup_choice::popup_choice;
+use dialog::external_change::{ExternalChangeChoice, external_change_prompt, lossy_overwrite_prompt, overwrite_prompt};
+use dialog::diff_viewer::show_diff;
+use dialog::history_browser::history_browser;
+use dialog::project_search::{ProjectAction, ProjectQuery, project_search as project_search_dialog};
+use dialog::recover_swap::{RecoverChoice, recover_swap_prompt};
+use dialog::undo_tree::undo_tree_browser;
+use document::Document;
+use encoding::Encoding;
+use line_ending::LineEnding;
+use line_index::LineIndex;
+use panes::{Pane, Panes, Rect, Split};
+use project_search::FileReplace;
+use search::{FindState, Search};
+use syntax::grammar::{Token, matched_style, unmatched_style};
+use syntax::highlighter::Highlighter;
+use syntax::registry::Registry;
+use undo::{Edit, Step, UndoTree};
+use widgets::find_bar::{FindBar, FindOption};
+use widgets::gutter::{Gutter, GutterView};
+use widgets::key_hint::KeyHint;
+use widgets::minimap::Minimap;
+use widgets::scrollbar::{Marker, MarkerKind};
+use widgets::tab_bar::TabBar;
 
 use fltk::{
     app, window::Window, text::TextEditor, text::TextBuffer,
+    text::{StyleTableEntryExt, TextAttr},
     enums::{Font},
     prelude::*,
     frame::Frame,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::env;
-use std::fs;
+use std::io;
+use std::path::PathBuf;
 use std::sync::mpsc::channel;
 
 #[cfg(target_os = "linux")]
@@ -51,6 +121,7 @@ fn load_config_and_apply(
     blink_callback: Rc<RefCell<Option<Box<dyn FnMut(app::TimeoutHandle)>>>>,
     editor_clone: Rc<RefCell<TextEditor>>,
     status_label: &mut Frame,
+    file_info_label: &mut Frame,
     dot: Option<&mut widgets::dot::Dot>,
     scrollbar: Option<&mut widgets::scrollbar::ScrollBar>,
 ) {
@@ -60,7 +131,6 @@ fn load_config_and_apply(
 
     let background = c.color_from_str(&c.background);
     let foreground = c.color_from_str(&c.foreground);
-    let sel = c.color_from_str(&c.selection_color);
     let font = Font::by_name(&c.font_family);
 
     wind.set_color(background);
@@ -71,27 +141,24 @@ fn load_config_and_apply(
     header.set_label_size(c.font_size);
     header.redraw();
 
-    editor.borrow_mut().set_color(background);
-    editor.borrow_mut().set_text_color(foreground);
-    editor.borrow_mut().set_cursor_style(c.cursor_style.to_fltk_cursor());
-    editor.borrow_mut().set_cursor_color(foreground);
-    editor.borrow_mut().set_selection_color(sel);
-
-    editor.borrow_mut().set_text_font(font);
-    editor.borrow_mut().set_text_size(c.font_size);
+    style_editor(&mut editor.borrow_mut(), c);
 
     status_label.set_color(background);
     status_label.set_label_color(foreground);
     status_label.set_label_font(font);
     status_label.set_label_size(c.font_size);
 
+    file_info_label.set_color(background);
+    file_info_label.set_label_color(foreground);
+    file_info_label.set_label_font(font);
+    file_info_label.set_label_size(c.font_size);
+
     if let Some(dot) = dot {
         refresh_status_dot(Some(dot), &cfg.borrow().theme);
     }
 
     if let Some(sb) = scrollbar {
-        sb.set_colors(background, foreground);
-        sb.set_style(cfg.borrow().theme.scrollbar_style.into());
+        style_scrollbar(sb, c);
     }
 
     if let Some(handle) = blink_timeout_handle.borrow_mut().take() {
@@ -141,23 +208,51 @@ fn load_config_and_apply(
     }
 }
 
+fn style_editor(editor: &mut TextEditor, theme: &config::Theme) {
+    let foreground = theme.color_from_str(&theme.foreground);
+    editor.set_color(theme.color_from_str(&theme.background));
+    editor.set_text_color(foreground);
+    editor.set_cursor_style(theme.cursor_style.to_fltk_cursor());
+    editor.set_cursor_color(foreground);
+    editor.set_selection_color(theme.color_from_str(&theme.selection_color));
+    editor.set_text_font(Font::by_name(&theme.font_family));
+    editor.set_text_size(theme.font_size);
+}
+
+fn style_scrollbar(scrollbar: &mut widgets::scrollbar::ScrollBar, theme: &config::Theme) {
+    scrollbar.set_colors(theme.color_from_str(&theme.background), theme.color_from_str(&theme.foreground));
+    scrollbar.set_state_colors(
+        theme.color_from_str(&theme.scrollbar_hover_color),
+        theme.color_from_str(&theme.scrollbar_active_color),
+    );
+    scrollbar.set_style(theme.scrollbar_style.into());
+    scrollbar.set_auto_hide(theme.scrollbar_auto_hide);
+    // The same colours as the highlight table, which has every token twice.
+    let palette = (0..2)
+        .flat_map(|_| Token::ALL.iter())
+        .map(|&token| theme.color_from_str(token.color(&theme.syntax).unwrap_or(&theme.foreground)))
+        .collect();
+    scrollbar.set_minimap_colors(
+        theme.color_from_str(&theme.foreground),
+        theme.color_from_str(&theme.minimap_view_color),
+        palette,
+    );
+}
+
 fn get_max_top(
     editor: &TextEditor,
     scrollbar: &mut widgets::scrollbar::ScrollBar,
+    line_index: &LineIndex,
 ) -> i32 {
-    let buf = editor.buffer().unwrap();
-    let line_height = (editor.text_size() as f32 * 1.4) as i32;
-    let visible_lines = (editor.height() / line_height).max(1);
-    let total_lines = buf.count_lines(0, buf.length());
-    let last_visible_line = if total_lines > 0 { total_lines - 1 } else { 0 };
-    let max_top = if last_visible_line < visible_lines {
-        0
-    } else {
-        last_visible_line.saturating_sub(visible_lines - 1)
-    };
+    fit_scrollbar(editor, scrollbar, line_index.line_count())
+}
 
+/// Sets the scrollbar range for a buffer of `total_lines` and returns the highest top line.
+fn fit_scrollbar(editor: &TextEditor, scrollbar: &mut widgets::scrollbar::ScrollBar, total_lines: i32) -> i32 {
+    let max_top = viewport::max_top(editor, total_lines);
     scrollbar.set_range(0, max_top);
-    if max_top > 0 {
+    scrollbar.set_page(viewport::visible_lines(editor));
+    if max_top > 0 || scrollbar.shows_minimap() {
         scrollbar.show();
     } else {
         scrollbar.hide();
@@ -166,6 +261,487 @@ fn get_max_top(
     max_top
 }
 
+fn set_modified(
+    document: &Rc<RefCell<Document>>,
+    value: bool,
+    wind: &Rc<RefCell<Window>>,
+    status_dot: &Rc<RefCell<Option<widgets::dot::Dot>>>,
+    tab_bar: &Rc<RefCell<TabBar>>,
+    theme: &config::Theme,
+) {
+    if document.borrow().modified == value {
+        return;
+    }
+    document.borrow_mut().modified = value;
+    wind.borrow_mut().set_label(&document.borrow().window_title());
+    tab_bar.borrow_mut().set_active_modified(value);
+    // An error the dot shows, such as a failed save or a missing file, stays until it is resolved.
+    let failed = status_dot
+        .borrow()
+        .as_ref()
+        .is_some_and(|dot| matches!(dot.current_state, StatusDotState::Negative));
+    if !failed {
+        let state = if value { StatusDotState::Modified } else { StatusDotState::Hidden };
+        update_status_dot(status_dot.borrow_mut().as_mut(), state, theme.font_size, theme);
+    }
+}
+
+fn track_modifications(
+    buf: &mut TextBuffer,
+    document: Rc<RefCell<Document>>,
+    wind: Rc<RefCell<Window>>,
+    status_dot: Rc<RefCell<Option<widgets::dot::Dot>>>,
+    tab_bar: Rc<RefCell<TabBar>>,
+    cfg: Rc<RefCell<Config>>,
+) {
+    buf.add_modify_callback(move |_pos, inserted, deleted, _restyled, _deleted_text| {
+        if inserted > 0 || deleted > 0 {
+            document.borrow_mut().swap_pending = true;
+            set_modified(&document, true, &wind, &status_dot, &tab_bar, &cfg.borrow().theme);
+        }
+    });
+}
+
+/// Keeps `line_index` up to date with edits to `buf`. The index itself has to be built
+/// from the text before the buffer is shown.
+fn track_line_index(buf: &mut TextBuffer, line_index: Rc<RefCell<LineIndex>>) {
+    let reader = buf.clone();
+    buf.add_modify_callback(move |pos, inserted, deleted, _restyled, _deleted_text| {
+        let mut index = line_index.borrow_mut();
+        index.delete(pos, deleted);
+        if inserted > 0
+            && let Some(text) = reader.text_range(pos, pos + inserted)
+        {
+            index.insert(pos, &text);
+        }
+    });
+}
+
+/// Records edits to `buf` in `undo`. Large documents keep no history, since every
+/// edit would hold a copy of the text it replaced.
+fn track_undo(buf: &mut TextBuffer, undo: Rc<RefCell<UndoTree>>, document: Rc<RefCell<Document>>) {
+    let reader = buf.clone();
+    buf.add_modify_callback(move |pos, inserted, deleted, _restyled, deleted_text| {
+        if (inserted == 0 && deleted == 0) || undo.borrow().applying || document.borrow().large {
+            return;
+        }
+        let inserted = if inserted > 0 {
+            reader.text_range(pos, pos + inserted).unwrap_or_default()
+        } else {
+            String::new()
+        };
+        undo.borrow_mut().record(Edit {
+            pos,
+            deleted: deleted_text.to_string(),
+            inserted,
+        });
+    });
+}
+
+/// Loads the persisted undo tree for `document` when enabled, or starts a fresh one.
+fn restore_undo(document: &Document, cfg: &Config, buf: &TextBuffer) -> UndoTree {
+    let restored = if cfg.editor.persistent_undo && !document.large {
+        UndoTree::load(&document.path, &buf.text())
+    } else {
+        None
+    };
+    restored.unwrap_or_default()
+}
+
+/// Reads the file at `path` into `buf` a chunk at a time, indexing its lines as the
+/// chunks arrive, so the text is never copied out of the buffer in full. Large files are
+/// appended chunk by chunk and the window redrawn in between, without handling events,
+/// so nothing can type into or save the half-loaded text.
+fn load_document(buf: &mut TextBuffer, path: &str, large_file_threshold: f64) -> io::Result<(Document, LineIndex)> {
+    let mut document = Document::new(path);
+    document.update_large(large_file_threshold);
+    let large = document.large;
+    let mut index = LineIndex::build("");
+    let mut loaded = 0;
+    let mut small = String::new();
+    document.read_into(|chunk| {
+        index.insert(loaded, chunk);
+        loaded += chunk.len() as i32;
+        if !large {
+            small.push_str(chunk);
+            return;
+        }
+        // Cleared only once the file reads, so a failed reload leaves the text alone.
+        if loaded == chunk.len() as i32 {
+            buf.set_text("");
+        }
+        buf.append(chunk);
+        app::redraw();
+        app::flush();
+    })?;
+    if !large {
+        buf.set_text(&small);
+    }
+    Ok((document, index))
+}
+
+/// Replaces only the part of `buf` where `old` and `new` differ, so the change is a
+/// single undo step and the cursor keeps its place when it's outside that part.
+fn replace_changed(buf: &mut TextBuffer, old: &str, new: &str) {
+    let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = old[prefix..]
+        .bytes()
+        .rev()
+        .zip(new[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    buf.replace(prefix as i32, (old.len() - suffix) as i32, &new[prefix..new.len() - suffix]);
+}
+
+/// Resets `style` to plain text for a buffer of `len` bytes. Large documents get an
+/// empty style buffer and therefore no highlighting.
+fn fit_styles(style: &TextBuffer, len: i32, large: bool) {
+    let mut style = style.clone();
+    if large {
+        style.set_text("");
+    } else {
+        style.set_text(&"A".repeat(len as usize));
+    }
+}
+
+/// Keeps `style` the same length as `buf` while it is shown, so highlight data lines up
+/// with the text.
+fn track_styles(buf: &mut TextBuffer, style: &TextBuffer, large: bool) {
+    if large {
+        return;
+    }
+    let mut style = style.clone();
+    buf.add_modify_callback(move |pos, inserted, deleted, _restyled, _deleted_text| {
+        if deleted > 0 {
+            style.remove(pos, pos + deleted);
+        }
+        if inserted > 0 {
+            style.insert(pos, &"A".repeat(inserted as usize));
+        }
+    });
+}
+
+/// Starts highlighting `buf` with the grammar the registry picks for `document`. Large
+/// files are left plain.
+fn detect_highlighter(registry: &Registry, document: &Document, buf: &TextBuffer) -> Highlighter {
+    if document.large {
+        return Highlighter::default();
+    }
+    let start = buf.text_range(0, buf.length().min(256)).unwrap_or_default();
+    Highlighter::new(registry.detect(&document.path, start.lines().next().unwrap_or("")))
+}
+
+/// Tells `highlighter` which lines edits to `buf` touch, then calls `schedule` to
+/// highlight them again.
+fn track_highlight(
+    buf: &mut TextBuffer,
+    highlighter: Rc<RefCell<Highlighter>>,
+    line_index: Rc<RefCell<LineIndex>>,
+    schedule: Rc<dyn Fn()>,
+) {
+    let reader = buf.clone();
+    buf.add_modify_callback(move |pos, inserted, deleted, _restyled, deleted_text| {
+        if inserted == 0 && deleted == 0 {
+            return;
+        }
+        // Text before `pos` is unchanged, so its line is the same before and after the
+        // edit whichever order the modify callbacks run in.
+        let line = line_index.borrow().line_of(pos) as usize;
+        let removed = deleted_text.matches('\n').count();
+        let added = if inserted > 0 {
+            reader.text_range(pos, pos + inserted).unwrap_or_default().matches('\n').count()
+        } else {
+            0
+        };
+        highlighter.borrow_mut().edit(line, removed, added);
+        schedule();
+    });
+}
+
+fn apply_highlight_table(editor: &mut TextEditor, style: &TextBuffer, theme: &config::Theme) {
+    let font = Font::by_name(&theme.font_family);
+    let background = theme.color_from_str(&theme.background);
+    let match_color = theme.color_from_str(&theme.match_color);
+    let color = |token: Token| theme.color_from_str(token.color(&theme.syntax).unwrap_or(&theme.foreground));
+    let entry = |token, attr, bgcolor| StyleTableEntryExt {
+        color: color(token),
+        font,
+        size: theme.font_size,
+        attr,
+        bgcolor,
+    };
+    // One entry per token, then the same with the match background, in style byte order.
+    let mut entries: Vec<StyleTableEntryExt> =
+        Token::ALL.iter().map(|&token| entry(token, TextAttr::None, background)).collect();
+    entries.extend(Token::ALL.iter().map(|&token| entry(token, TextAttr::BgColor, match_color)));
+    editor.set_highlight_data_ext(style.clone(), entries);
+}
+
+/// Replaces the search match markers on `scrollbar` with one per line range in `matches`.
+fn mark_matches(
+    scrollbar: &mut widgets::scrollbar::ScrollBar,
+    line_index: &LineIndex,
+    matches: &[(usize, usize)],
+    theme: &config::Theme,
+) {
+    scrollbar.clear_markers(MarkerKind::SearchMatch);
+    let color = theme.color_from_str(&theme.search_marker_color);
+    scrollbar.add_markers(matches.iter().map(|&(start, end)| Marker {
+        kind: MarkerKind::SearchMatch,
+        start: line_index.line_of(start as i32),
+        end: line_index.line_of((end as i32 - 1).max(start as i32)) + 1,
+        color,
+    }));
+}
+
+/// Clears the match style from every position and marks `matches` with it, keeping the
+/// syntax styles underneath.
+fn paint_matches(style: &mut TextBuffer, matches: &[(usize, usize)]) {
+    let len = style.length() as usize;
+    if len == 0 {
+        return;
+    }
+    let mut styles: Vec<u8> = style.text().bytes().map(unmatched_style).collect();
+    for &(start, end) in matches.iter().filter(|m| m.1 <= len) {
+        for b in &mut styles[start..end] {
+            *b = matched_style(*b);
+        }
+    }
+    // Every style byte is ASCII.
+    style.set_text(&String::from_utf8(styles).unwrap_or_default());
+}
+
+fn selection_or_cursor(editor: &TextEditor) -> (usize, usize) {
+    let pos = editor.insert_position() as usize;
+    editor
+        .buffer()
+        .and_then(|buf| buf.selection_position())
+        .map(|(start, end)| (start as usize, end as usize))
+        .unwrap_or((pos, pos))
+}
+
+/// Scrolls so `line` is visible, centering it when it was off screen, and then scrolls
+/// sideways if the cursor is still out of view.
+fn reveal_line(
+    editor: &mut TextEditor,
+    scrollbar: &mut widgets::scrollbar::ScrollBar,
+    hscrollbar: &widgets::scrollbar::ScrollBar,
+    line_index: &LineIndex,
+    top_line: &mut i32,
+    line: i32,
+) {
+    let max_top = get_max_top(editor, scrollbar, line_index);
+    let visible_lines = viewport::visible_lines(editor);
+    if line < *top_line || line >= *top_line + visible_lines {
+        *top_line = (line - visible_lines / 2).clamp(0, max_top);
+    }
+    viewport::scroll_to(editor, *top_line, hscrollbar.value());
+    editor.show_insert_position();
+    scrollbar.set_value(*top_line);
+}
+
+/// Puts a pane's gutter, editor and scrollbars in the box `x`, `y`, `w`, `h`.
+fn place_pane(
+    editor: &mut TextEditor,
+    scrollbar: &mut widgets::scrollbar::ScrollBar,
+    hscrollbar: &mut widgets::scrollbar::ScrollBar,
+    gutter: &mut Gutter,
+    (x, y, w, h): Rect,
+) {
+    // The vertical scrollbar is wider than the horizontal one is tall when it is a minimap.
+    let scrollbar_width = scrollbar.widget.w();
+    let hscrollbar_height = hscrollbar.widget.h();
+    let gutter_width = gutter.fitting_width();
+    let editor_width = w - gutter_width - scrollbar_width;
+    gutter.widget.resize(x, y, gutter_width, h);
+    editor.resize(x + gutter_width, y, editor_width, h - hscrollbar_height);
+    scrollbar.set_pos(x + w - scrollbar_width, y);
+    scrollbar.set_size(scrollbar_width, h);
+    hscrollbar.set_pos(x + gutter_width, y + h - hscrollbar_height);
+    hscrollbar.set_size(editor_width, hscrollbar_height);
+}
+
+/// Scrolls a pane for a mouse wheel or touchpad event, `step` lines at a time up and down
+/// and as many line heights sideways, and returns the new first line.
+fn scroll_by_wheel(
+    editor: &mut TextEditor,
+    scrollbar: &mut widgets::scrollbar::ScrollBar,
+    hscrollbar: &mut widgets::scrollbar::ScrollBar,
+    step: i32,
+) -> i32 {
+    let top = match app::event_dy() {
+        app::MouseWheel::Up => scrollbar.value() - step,
+        app::MouseWheel::Down => scrollbar.value() + step,
+        _ => scrollbar.value(),
+    };
+    let sideways = step * viewport::line_height(editor);
+    let left = match app::event_dx() {
+        app::MouseWheel::Left => hscrollbar.value() - sideways,
+        app::MouseWheel::Right => hscrollbar.value() + sideways,
+        _ => hscrollbar.value(),
+    };
+    scrollbar.set_value(top);
+    hscrollbar.set_value(left);
+    viewport::scroll_to(editor, scrollbar.value(), hscrollbar.value());
+    scrollbar.value()
+}
+
+/// The event handler shared by every pane's editor.
+type EditorEvents = Rc<dyn Fn(&mut TextEditor, fltk::enums::Event) -> bool>;
+
+/// Selects whole lines in the pane of an editor, from the line a gutter click started on
+/// to the line under the mouse.
+type GutterSelect = Rc<dyn Fn(&TextEditor, i32, i32)>;
+
+fn pane_editor(x: i32, y: i32, w: i32, h: i32) -> TextEditor {
+    let mut editor = TextEditor::new(x, y, w, h, "");
+    editor.set_scrollbar_align(fltk::enums::Align::Inside);
+    editor.set_frame(fltk::enums::FrameType::NoBox);
+    editor
+}
+
+/// The vertical and horizontal scrollbars for `editor`. The vertical one also scrolls the
+/// gutter, and keeps `top_line` in step while that editor has focus.
+fn pane_scrollbars(
+    editor: &TextEditor,
+    gutter: &Gutter,
+    focused: &Rc<RefCell<TextEditor>>,
+    top_line: &Rc<RefCell<i32>>,
+    width: i32,
+) -> (widgets::scrollbar::ScrollBar, widgets::scrollbar::ScrollBar) {
+    let mut scrollbar = widgets::scrollbar::ScrollBar::new(editor.x() + editor.w(), editor.y(), width, editor.h());
+    let mut hscrollbar = widgets::scrollbar::ScrollBar::horizontal(editor.x(), editor.y() + editor.h(), editor.w(), width);
+    hscrollbar.set_range(0, 0);
+    scrollbar.set_minimap(Minimap::new(editor));
+    scrollbar.set_on_change({
+        let editor = editor.clone();
+        let gutter = gutter.clone();
+        let hscrollbar = hscrollbar.clone();
+        let focused = focused.clone();
+        let top_line = top_line.clone();
+        move |val| {
+            viewport::scroll_to(&mut editor.clone(), val, hscrollbar.value());
+            gutter.clone().set_top(val);
+            if *focused.borrow() == editor {
+                *top_line.borrow_mut() = val;
+            }
+        }
+    });
+    hscrollbar.set_on_change({
+        let editor = editor.clone();
+        let gutter = gutter.clone();
+        // The gutter knows the first line shown, which saves the two scrollbars holding
+        // on to each other.
+        move |val| viewport::scroll_to(&mut editor.clone(), gutter.view().top, val)
+    });
+    (scrollbar, hscrollbar)
+}
+
+/// A gutter for `editor`; clicks in it go through `select`.
+fn pane_gutter(editor: &TextEditor, select: &Rc<RefCell<Option<GutterSelect>>>) -> Gutter {
+    let mut gutter = Gutter::new(editor.x(), editor.y(), 0, editor.h());
+    gutter.set_on_select({
+        let editor = editor.clone();
+        let select = select.clone();
+        move |from, to| {
+            let select = select.borrow().clone();
+            if let Some(select) = select {
+                select(&editor, from, to);
+            }
+        }
+    });
+    gutter
+}
+
+/// Keeps a pane's gutter and scrollbars, and while it has focus `top_line`, in step with
+/// what `editor` shows. It runs every time the editor draws, so scrolling from the
+/// keyboard, the mouse or code is all picked up the same way.
+fn follow_view(
+    editor: &mut TextEditor,
+    gutter: &Gutter,
+    scrollbar: &widgets::scrollbar::ScrollBar,
+    hscrollbar: &widgets::scrollbar::ScrollBar,
+    focused: &Rc<RefCell<TextEditor>>,
+    line_index: &Rc<RefCell<LineIndex>>,
+    top_line: &Rc<RefCell<i32>>,
+) {
+    editor.draw({
+        let mut gutter = gutter.clone();
+        let mut scrollbar = scrollbar.clone();
+        let mut hscrollbar = hscrollbar.clone();
+        let focused = focused.clone();
+        let line_index = line_index.clone();
+        let top_line = top_line.clone();
+        move |ed| {
+            let (Ok(focused), Ok(index)) = (focused.try_borrow(), line_index.try_borrow()) else { return };
+            let Some(buf) = ed.buffer() else { return };
+            let is_focused = *focused == *ed;
+            // Only the focused buffer has a line index; panes showing another buffer keep
+            // the view they were given when laid out or scrolled.
+            if !is_focused && focused.buffer().as_ref() != Some(&buf) {
+                return;
+            }
+            let view = gutter.view();
+            let top = viewport::first_visible_line(ed, &index, view.top).unwrap_or(view.top);
+            let current = index.line_of(ed.insert_position());
+            gutter.set_view(GutterView { top, lines: index.line_count(), current });
+            if gutter.fitting_width() != gutter.widget.w() {
+                let mut gutter = gutter.clone();
+                let mut ed = ed.clone();
+                app::add_timeout3(0.0, move |_| gutter.fit(&mut ed));
+            }
+
+            // The editor can scroll a little past the last full screen to show the end.
+            let max_top = viewport::max_top(ed, index.line_count()).max(top);
+            scrollbar.set_range(0, max_top);
+            // A minimap is worth showing even when there is nothing to scroll.
+            let wanted = max_top > 0 || scrollbar.shows_minimap();
+            if wanted != scrollbar.widget.visible() {
+                if wanted {
+                    scrollbar.show();
+                } else {
+                    scrollbar.hide();
+                }
+            }
+            scrollbar.set_page(viewport::visible_lines(ed));
+            scrollbar.set_value(top);
+            if scrollbar.shows_minimap() {
+                // The minimap draws the text, so it changes whenever the editor does.
+                scrollbar.widget.redraw();
+            }
+            if let Some((left, widest)) = viewport::horizontal_extent(ed, &index, top) {
+                let text_width = viewport::text_width(ed);
+                hscrollbar.set_page(text_width);
+                hscrollbar.set_range(0, (widest - text_width).max(left).max(0));
+                hscrollbar.set_value(left);
+            }
+            if is_focused && let Ok(mut top_line) = top_line.try_borrow_mut() {
+                *top_line = top;
+            }
+        }
+    });
+}
+
+/// Byte offset `col` bytes into `line`, kept within the line and on a character boundary.
+fn position_for_line_col(buf: &TextBuffer, index: &LineIndex, line: usize, col: usize) -> usize {
+    if line as i32 >= index.line_count() {
+        return buf.length() as usize;
+    }
+    let start = index.line_start(line as i32);
+    let text = buf.text_range(start, index.line_end(line as i32)).unwrap_or_default();
+    let mut col = col.min(text.len());
+    while !text.is_char_boundary(col) {
+        col -= 1;
+    }
+    start as usize + col
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.iter().any(|a| a == "-h" || a == "--help") {
@@ -173,55 +749,48 @@ fn main() {
         return;
     }
 
+    swap::install_panic_hook();
     fontconfig_init::init();
-    let cfg = Rc::from(RefCell::from(Config::default()));
+    let cfg = Rc::from(RefCell::from(Config::load()));
     let app = app::App::default();
 
     let args: Vec<String> = env::args().collect();
     let file_path = if args.len() > 1 { args[1].clone() } else { "out.txt".to_string() };
-    let abs_path = fs::canonicalize(&file_path)
-        .map(|p| p.display().to_string())
-        .unwrap_or(file_path.clone());
-    let file_name = std::path::Path::new(&file_path)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("untitled");
-
-    let window_title = format!("Skrift - {}", file_name);
-    let wind = Rc::new(RefCell::new(Window::new(100, 100, 800, 600, window_title.as_str())));
-    let header = Rc::new(RefCell::new(Frame::new(0, 0, 800, 30, abs_path.as_str())));
+    let more_paths: Vec<String> = args.iter().skip(2).cloned().collect();
+    let document = Rc::new(RefCell::new(Document::new(&file_path)));
+
+    let wind = Rc::new(RefCell::new(Window::new(100, 100, 800, 600, document.borrow().window_title().as_str())));
+    let header = Rc::new(RefCell::new(Frame::new(0, 0, 800, 30, document.borrow().abs_path().as_str())));
+
+    let tab_bar = Rc::new(RefCell::new(TabBar::new(0, 30, 800, 28)));
+    let tabs: Rc<RefCell<Tabs>> = Rc::new(RefCell::new(Tabs::new()));
 
     let pad = 10;
     let scrollbar_width = 12;
-    let editor_height = 540;
+    let editor_top = 58;
+    let editor_height = 600 - editor_top - 30;
     let editor_width = 800 - pad * 2 - scrollbar_width;
-    let editor = Rc::from(RefCell::from(TextEditor::new(
-        pad, 30, editor_width, editor_height, "",
+    // Stands in for the whole editor area when the window resizes; the panes are laid out
+    // inside it. It starts at the left edge, where each pane's gutter goes.
+    let pane_area = Frame::new(0, editor_top, editor_width + scrollbar_width + pad, editor_height, "");
+    let panes: Rc<RefCell<Panes>> = Rc::new(RefCell::new(Panes::new()));
+    let editor = Rc::from(RefCell::from(pane_editor(
+        0, editor_top, editor_width + pad, editor_height,
     )));
-    editor.borrow_mut().set_scrollbar_align(fltk::enums::Align::Inside);
-    editor.borrow_mut().set_frame(fltk::enums::FrameType::NoBox);
-    let top_line = Rc::new(RefCell::new(1));
-
-    let mut scrollbar = widgets::scrollbar::ScrollBar::new(
-        pad + editor_width,
-        30,
-        scrollbar_width,
-        editor_height,
-    );
-    scrollbar.set_style(widgets::scrollbar::ScrollBarStyle::Rounded);
-
-    let top_line_for_scrollbar = top_line.clone();
-    let editor_for_scrollbar = editor.clone();
-    scrollbar.set_on_change(move |val| {
-        editor_for_scrollbar.borrow_mut().scroll(val, 0);
-        *top_line_for_scrollbar.borrow_mut() = val;
-    });
-    scrollbar.show();
-    let scrollbar = Rc::new(RefCell::new(scrollbar));
+    let top_line = Rc::new(RefCell::new(0));
 
-    let mut status_label = Frame::new(
-        pad, 30 + editor_height, editor_width, 30, "",
-    );
+    let status_label = Rc::new(RefCell::new(Frame::new(
+        pad, editor_top + editor_height, editor_width, 30, "",
+    )));
+    let file_info_label = Rc::new(RefCell::new(Frame::new(
+        pad, editor_top + editor_height, editor_width, 30, "",
+    )));
+    file_info_label.borrow_mut().set_align(fltk::enums::Align::Right | fltk::enums::Align::Inside);
+    let find_bar = Rc::new(RefCell::new(FindBar::new(
+        pad, editor_height, editor_width + scrollbar_width, 30,
+    )));
+    let key_hint = Rc::new(RefCell::new(KeyHint::new()));
+    let chord = Rc::new(RefCell::new(Chord::new()));
 
     let file_exists = Rc::new(RefCell::new(std::path::Path::new(&file_path).exists()));
 
@@ -238,15 +807,116 @@ fn main() {
     }
     *status_dot.borrow_mut() = Some(dot);
     let mut buf = TextBuffer::default();
+    let mut loaded_index = LineIndex::build("");
     if std::path::Path::new(&file_path).exists() {
-        if let Ok(contents) = fs::read_to_string(&file_path) {
-            buf.set_text(&contents);
+        match load_document(&mut buf, &file_path, cfg.borrow().editor.large_file_threshold) {
+            Ok((loaded, index)) => {
+                if loaded.lossy {
+                    eprintln!("{} has bytes that could not be read as text", file_path);
+                    let theme = cfg.borrow().theme.clone();
+                    update_status_dot(status_dot.borrow_mut().as_mut(), StatusDotState::Negative, theme.font_size, &theme);
+                }
+                *document.borrow_mut() = loaded;
+                loaded_index = index;
+            }
+            Err(err) => {
+                eprintln!("Failed to read {}: {}", file_path, err);
+                fltk::dialog::alert_default(&format!(
+                    "Could not read {}:\n{}",
+                    file_path,
+                    file_io::describe_error(&err)
+                ));
+            }
         }
     } else {
         println!("File not found: {}", file_path);
     }
+    track_modifications(
+        &mut buf,
+        document.clone(),
+        wind.clone(),
+        status_dot.clone(),
+        tab_bar.clone(),
+        cfg.clone(),
+    );
+    let line_index = Rc::new(RefCell::new(loaded_index));
+    track_line_index(&mut buf, line_index.clone());
+
+    // Filled in once panes can be focused; gutter clicks go through it.
+    let gutter_select: Rc<RefCell<Option<GutterSelect>>> = Rc::new(RefCell::new(None));
+    let gutter = pane_gutter(&editor.borrow(), &gutter_select);
+    let (mut scrollbar, hscrollbar) = pane_scrollbars(&editor.borrow(), &gutter, &editor, &top_line, scrollbar_width);
+    scrollbar.set_style(widgets::scrollbar::ScrollBarStyle::Rounded);
+    scrollbar.show();
+    follow_view(&mut editor.borrow_mut(), &gutter, &scrollbar, &hscrollbar, &editor, &line_index, &top_line);
+    let scrollbar = Rc::new(RefCell::new(scrollbar));
+    let hscrollbar = Rc::new(RefCell::new(hscrollbar));
+    let gutter = Rc::new(RefCell::new(gutter));
+    let undo = Rc::new(RefCell::new(restore_undo(&document.borrow(), &cfg.borrow(), &buf)));
+    track_undo(&mut buf, undo.clone(), document.clone());
+    let style_buf = TextBuffer::default();
+    fit_styles(&style_buf, buf.length(), document.borrow().large);
+    track_styles(&mut buf, &style_buf, document.borrow().large);
+    let find_state = Rc::new(RefCell::new(FindState::default()));
+    let registry = Rc::new(Registry::load());
+    let highlighter = Rc::new(RefCell::new(detect_highlighter(&registry, &document.borrow(), &buf)));
+    // Highlights the shown buffer a slice at a time from a zero-length timeout, so typing
+    // and scrolling stay responsive while a large file catches up.
+    let highlight_scheduled = Rc::new(Cell::new(false));
+    let schedule_highlight: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let panes = panes.clone();
+        let style_buf = style_buf.clone();
+        let line_index = line_index.clone();
+        let find_state = find_state.clone();
+        let highlighter = highlighter.clone();
+        move || {
+            if highlight_scheduled.replace(true) {
+                return;
+            }
+            app::add_timeout3(0.0, {
+                let editor = editor.clone();
+                let panes = panes.clone();
+                let style_buf = style_buf.clone();
+                let line_index = line_index.clone();
+                let find_state = find_state.clone();
+                let highlighter = highlighter.clone();
+                let highlight_scheduled = highlight_scheduled.clone();
+                move |handle| {
+                    let Some(buf) = editor.borrow().buffer() else {
+                        highlight_scheduled.set(false);
+                        return;
+                    };
+                    let mut highlighter = highlighter.borrow_mut();
+                    highlighter.run(
+                        &buf,
+                        &mut style_buf.clone(),
+                        &line_index.borrow(),
+                        &find_state.borrow().matches,
+                        std::time::Duration::from_millis(8),
+                    );
+                    editor.borrow_mut().redraw();
+                    for (_, pane) in panes.borrow_mut().others_mut() {
+                        if pane.editor.buffer().as_ref() == Some(&buf) {
+                            pane.editor.redraw();
+                        }
+                    }
+                    if highlighter.is_dirty() {
+                        app::repeat_timeout3(0.0, handle);
+                    } else {
+                        highlight_scheduled.set(false);
+                    }
+                }
+            });
+        }
+    });
+    track_highlight(&mut buf, highlighter.clone(), line_index.clone(), schedule_highlight.clone());
+    schedule_highlight();
     editor.borrow_mut().set_buffer(buf.clone());
-    get_max_top(&editor.borrow(), &mut scrollbar.borrow_mut());
+    swap::set_crash_target(&buf, &file_path);
+    file_info_label.borrow_mut().set_label(&document.borrow().file_info());
+    tab_bar.borrow_mut().set_tabs(tabs.borrow().labels(&document.borrow()), 0);
+    get_max_top(&editor.borrow(), &mut scrollbar.borrow_mut(), &line_index.borrow());
 
     let blink_state = Rc::from(RefCell::from(true));
     let blink_paused = Rc::from(RefCell::from(false));
@@ -351,32 +1021,117 @@ fn main() {
         blink_timeout_handle.clone(),
         blink_callback.clone(),
         editor_clone.clone(),
-        &mut status_label,
+        &mut status_label.borrow_mut(),
+        &mut file_info_label.borrow_mut(),
         status_dot.borrow_mut().as_mut(),
         Some(&mut *scrollbar.borrow_mut()),
     );
+    let apply_widget_theme: Rc<dyn Fn()> = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let find_bar = find_bar.clone();
+        let key_hint = key_hint.clone();
+        let tab_bar = tab_bar.clone();
+        let gutter = gutter.clone();
+        let hscrollbar = hscrollbar.clone();
+        let panes = panes.clone();
+        let style_buf = style_buf.clone();
+        // Panes showing another buffer than the focused one can't use its styles, and an
+        // empty style buffer draws everything in the plain style.
+        let plain_style = TextBuffer::default();
+        move || {
+            let theme = cfg.borrow().theme.clone();
+            apply_highlight_table(&mut editor.borrow_mut(), &style_buf, &theme);
+            find_bar.borrow_mut().apply_theme(&theme);
+            key_hint.borrow_mut().apply_theme(&theme);
+            tab_bar.borrow_mut().apply_theme(&theme);
+            let line_numbers = cfg.borrow().editor.line_numbers;
+            gutter.borrow_mut().apply_theme(&theme, line_numbers);
+            style_scrollbar(&mut hscrollbar.borrow_mut(), &theme);
+            let shown = editor.borrow().buffer();
+            for (_, pane) in panes.borrow_mut().others_mut() {
+                style_editor(&mut pane.editor, &theme);
+                style_scrollbar(&mut pane.scrollbar, &theme);
+                style_scrollbar(&mut pane.hscrollbar, &theme);
+                pane.gutter.apply_theme(&theme, line_numbers);
+                let style = if pane.editor.buffer() == shown { &style_buf } else { &plain_style };
+                apply_highlight_table(&mut pane.editor, style, &theme);
+                pane.editor.redraw();
+            }
+        }
+    });
+    apply_widget_theme();
 
-    wind.borrow_mut().resizable(&editor.borrow().as_base_widget());
+    wind.borrow_mut().resizable(&pane_area);
     wind.borrow_mut().end();
     wind.borrow_mut().show();
 
-    wind.borrow_mut().handle({
+    let layout_panes: Rc<dyn Fn()> = Rc::new({
+        let wind = wind.clone();
         let editor = editor.clone();
         let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let gutter = gutter.clone();
+        let panes = panes.clone();
+        let find_bar = find_bar.clone();
+        let line_index = line_index.clone();
+        let pane_area = pane_area.clone();
+        move || {
+            let (x, y, w, mut h) = (pane_area.x(), pane_area.y(), pane_area.w(), pane_area.h());
+            {
+                let mut bar = find_bar.borrow_mut();
+                if bar.group.visible() {
+                    let bar_h = bar.group.h();
+                    h -= bar_h;
+                    bar.group.resize(x, y + h, w, bar_h);
+                }
+            }
+            let mut panes = panes.borrow_mut();
+            let focused = panes.focused();
+            let shown = editor.borrow().buffer();
+            for (index, rect) in panes.rects(x, y, w, h) {
+                if index == focused {
+                    let mut ed = editor.borrow_mut();
+                    let mut sb = scrollbar.borrow_mut();
+                    place_pane(&mut ed, &mut sb, &mut hscrollbar.borrow_mut(), &mut gutter.borrow_mut(), rect);
+                    get_max_top(&ed, &mut sb, &line_index.borrow());
+                } else if let Some(pane) = panes.pane_mut(index) {
+                    let lines = match pane.editor.buffer() {
+                        Some(buf) if Some(&buf) == shown.as_ref() => line_index.borrow().line_count(),
+                        Some(buf) => buf.count_lines(0, buf.length()) + 1,
+                        None => 1,
+                    };
+                    let view = GutterView { lines, ..pane.gutter.view() };
+                    pane.gutter.set_view(view);
+                    place_pane(&mut pane.editor, &mut pane.scrollbar, &mut pane.hscrollbar, &mut pane.gutter, rect);
+                    fit_scrollbar(&pane.editor, &mut pane.scrollbar, lines);
+                }
+            }
+            let mut wind = wind.borrow_mut();
+            wind.init_sizes();
+            wind.redraw();
+        }
+    });
+
+    wind.borrow_mut().handle({
+        let layout_panes = layout_panes.clone();
         move |_w, ev| {
             if ev == fltk::enums::Event::Resize {
-                let ed = editor.borrow_mut();
-                let mut sb = scrollbar.borrow_mut();
-                get_max_top(&ed, &mut sb);
+                layout_panes();
             }
             false
         }
     });
-
-    let buf_rc = Rc::from(RefCell::from(buf));
+    // The theme may have widened the scrollbar into a minimap.
+    layout_panes();
 
     let last_cursor_pos = Rc::new(RefCell::new(-1i32));
-    fn update_status_label(editor: &TextEditor, label: &mut Frame, last_pos: &Rc<RefCell<i32>>) {
+    fn update_status_label(
+        editor: &TextEditor,
+        label: &mut Frame,
+        last_pos: &Rc<RefCell<i32>>,
+        line_index: &LineIndex,
+    ) {
         let current_pos = editor.insert_position();
         if current_pos == *last_pos.borrow() {
             return;
@@ -384,110 +1139,1418 @@ fn main() {
         *last_pos.borrow_mut() = current_pos;
 
         if let Some(buf) = editor.buffer() {
-            let text = buf.text();
-            let mut line = 1;
-            let mut col = 1;
-            let mut count = 0;
-            for c in text.chars() {
-                if count == current_pos {
-                    break;
-                }
-                if c == '\n' {
-                    line += 1;
-                    col = 1;
-                } else {
-                    col += 1;
-                }
-                count += 1;
+            let line = line_index.line_of(current_pos);
+            let prefix = buf
+                .text_range(line_index.line_start(line), current_pos)
+                .unwrap_or_default();
+            let (col, display_col) = line_index::columns(&prefix, buf.tab_distance());
+            if col == display_col {
+                label.set_label(&format!("{}, {}", line + 1, col));
+            } else {
+                label.set_label(&format!("{}, {}-{}", line + 1, col, display_col));
             }
-            label.set_label(&format!("{}, {}", line, col));
         }
     }
 
-    let status_dot_clone = status_dot.clone();
-    let last_cursor_pos_clone = last_cursor_pos.clone();
+    let (doc_tx, doc_rx) = channel();
+    let document_watcher: Rc<RefCell<Option<file_watcher::WatchedFile>>> = Rc::new(RefCell::new(None));
+    let watch_document: Rc<dyn Fn()> = Rc::new({
+        let document = document.clone();
+        let document_watcher = document_watcher.clone();
+        move || {
+            let path = document.borrow().path.clone();
+            let mut current = document_watcher.borrow_mut();
+            if current.as_ref().is_some_and(|(watched, _)| *watched == path) {
+                return;
+            }
+            *current = file_watcher::start_file_watcher(std::path::Path::new(&path), doc_tx.clone())
+                .map(|watcher| (path, watcher));
+        }
+    });
+    watch_document();
 
-    editor.borrow_mut().handle({
+    let save_to: Rc<dyn Fn(&str) -> bool> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let file_info_label = file_info_label.clone();
+        let status_dot = status_dot.clone();
+        let tab_bar = tab_bar.clone();
         let cfg = cfg.clone();
-        let buf = buf_rc.clone();
+        let wind = wind.clone();
+        let header = header.clone();
+        let watch_document = watch_document.clone();
+        let undo = undo.clone();
+        let registry = registry.clone();
+        let highlighter = highlighter.clone();
+        let style_buf = style_buf.clone();
+        let find_state = find_state.clone();
+        let schedule_highlight = schedule_highlight.clone();
+        move |path: &str| {
+            let theme = cfg.borrow().theme.clone();
+            let overwrites_external_change = {
+                let doc = document.borrow();
+                doc.path == path && std::path::Path::new(path).exists() && doc.disk_changed()
+            };
+            if overwrites_external_change && !overwrite_prompt(&document.borrow().file_name()) {
+                return false;
+            }
+            let overwrites_lossy = {
+                let doc = document.borrow();
+                doc.lossy && doc.path == path
+            };
+            if overwrites_lossy && !lossy_overwrite_prompt(&document.borrow().file_name()) {
+                return false;
+            }
+            if let Err(err) = backup::backup_before_save(path, &cfg.borrow().files) {
+                eprintln!("Failed to back up {}: {}", path, err);
+            }
+            let text = editor.borrow().buffer().map(|b| b.text()).unwrap_or_default();
+            let result = document
+                .borrow()
+                .encode(&text)
+                .and_then(|bytes| file_io::write_atomic(std::path::Path::new(path), &bytes));
+            if let Err(err) = result {
+                eprintln!("Failed to save {}: {}", path, err);
+                update_status_dot(status_dot.borrow_mut().as_mut(), StatusDotState::Negative, theme.font_size, &theme);
+                fltk::dialog::alert_default(&format!(
+                    "Could not save {}:\n{}",
+                    path,
+                    file_io::describe_error(&err)
+                ));
+                return false;
+            }
+            swap::remove_swap(&document.borrow().path);
+            if document.borrow().path != path {
+                document.borrow_mut().path = path.to_string();
+                wind.borrow_mut().set_label(&document.borrow().window_title());
+                header.borrow_mut().set_label(&document.borrow().abs_path());
+                swap::remove_swap(path);
+                if let Some(buf) = editor.borrow().buffer() {
+                    swap::set_crash_target(&buf, path);
+                    // A new extension may call for another grammar.
+                    let detected = detect_highlighter(&registry, &document.borrow(), &buf);
+                    if detected.name() != highlighter.borrow().name() {
+                        fit_styles(&style_buf, buf.length(), document.borrow().large);
+                        paint_matches(&mut style_buf.clone(), &find_state.borrow().matches);
+                        *highlighter.borrow_mut() = detected;
+                        schedule_highlight();
+                    }
+                }
+            }
+            document.borrow_mut().swap_pending = false;
+            document.borrow_mut().lossy = false;
+            document.borrow_mut().record_disk_stamp();
+            file_info_label.borrow_mut().set_label(&document.borrow().file_info());
+            let files = cfg.borrow().files.clone();
+            if files.history
+                && !document.borrow().large
+                && let Err(err) = history::record(path, &text, files.history_max_age_days)
+            {
+                eprintln!("Failed to record history for {}: {}", path, err);
+            }
+            undo.borrow_mut().mark_saved();
+            if cfg.borrow().editor.persistent_undo
+                && !document.borrow().large
+                && let Err(err) = undo.borrow().save(path, &text)
+            {
+                eprintln!("Failed to save undo history for {}: {}", path, err);
+            }
+            watch_document();
+            set_modified(&document, false, &wind, &status_dot, &tab_bar, &theme);
+            if let Some(dot) = status_dot.borrow_mut().as_mut() {
+                update_status_dot(Some(&mut *dot), StatusDotState::Hidden, theme.font_size, &theme);
+                show_status_dot_timed(
+                    Some(dot),
+                    StatusDotState::Positive,
+                    theme.font_size,
+                    &theme,
+                    1.0,
+                    status_dot.clone(),
+                );
+            }
+            true
+        }
+    });
+
+    let save_file: Rc<dyn Fn() -> bool> = Rc::new({
+        let document = document.clone();
+        let save_to = save_to.clone();
+        move || {
+            let path = document.borrow().path.clone();
+            save_to(&path)
+        }
+    });
+
+    let confirm_unsaved: Rc<dyn Fn() -> bool> = Rc::new({
+        let document = document.clone();
+        let save_file = save_file.clone();
+        move || {
+            if !document.borrow().modified {
+                return true;
+            }
+            let file_name = document.borrow().file_name();
+            match unsaved_changes_prompt(&file_name) {
+                UnsavedChoice::Save => save_file(),
+                UnsavedChoice::Discard => {
+                    swap::remove_swap(&document.borrow().path);
+                    true
+                }
+                UnsavedChoice::Cancel => false,
+            }
+        }
+    });
+
+    let reload_document: Rc<dyn Fn()> = Rc::new({
         let editor = editor.clone();
-        let blink_state = blink_state.clone();
-        let blink_paused = blink_paused.clone();
-        let interval = cfg.borrow().theme.cursor_flash_interval;
-        let blink_timeout_handle = blink_timeout_handle.clone();
-        let blink_callback = blink_callback.clone();
-        let wind_ptr = Rc::as_ptr(&wind) as *mut RefCell<Window>;
-        let header_ptr = Rc::as_ptr(&header) as *mut RefCell<Frame>;
-        let editor_clone = editor_clone.clone();
-        let status_label_ptr = &mut status_label as *mut Frame;
-        let status_dot = status_dot_clone;
-        let last_cursor_pos = last_cursor_pos_clone;
+        let document = document.clone();
+        let status_dot = status_dot.clone();
+        let tab_bar = tab_bar.clone();
+        let cfg = cfg.clone();
+        let wind = wind.clone();
         let top_line = top_line.clone();
         let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let file_info_label = file_info_label.clone();
+        let line_index = line_index.clone();
+        let undo = undo.clone();
+        move || {
+            let Some(mut buf) = editor.borrow().buffer() else { return };
+            let pos = editor.borrow().insert_position();
+            let (line, col) = {
+                let index = line_index.borrow();
+                let line = index.line_of(pos);
+                (line as usize, (pos - index.line_start(line)) as usize)
+            };
+            let top = *top_line.borrow();
+
+            // The buffer keeps the shared line index up to date as it fills. The old
+            // history doesn't fit the new text, so it is replaced rather than added to.
+            let path = document.borrow().path.clone();
+            undo.borrow_mut().applying = true;
+            let loaded = load_document(&mut buf, &path, cfg.borrow().editor.large_file_threshold);
+            undo.borrow_mut().applying = false;
+            match loaded {
+                Ok((mut loaded, _)) => {
+                    // Left as it was so `set_modified` below updates the markers.
+                    loaded.modified = document.borrow().modified;
+                    *document.borrow_mut() = loaded;
+                }
+                Err(err) => {
+                    eprintln!("Failed to reload {}: {}", path, err);
+                    return;
+                }
+            }
+            *undo.borrow_mut() = restore_undo(&document.borrow(), &cfg.borrow(), &buf);
+            {
+                let mut ed = editor.borrow_mut();
+                ed.set_insert_position(position_for_line_col(&buf, &line_index.borrow(), line, col) as i32);
 
-        move |_, ev| {
-            match ev {
-                fltk::enums::Event::KeyDown | fltk::enums::Event::KeyUp => {
-                    pause_blink(
-                        blink_paused.clone(),
-                        editor.clone(),
-                        blink_state.clone(),
-                        interval,
-                        blink_timeout_handle.clone(),
-                        blink_callback.clone(),
-                    );
+                let mut sb = scrollbar.borrow_mut();
+                let top = top.min(get_max_top(&ed, &mut sb, &line_index.borrow()));
+                viewport::scroll_to(&mut ed, top, hscrollbar.borrow().value());
+                *top_line.borrow_mut() = top;
+                sb.set_value(top);
+            }
+            swap::remove_swap(&document.borrow().path);
+            document.borrow_mut().swap_pending = false;
+            let theme = cfg.borrow().theme.clone();
+            set_modified(&document, false, &wind, &status_dot, &tab_bar, &theme);
+            // The buffer matches the file again, which settles any earlier error unless
+            // the file itself didn't read exactly.
+            let state = if document.borrow().lossy { StatusDotState::Negative } else { StatusDotState::Hidden };
+            update_status_dot(status_dot.borrow_mut().as_mut(), state, theme.font_size, &theme);
+            file_info_label.borrow_mut().set_label(&document.borrow().file_info());
+            println!("Reloaded {}", document.borrow().path);
+        }
+    });
 
-                    {
-                        let ed = editor.borrow_mut();
-                        let mut sb = scrollbar.borrow_mut();
-                        let max_top = get_max_top(&ed, &mut sb);
-
-                        if let Some(buf) = ed.buffer() {
-                            let total_lines = buf.count_lines(0, buf.length());
-                            let insert_pos = ed.insert_position();
-                            let line_of_cursor = buf.line_start(insert_pos);
-                            let line_idx = buf.count_lines(0, line_of_cursor);
-                            if line_idx >= total_lines - 1 {
-                                *top_line.borrow_mut() = max_top;
-                            }
+    let apply_undo: Rc<dyn Fn(Vec<Step>)> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let status_dot = status_dot.clone();
+        let tab_bar = tab_bar.clone();
+        let cfg = cfg.clone();
+        let wind = wind.clone();
+        let undo = undo.clone();
+        move |steps: Vec<Step>| {
+            let Some(mut buf) = editor.borrow().buffer() else { return };
+            let mut cursor = None;
+            undo.borrow_mut().applying = true;
+            for step in steps {
+                match step {
+                    Step::Undo(edits) => {
+                        for edit in edits.iter().rev() {
+                            buf.replace(edit.pos, edit.pos + edit.inserted.len() as i32, &edit.deleted);
+                            cursor = Some(edit.pos + edit.deleted.len() as i32);
                         }
-
-                        let top = *top_line.borrow();
-                        sb.set_value(top);
                     }
-                    unsafe {
-                        update_status_label(&editor.borrow(), &mut *status_label_ptr, &last_cursor_pos);
+                    Step::Redo(edits) => {
+                        for edit in &edits {
+                            buf.replace(edit.pos, edit.pos + edit.deleted.len() as i32, &edit.inserted);
+                            cursor = Some(edit.pos + edit.inserted.len() as i32);
+                        }
                     }
                 }
-                fltk::enums::Event::Push | fltk::enums::Event::Drag | fltk::enums::Event::Released => {
-                    let editor = editor.clone();
-                    let status_label_ptr = status_label_ptr.clone();
-                    let last_cursor_pos = last_cursor_pos.clone();
-                    unsafe {
-                        update_status_label(&editor.borrow(), &mut *status_label_ptr, &last_cursor_pos);
-                    }
+            }
+            undo.borrow_mut().applying = false;
+            if let Some(cursor) = cursor {
+                editor.borrow_mut().set_insert_position(cursor);
+            }
+            if undo.borrow().is_saved() {
+                set_modified(&document, false, &wind, &status_dot, &tab_bar, &cfg.borrow().theme);
+            }
+        }
+    });
+
+    let refresh_find: Rc<dyn Fn()> = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let scrollbar = scrollbar.clone();
+        let line_index = line_index.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let style_buf = style_buf.clone();
+        move || {
+            let (query, options) = {
+                let bar = find_bar.borrow();
+                if !bar.group.visible() {
+                    return;
                 }
-                fltk::enums::Event::MouseWheel => {
+                (bar.find.value(), bar.options())
+            };
+            let Some(buf) = editor.borrow().buffer() else { return };
+            let selection = selection_or_cursor(&editor.borrow());
+            let mut state = find_state.borrow_mut();
+            let label = if query.is_empty() {
+                state.matches.clear();
+                state.cut_short = false;
+                String::new()
+            } else {
+                match Search::new(&query, options) {
+                    Ok(search) => {
+                        let text = buf.text();
+                        let range = state.scope.filter(|_| options.in_selection).unwrap_or((0, text.len()));
+                        (state.matches, state.cut_short) = search.find_all(&text, range);
+                        state.count_label(selection)
+                    }
+                    Err(err) => {
+                        state.matches.clear();
+                        state.cut_short = false;
+                        err
+                    }
+                }
+            };
+            paint_matches(&mut style_buf.clone(), &state.matches);
+            mark_matches(&mut scrollbar.borrow_mut(), &line_index.borrow(), &state.matches, &cfg.borrow().theme);
+            editor.borrow_mut().redraw();
+            find_bar.borrow_mut().set_count(&label);
+        }
+    });
+
+    let find_step: Rc<dyn Fn(bool)> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let line_index = line_index.clone();
+        let top_line = top_line.clone();
+        let refresh_find = refresh_find.clone();
+        move |forward: bool| {
+            // Large files aren't searched while typing, so look for matches on demand.
+            if document.borrow().large {
+                refresh_find();
+            }
+            let selection = selection_or_cursor(&editor.borrow());
+            let target = find_state.borrow().step(selection, forward);
+            let Some((start, end)) = target else { return };
+            {
+                let mut ed = editor.borrow_mut();
+                if let Some(mut buf) = ed.buffer() {
+                    buf.select(start as i32, end as i32);
+                }
+                ed.set_insert_position(end as i32);
+                let line = line_index.borrow().line_of(start as i32);
+                reveal_line(
+                    &mut ed,
+                    &mut scrollbar.borrow_mut(),
+                    &hscrollbar.borrow(),
+                    &line_index.borrow(),
+                    &mut top_line.borrow_mut(),
+                    line,
+                );
+            }
+            let label = find_state.borrow().count_label((start, end));
+            find_bar.borrow_mut().set_count(&label);
+        }
+    });
+
+    let replace_next: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let refresh_find = refresh_find.clone();
+        let find_step = find_step.clone();
+        move || {
+            let selection = selection_or_cursor(&editor.borrow());
+            if find_state.borrow().matches.contains(&selection) {
+                let (query, template, options) = {
+                    let bar = find_bar.borrow();
+                    (bar.find.value(), bar.replace.value(), bar.options())
+                };
+                let Some(mut buf) = editor.borrow().buffer() else { return };
+                if let Ok(search) = Search::new(&query, options) {
+                    let replacement = search.replacement(&buf.text(), selection.0, &template);
+                    buf.unselect();
+                    buf.replace(selection.0 as i32, selection.1 as i32, &replacement);
+                    let end = selection.0 + replacement.len();
+                    editor.borrow_mut().set_insert_position(end as i32);
+                    if let Some(scope) = find_state.borrow_mut().scope.as_mut() {
+                        scope.1 = (scope.1 + end).saturating_sub(selection.1);
+                    }
+                }
+                refresh_find();
+            }
+            find_step(true);
+        }
+    });
+
+    let replace_all: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let refresh_find = refresh_find.clone();
+        move || {
+            refresh_find();
+            let (query, template, options) = {
+                let bar = find_bar.borrow();
+                (bar.find.value(), bar.replace.value(), bar.options())
+            };
+            let Ok(search) = Search::new(&query, options) else { return };
+            let matches = find_state.borrow().matches.clone();
+            let (Some(&(first, _)), Some(&(_, last))) = (matches.first(), matches.last()) else { return };
+            let Some(mut buf) = editor.borrow().buffer() else { return };
+            let replaced = search.replace_matches(&buf.text(), &matches, &template);
+            // One replace over the whole span keeps "replace all" a single undo step.
+            buf.unselect();
+            buf.replace(first as i32, last as i32, &replaced);
+            editor.borrow_mut().set_insert_position((first + replaced.len()) as i32);
+            if let Some(scope) = find_state.borrow_mut().scope.as_mut() {
+                scope.1 = (scope.1 + replaced.len()).saturating_sub(last - first);
+            }
+            refresh_find();
+            find_bar.borrow_mut().set_count(&format!("Replaced {}", matches.len()));
+        }
+    });
+
+    let show_find: Rc<dyn Fn(bool)> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let find_bar = find_bar.clone();
+        let layout_panes = layout_panes.clone();
+        let refresh_find = refresh_find.clone();
+        move |focus_replace: bool| {
+            let docked = find_bar.borrow().group.visible();
+            if !docked {
+                find_bar.borrow_mut().group.show();
+                layout_panes();
+            }
+            {
+                let mut bar = find_bar.borrow_mut();
+                if let Some(buf) = editor.borrow().buffer() {
+                    let selected = buf.selection_text();
+                    if !selected.is_empty() && !selected.contains('\n') {
+                        bar.find.set_value(&selected);
+                    }
+                }
+                let input = if focus_replace { &mut bar.replace } else { &mut bar.find };
+                let _ = input.take_focus();
+                let _ = input.set_position(0);
+                let _ = input.set_mark(input.value().len() as i32);
+            }
+            if !document.borrow().large {
+                refresh_find();
+            }
+        }
+    });
+
+    let hide_find: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let scrollbar = scrollbar.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let style_buf = style_buf.clone();
+        let layout_panes = layout_panes.clone();
+        move || {
+            if find_bar.borrow().group.visible() {
+                find_bar.borrow_mut().group.hide();
+                layout_panes();
+            }
+            find_state.borrow_mut().matches.clear();
+            paint_matches(&mut style_buf.clone(), &[]);
+            scrollbar.borrow_mut().clear_markers(MarkerKind::SearchMatch);
+            let mut ed = editor.borrow_mut();
+            ed.redraw();
+            let _ = ed.take_focus();
+        }
+    });
+
+    let on_find_option: Rc<dyn Fn(FindOption)> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let find_bar = find_bar.clone();
+        let find_state = find_state.clone();
+        let refresh_find = refresh_find.clone();
+        move |option: FindOption| {
+            if option == FindOption::InSelection {
+                let scope = if find_bar.borrow().options().in_selection {
+                    editor
+                        .borrow()
+                        .buffer()
+                        .and_then(|buf| buf.selection_position())
+                        .map(|(start, end)| (start as usize, end as usize))
+                } else {
+                    None
+                };
+                find_state.borrow_mut().scope = scope;
+            }
+            if !document.borrow().large {
+                refresh_find();
+            }
+        }
+    });
+    find_bar.borrow_mut().set_on_option({
+        let on_find_option = on_find_option.clone();
+        move |option| on_find_option(option)
+    });
+
+    let find_action: Rc<dyn Fn(&Binding) -> bool> = Rc::new({
+        let find_bar = find_bar.clone();
+        let show_find = show_find.clone();
+        let find_step = find_step.clone();
+        let replace_next = replace_next.clone();
+        let replace_all = replace_all.clone();
+        let on_find_option = on_find_option.clone();
+        move |binding: &Binding| {
+            let visible = find_bar.borrow().group.visible();
+            let toggle = |option| {
+                find_bar.borrow_mut().toggle(option);
+                on_find_option(option);
+            };
+            match binding {
+                Binding::Find => show_find(false),
+                Binding::Replace => show_find(true),
+                Binding::FindNext | Binding::FindPrevious if !visible => show_find(false),
+                Binding::FindNext => find_step(true),
+                Binding::FindPrevious => find_step(false),
+                Binding::ReplaceNext | Binding::ReplaceAll if !visible => show_find(true),
+                Binding::ReplaceNext => replace_next(),
+                Binding::ReplaceAll => replace_all(),
+                Binding::ToggleRegex => toggle(FindOption::Regex),
+                Binding::ToggleCase => toggle(FindOption::CaseSensitive),
+                Binding::ToggleWholeWord => toggle(FindOption::WholeWord),
+                Binding::ToggleInSelection => toggle(FindOption::InSelection),
+                _ => return false,
+            }
+            true
+        }
+    });
+
+    {
+        let mut bar = find_bar.borrow_mut();
+        bar.find.set_callback({
+            let document = document.clone();
+            let refresh_find = refresh_find.clone();
+            move |_| {
+                if !document.borrow().large {
+                    refresh_find();
+                }
+            }
+        });
+        let find_keys = |on_enter: Rc<dyn Fn(bool)>| {
+            let cfg = cfg.clone();
+            let hide_find = hide_find.clone();
+            let find_action = find_action.clone();
+            move |_: &mut fltk::input::Input, ev: fltk::enums::Event| {
+                if ev != fltk::enums::Event::KeyDown {
+                    return false;
+                }
+                match app::event_key() {
+                    fltk::enums::Key::Enter | fltk::enums::Key::KPEnter => {
+                        on_enter(!app::event_state().contains(fltk::enums::Shortcut::Shift));
+                        return true;
+                    }
+                    fltk::enums::Key::Escape => {
+                        hide_find();
+                        return true;
+                    }
+                    _ => {}
+                }
+                let bindings: Vec<Binding> = cfg
+                    .borrow()
+                    .bindings
+                    .iter()
+                    .filter(|(_, sequences)| {
+                        sequences.iter().any(|sequence| sequence.single().is_some_and(|combo| combo.matches_event()))
+                    })
+                    .map(|(binding, _)| binding.clone())
+                    .collect();
+                bindings.iter().any(|binding| find_action(binding))
+            }
+        };
+        bar.find.handle(find_keys(find_step.clone()));
+        bar.replace.handle(find_keys(Rc::new({
+            let replace_next = replace_next.clone();
+            move |_| replace_next()
+        })));
+        bar.prev.set_callback({
+            let find_step = find_step.clone();
+            move |_| find_step(false)
+        });
+        bar.next.set_callback({
+            let find_step = find_step.clone();
+            move |_| find_step(true)
+        });
+        bar.replace_one.set_callback({
+            let replace_next = replace_next.clone();
+            move |_| replace_next()
+        });
+        bar.replace_all.set_callback({
+            let replace_all = replace_all.clone();
+            move |_| replace_all()
+        });
+        bar.close.set_callback({
+            let hide_find = hide_find.clone();
+            move |_| hide_find()
+        });
+    }
+
+    let handle_external_change: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let status_dot = status_dot.clone();
+        let tab_bar = tab_bar.clone();
+        let cfg = cfg.clone();
+        let wind = wind.clone();
+        let reload_document = reload_document.clone();
+        move || {
+            let path = document.borrow().path.clone();
+            if !std::path::Path::new(&path).exists() {
+                println!("{} was removed from disk", path);
+                document.borrow_mut().record_disk_stamp();
+                set_modified(&document, true, &wind, &status_dot, &tab_bar, &cfg.borrow().theme);
+                return;
+            }
+            if !document.borrow().modified {
+                reload_document();
+                return;
+            }
+            let file_name = document.borrow().file_name();
+            loop {
+                match external_change_prompt(&file_name) {
+                    ExternalChangeChoice::Reload => {
+                        reload_document();
+                        break;
+                    }
+                    ExternalChangeChoice::KeepMine => {
+                        document.borrow_mut().record_disk_stamp();
+                        break;
+                    }
+                    ExternalChangeChoice::ShowDiff if document.borrow().large => {
+                        fltk::dialog::alert_default("Diffs are not available in large-file mode.");
+                    }
+                    ExternalChangeChoice::ShowDiff => {
+                        let mut on_disk = Document::new(&path);
+                        let disk_text = on_disk.read().unwrap_or_default();
+                        let buffer_text = editor.borrow().buffer().map(|b| b.text()).unwrap_or_default();
+                        show_diff(
+                            &format!("{}: disk -> editor", file_name),
+                            &diff::unified(&disk_text, &buffer_text, 3),
+                            &cfg.borrow().theme,
+                        );
+                    }
+                }
+            }
+        }
+    });
+
+    let offer_recovery: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        move || {
+            let path = document.borrow().path.clone();
+            let Some(swapped) = swap::read_swap(&path) else { return };
+            let Some(mut buf) = editor.borrow().buffer() else { return };
+            if swapped == buf.text() {
+                swap::remove_swap(&path);
+                return;
+            }
+            let file_name = document.borrow().file_name();
+            match recover_swap_prompt(&file_name) {
+                RecoverChoice::Recover => {
+                    buf.set_text(&swapped);
+                    println!("Recovered {} from {}", path, swap::swap_path_for(&path).display());
+                }
+                RecoverChoice::Delete => swap::remove_swap(&path),
+                RecoverChoice::Ignore => {}
+            }
+        }
+    });
+    offer_recovery();
+
+    let autosave_check_interval = 1.0;
+    let last_autosave = Rc::new(RefCell::new(std::time::Instant::now()));
+    app::add_timeout3(autosave_check_interval, {
+        let editor = editor.clone();
+        let document = document.clone();
+        let cfg = cfg.clone();
+        move |handle| {
+            let interval = cfg.borrow().editor.autosave_interval;
+            let due = last_autosave.borrow().elapsed().as_secs_f64() >= interval;
+            let large = document.borrow().large;
+            if interval > 0.0 && due && !large && document.borrow().swap_pending {
+                let path = document.borrow().path.clone();
+                let text = editor.borrow().buffer().map(|b| b.text()).unwrap_or_default();
+                match swap::write_swap(&path, &text) {
+                    Ok(()) => document.borrow_mut().swap_pending = false,
+                    Err(err) => eprintln!("Failed to write swap file for {}: {}", path, err),
+                }
+                *last_autosave.borrow_mut() = std::time::Instant::now();
+            }
+            app::repeat_timeout3(autosave_check_interval, handle);
+        }
+    });
+
+    let document_check_interval = 0.25;
+    app::add_timeout3(document_check_interval, {
+        let document = document.clone();
+        let handle_external_change = handle_external_change.clone();
+        move |handle| {
+            let mut changed = false;
+            while doc_rx.try_recv().is_ok() {
+                changed = true;
+            }
+            if changed && document.borrow().disk_changed() {
+                handle_external_change();
+            }
+            app::repeat_timeout3(document_check_interval, handle);
+        }
+    });
+
+    let park_active: Rc<dyn Fn() -> Parked> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        let line_index = line_index.clone();
+        let undo = undo.clone();
+        let highlighter = highlighter.clone();
+        let style_buf = style_buf.clone();
+        let top_line = top_line.clone();
+        move || {
+            let ed = editor.borrow();
+            Parked {
+                document: std::mem::replace(&mut *document.borrow_mut(), Document::new("")),
+                buffer: ed.buffer().unwrap_or_default(),
+                line_index: std::mem::replace(&mut *line_index.borrow_mut(), LineIndex::build("")),
+                undo: std::mem::take(&mut *undo.borrow_mut()),
+                highlighter: std::mem::take(&mut *highlighter.borrow_mut()),
+                styles: style_buf.text(),
+                cursor: ed.insert_position(),
+                top_line: *top_line.borrow(),
+            }
+        }
+    });
+
+    let unpark: Rc<dyn Fn(Parked)> = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let document = document.clone();
+        let wind = wind.clone();
+        let header = header.clone();
+        let status_dot = status_dot.clone();
+        let file_info_label = file_info_label.clone();
+        let tab_bar = tab_bar.clone();
+        let tabs = tabs.clone();
+        let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let top_line = top_line.clone();
+        let line_index = line_index.clone();
+        let undo = undo.clone();
+        let style_buf = style_buf.clone();
+        let highlighter = highlighter.clone();
+        let schedule_highlight = schedule_highlight.clone();
+        let find_state = find_state.clone();
+        let refresh_find = refresh_find.clone();
+        let watch_document = watch_document.clone();
+        let handle_external_change = handle_external_change.clone();
+        let apply_widget_theme = apply_widget_theme.clone();
+        let last_cursor_pos = last_cursor_pos.clone();
+        let status_label = status_label.clone();
+        move |parked: Parked| {
+            let Parked { document: shown, buffer, line_index: index, undo: tree, highlighter: mut parked_highlighter, styles, cursor, top_line: top } = parked;
+            if styles.len() == buffer.length() as usize {
+                style_buf.clone().set_text(&styles);
+            } else {
+                fit_styles(&style_buf, buffer.length(), shown.large);
+                parked_highlighter.reset();
+            }
+            *document.borrow_mut() = shown;
+            *line_index.borrow_mut() = index;
+            *undo.borrow_mut() = tree;
+            *highlighter.borrow_mut() = parked_highlighter;
+            {
+                let mut ed = editor.borrow_mut();
+                // A pane that already shows the buffer keeps its own cursor and scroll position.
+                let (top, left) = if ed.buffer().as_ref() == Some(&buffer) {
+                    (*top_line.borrow(), hscrollbar.borrow().value())
+                } else {
+                    ed.set_buffer(buffer.clone());
+                    ed.set_insert_position(cursor);
+                    (top, 0)
+                };
+                let mut sb = scrollbar.borrow_mut();
+                let top = top.min(get_max_top(&ed, &mut sb, &line_index.borrow()));
+                viewport::scroll_to(&mut ed, top, left);
+                *top_line.borrow_mut() = top;
+                sb.set_value(top);
+            }
+            swap::set_crash_target(&buffer, &document.borrow().path);
+
+            let theme = cfg.borrow().theme.clone();
+            let state = {
+                let doc = document.borrow();
+                wind.borrow_mut().set_label(&doc.window_title());
+                header.borrow_mut().set_label(&doc.abs_path());
+                file_info_label.borrow_mut().set_label(&doc.file_info());
+                if doc.lossy || !std::path::Path::new(&doc.path).exists() {
+                    StatusDotState::Negative
+                } else if doc.modified {
+                    StatusDotState::Modified
+                } else {
+                    StatusDotState::Hidden
+                }
+            };
+            update_status_dot(status_dot.borrow_mut().as_mut(), state, theme.font_size, &theme);
+            let labels = tabs.borrow().labels(&document.borrow());
+            tab_bar.borrow_mut().set_tabs(labels, tabs.borrow().active());
+
+            find_state.borrow_mut().scope = None;
+            if !document.borrow().large {
+                refresh_find();
+            }
+            *last_cursor_pos.borrow_mut() = -1;
+            update_status_label(
+                &editor.borrow(),
+                &mut status_label.borrow_mut(),
+                &last_cursor_pos,
+                &line_index.borrow(),
+            );
+            apply_widget_theme();
+            editor.borrow_mut().redraw();
+            schedule_highlight();
+            watch_document();
+            let changed = {
+                let doc = document.borrow();
+                std::path::Path::new(&doc.path).exists() && doc.disk_changed()
+            };
+            if changed {
+                handle_external_change();
+            }
+        }
+    });
+
+    // The autosave timer only looks at the shown buffer, so write its swap file before it's parked.
+    let stash_swap: Rc<dyn Fn()> = Rc::new({
+        let editor = editor.clone();
+        let document = document.clone();
+        move || {
+            let pending = {
+                let doc = document.borrow();
+                doc.swap_pending && !doc.large
+            };
+            if pending {
+                let path = document.borrow().path.clone();
+                let text = editor.borrow().buffer().map(|b| b.text()).unwrap_or_default();
+                match swap::write_swap(&path, &text) {
+                    Ok(()) => document.borrow_mut().swap_pending = false,
+                    Err(err) => eprintln!("Failed to write swap file for {}: {}", path, err),
+                }
+            }
+        }
+    });
+
+    let switch_tab: Rc<dyn Fn(usize)> = Rc::new({
+        let tabs = tabs.clone();
+        let stash_swap = stash_swap.clone();
+        let park_active = park_active.clone();
+        let unpark = unpark.clone();
+        move |index: usize| {
+            if index == tabs.borrow().active() || index >= tabs.borrow().count() {
+                return;
+            }
+            stash_swap();
+            let current = park_active();
+            let shown = tabs.borrow_mut().switch(index, current);
+            unpark(shown);
+        }
+    });
+
+    let close_tab: Rc<dyn Fn(usize)> = Rc::new({
+        let tabs = tabs.clone();
+        let panes = panes.clone();
+        let switch_tab = switch_tab.clone();
+        let confirm_unsaved = confirm_unsaved.clone();
+        let park_active = park_active.clone();
+        let unpark = unpark.clone();
+        move |index: usize| {
+            // The tab has to be shown to be saved, so the prompt asks about it there.
+            let previous = tabs.borrow().active();
+            switch_tab(index);
+            if !confirm_unsaved() {
+                switch_tab(previous);
+                return;
+            }
+            let next = tabs.borrow_mut().close_active();
+            match next {
+                Some(next) => {
+                    let closed = park_active();
+                    for (_, pane) in panes.borrow_mut().others_mut() {
+                        if pane.editor.buffer().as_ref() == Some(&closed.buffer) {
+                            pane.editor.set_buffer(next.buffer.clone());
+                        }
+                    }
+                    unpark(next);
+                    // Closing another tab leaves the user where they were.
+                    if previous != index {
+                        switch_tab(if previous > index { previous - 1 } else { previous });
+                    }
+                }
+                None => {
+                    println!("Quitting app");
+                    app::quit();
+                }
+            }
+        }
+    });
+
+    let confirm_all_unsaved: Rc<dyn Fn() -> bool> = Rc::new({
+        let document = document.clone();
+        let tabs = tabs.clone();
+        let switch_tab = switch_tab.clone();
+        let confirm_unsaved = confirm_unsaved.clone();
+        move || {
+            let count = tabs.borrow().count();
+            for index in 0..count {
+                let modified = tabs.borrow().document(index, &document.borrow()).modified;
+                if modified {
+                    switch_tab(index);
+                    if !confirm_unsaved() {
+                        return false;
+                    }
+                }
+            }
+            true
+        }
+    });
+
+    let focus_pane: Rc<dyn Fn(usize)> = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let find_state = find_state.clone();
+        let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let gutter = gutter.clone();
+        let top_line = top_line.clone();
+        let panes = panes.clone();
+        let tabs = tabs.clone();
+        let line_index = line_index.clone();
+        let stash_swap = stash_swap.clone();
+        let park_active = park_active.clone();
+        let unpark = unpark.clone();
+        let apply_widget_theme = apply_widget_theme.clone();
+        let last_cursor_pos = last_cursor_pos.clone();
+        let status_label = status_label.clone();
+        move |index: usize| {
+            if index == panes.borrow().focused() || index >= panes.borrow().count() {
+                return;
+            }
+            // The focused pane always shows the active tab, so focusing a pane that shows
+            // another buffer switches to that buffer's tab.
+            let shows = panes.borrow_mut().pane_mut(index).and_then(|pane| pane.editor.buffer());
+            let tab = shows.and_then(|buffer| tabs.borrow().find_buffer(&buffer));
+            let current = tab.map(|_| {
+                stash_swap();
+                park_active()
+            });
+            editor.borrow_mut().show_cursor(false);
+            {
+                let mut ed = editor.borrow_mut();
+                let mut sb = scrollbar.borrow_mut();
+                let mut hsb = hscrollbar.borrow_mut();
+                // Search matches are only marked on the focused pane.
+                sb.clear_markers(MarkerKind::SearchMatch);
+                panes.borrow_mut().focus(index, &mut ed, &mut sb, &mut hsb, &mut gutter.borrow_mut());
+                ed.show_cursor(true);
+                *top_line.borrow_mut() = sb.value();
+                if tab.is_none() {
+                    let matches = &find_state.borrow().matches;
+                    mark_matches(&mut sb, &line_index.borrow(), matches, &cfg.borrow().theme);
+                }
+            }
+            let _ = editor.borrow().clone().take_focus();
+            match (tab, current) {
+                (Some(tab), Some(current)) => {
+                    let shown = tabs.borrow_mut().switch(tab, current);
+                    unpark(shown);
+                }
+                _ => {
+                    apply_widget_theme();
+                    *last_cursor_pos.borrow_mut() = -1;
+                    update_status_label(
+                        &editor.borrow(),
+                        &mut status_label.borrow_mut(),
+                        &last_cursor_pos,
+                        &line_index.borrow(),
+                    );
+                }
+            }
+        }
+    });
+
+    let close_pane: Rc<dyn Fn()> = Rc::new({
+        let panes = panes.clone();
+        let focus_pane = focus_pane.clone();
+        let layout_panes = layout_panes.clone();
+        move || {
+            let (closing, next) = {
+                let panes = panes.borrow();
+                if panes.count() < 2 {
+                    return;
+                }
+                (panes.focused(), panes.neighbour(panes.focused(), 1))
+            };
+            focus_pane(next);
+            let removed = panes.borrow_mut().remove(closing);
+            if let Some(mut pane) = removed {
+                pane.editor.hide();
+                pane.scrollbar.hide();
+                pane.hscrollbar.hide();
+                pane.gutter.widget.hide();
+                // Deferred, since this may run inside the closing editor's own event handler.
+                app::delete_widget(pane.editor);
+                app::delete_widget(pane.scrollbar.widget);
+                app::delete_widget(pane.hscrollbar.widget);
+                app::delete_widget(pane.gutter.widget);
+            }
+            layout_panes();
+        }
+    });
+
+    *gutter_select.borrow_mut() = Some(Rc::new({
+        let editor = editor.clone();
+        let panes = panes.clone();
+        let line_index = line_index.clone();
+        let focus_pane = focus_pane.clone();
+        let last_cursor_pos = last_cursor_pos.clone();
+        let status_label = status_label.clone();
+        move |clicked: &TextEditor, from: i32, to: i32| {
+            if *editor.borrow() != *clicked {
+                let index = panes.borrow().index_of(clicked);
+                if let Some(index) = index {
+                    focus_pane(index);
+                }
+            }
+            let mut ed = editor.borrow_mut();
+            let Some(mut buf) = ed.buffer() else { return };
+            let (first, last) = (from.min(to), from.max(to));
+            let (start, end) = {
+                let index = line_index.borrow();
+                let end = if last + 1 < index.line_count() { index.line_start(last + 1) } else { buf.length() };
+                (index.line_start(first), end)
+            };
+            buf.select(start, end);
+            ed.set_insert_position(if to < from { start } else { end });
+            let _ = ed.take_focus();
+            ed.redraw();
+            update_status_label(&ed, &mut status_label.borrow_mut(), &last_cursor_pos, &line_index.borrow());
+        }
+    }));
+
+    // The event handler is shared by every pane's editor and defined further down,
+    // after the actions it dispatches to, so new panes look it up here.
+    let pane_events: Rc<RefCell<Option<EditorEvents>>> = Rc::new(RefCell::new(None));
+    let split_pane: Rc<dyn Fn(Split)> = Rc::new({
+        let cfg = cfg.clone();
+        let wind = wind.clone();
+        let editor = editor.clone();
+        let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let top_line = top_line.clone();
+        let line_index = line_index.clone();
+        let gutter_select = gutter_select.clone();
+        let panes = panes.clone();
+        let style_buf = style_buf.clone();
+        let pane_events = pane_events.clone();
+        let focus_pane = focus_pane.clone();
+        let layout_panes = layout_panes.clone();
+        move |split: Split| {
+            let (buffer, cursor, top, left) = {
+                let ed = editor.borrow();
+                (ed.buffer(), ed.insert_position(), *top_line.borrow(), hscrollbar.borrow().value())
+            };
+            let Some(buffer) = buffer else { return };
+            let theme = cfg.borrow().theme.clone();
+            let mut pane = pane_editor(0, 0, 100, 100);
+            style_editor(&mut pane, &theme);
+            pane.set_buffer(buffer);
+            apply_highlight_table(&mut pane, &style_buf, &theme);
+            pane.set_insert_position(cursor);
+            pane.handle({
+                let pane_events = pane_events.clone();
+                move |w, ev| {
+                    let events = pane_events.borrow().clone();
+                    events.is_some_and(|events| events(w, ev))
+                }
+            });
+            let mut pane_gutter = pane_gutter(&pane, &gutter_select);
+            pane_gutter.apply_theme(&theme, cfg.borrow().editor.line_numbers);
+            let width = scrollbar.borrow().widget.w();
+            let (mut pane_bar, mut pane_hbar) = pane_scrollbars(&pane, &pane_gutter, &editor, &top_line, width);
+            style_scrollbar(&mut pane_bar, &theme);
+            style_scrollbar(&mut pane_hbar, &theme);
+            follow_view(&mut pane, &pane_gutter, &pane_bar, &pane_hbar, &editor, &line_index, &top_line);
+            {
+                let mut wind = wind.borrow_mut();
+                wind.add(&pane);
+                wind.add(&pane_bar.widget);
+                wind.add(&pane_hbar.widget);
+                wind.add(&pane_gutter.widget);
+            }
+            let pane = Pane { editor: pane.clone(), scrollbar: pane_bar, hscrollbar: pane_hbar, gutter: pane_gutter };
+            let index = panes.borrow_mut().split(split, pane);
+            layout_panes();
+            if let Some(added) = panes.borrow_mut().pane_mut(index) {
+                viewport::scroll_to(&mut added.editor, top, left);
+                added.scrollbar.set_value(top);
+                added.hscrollbar.set_value(left);
+                added.gutter.set_top(top);
+            }
+            focus_pane(index);
+        }
+    });
+
+    {
+        let mut bar = tab_bar.borrow_mut();
+        bar.set_on_select({
+            let switch_tab = switch_tab.clone();
+            move |index| switch_tab(index)
+        });
+        bar.set_on_close({
+            let close_tab = close_tab.clone();
+            move |index| close_tab(index)
+        });
+    }
+
+    wind.borrow_mut().set_callback({
+        let confirm_all_unsaved = confirm_all_unsaved.clone();
+        move |_w| {
+            if confirm_all_unsaved() {
+                app::quit();
+            }
+        }
+    });
+
+    let open_document: Rc<dyn Fn(&str) -> bool> = Rc::new({
+        let cfg = cfg.clone();
+        let document = document.clone();
+        let wind = wind.clone();
+        let status_dot = status_dot.clone();
+        let tab_bar = tab_bar.clone();
+        let tabs = tabs.clone();
+        let line_index = line_index.clone();
+        let undo = undo.clone();
+        let style_buf = style_buf.clone();
+        let registry = registry.clone();
+        let highlighter = highlighter.clone();
+        let schedule_highlight = schedule_highlight.clone();
+        let switch_tab = switch_tab.clone();
+        let park_active = park_active.clone();
+        let unpark = unpark.clone();
+        let offer_recovery = offer_recovery.clone();
+        move |path: &str| {
+            let existing = tabs.borrow().find(path, &document.borrow());
+            if let Some(index) = existing {
+                switch_tab(index);
+                return true;
+            }
+            let mut opened = Document::new(path);
+            let mut buf = TextBuffer::default();
+            let mut opened_index = LineIndex::build("");
+            if std::path::Path::new(path).exists() {
+                (opened, opened_index) = match load_document(&mut buf, path, cfg.borrow().editor.large_file_threshold) {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        eprintln!("Failed to read {}: {}", path, err);
+                        fltk::dialog::alert_default(&format!(
+                            "Could not read {}:\n{}",
+                            path,
+                            file_io::describe_error(&err)
+                        ));
+                        return false;
+                    }
+                };
+            } else {
+                println!("File not found: {}", path);
+            }
+
+            track_modifications(
+                &mut buf,
+                document.clone(),
+                wind.clone(),
+                status_dot.clone(),
+                tab_bar.clone(),
+                cfg.clone(),
+            );
+            track_line_index(&mut buf, line_index.clone());
+            let tree = restore_undo(&opened, &cfg.borrow(), &buf);
+            track_undo(&mut buf, undo.clone(), document.clone());
+            track_styles(&mut buf, &style_buf, opened.large);
+            track_highlight(&mut buf, highlighter.clone(), line_index.clone(), schedule_highlight.clone());
+            let parked = Parked {
+                line_index: opened_index,
+                highlighter: detect_highlighter(&registry, &opened, &buf),
+                styles: if opened.large { String::new() } else { "A".repeat(buf.length() as usize) },
+                document: opened,
+                buffer: buf,
+                undo: tree,
+                cursor: 0,
+                top_line: 0,
+            };
+
+            let current = park_active();
+            tabs.borrow_mut().push(current);
+            unpark(parked);
+            offer_recovery();
+            true
+        }
+    });
+    for path in &more_paths {
+        open_document(path);
+    }
+    switch_tab(0);
+
+    let project_query = Rc::new(RefCell::new(ProjectQuery::default()));
+    let last_project_replace: Rc<RefCell<Vec<FileReplace>>> = Rc::new(RefCell::new(Vec::new()));
+    let run_project_search: Rc<dyn Fn()> = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let document = document.clone();
+        let open_document = open_document.clone();
+        let scrollbar = scrollbar.clone();
+        let hscrollbar = hscrollbar.clone();
+        let line_index = line_index.clone();
+        let top_line = top_line.clone();
+        move || {
+            let (open_path, root) = {
+                let doc = document.borrow();
+                (
+                    PathBuf::from(file_io::absolute_path(&doc.path)),
+                    project_search::search_root(&doc.path, &cfg.borrow().files.search_root),
+                )
+            };
+            let Some(mut buf) = editor.borrow().buffer() else { return };
+            let text = buf.text();
+            let theme = cfg.borrow().theme.clone();
+            let can_undo = !last_project_replace.borrow().is_empty();
+            let action = project_search_dialog(
+                &root,
+                Some((&open_path, &text)),
+                &mut project_query.borrow_mut(),
+                can_undo,
+                &theme,
+            );
+            let mut failed = Vec::new();
+            match action {
+                None => {}
+                Some(ProjectAction::Open(path, start, end)) => {
+                    if path != open_path && !open_document(&path.to_string_lossy()) {
+                        return;
+                    }
+                    let Some(mut buf) = editor.borrow().buffer() else { return };
+                    let end = (end as i32).min(buf.length());
+                    buf.select(start as i32, end);
                     let mut ed = editor.borrow_mut();
-                    let dy = app::event_dy();
-                    let mut top = *top_line.borrow();
-                    let max_top = get_max_top(&ed, &mut scrollbar.borrow_mut());
+                    ed.set_insert_position(end);
+                    let line = line_index.borrow().line_of(start as i32);
+                    reveal_line(
+                        &mut ed,
+                        &mut scrollbar.borrow_mut(),
+                        &hscrollbar.borrow(),
+                        &line_index.borrow(),
+                        &mut top_line.borrow_mut(),
+                        line,
+                    );
+                }
+                Some(ProjectAction::Replace(files)) => {
+                    let mut applied = Vec::new();
+                    for file in files {
+                        let result = if file.path == open_path {
+                            replace_changed(&mut buf, &file.original, &file.replaced);
+                            Ok(())
+                        } else {
+                            project_search::swap_contents(&file.path, &file.original, &file.replaced)
+                        };
+                        match result {
+                            Ok(()) => applied.push(file),
+                            Err(reason) => failed.push(format!("{}: {}", file.path.display(), reason)),
+                        }
+                    }
+                    *last_project_replace.borrow_mut() = applied;
+                }
+                Some(ProjectAction::UndoReplace) => {
+                    for file in last_project_replace.borrow_mut().drain(..) {
+                        let result = if file.path == open_path {
+                            if buf.text() == file.replaced {
+                                replace_changed(&mut buf, &file.replaced, &file.original);
+                                Ok(())
+                            } else {
+                                Err("edited since the replace".to_string())
+                            }
+                        } else {
+                            project_search::swap_contents(&file.path, &file.replaced, &file.original)
+                        };
+                        if let Err(reason) = result {
+                            failed.push(format!("{}: {}", file.path.display(), reason));
+                        }
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                eprintln!("Project replace skipped {} files", failed.len());
+                fltk::dialog::alert_default(&format!("These files were left unchanged:\n{}", failed.join("\n")));
+            }
+        }
+    });
 
-                    let scroll_multiplier = cfg.borrow().editor.scroll_multiplier.max(1);
+    let status_dot_clone = status_dot.clone();
+    let last_cursor_pos_clone = last_cursor_pos.clone();
 
-                    match dy {
-                        fltk::app::MouseWheel::Up => {
-                            top = (top - scroll_multiplier).max(0);
+    let editor_events: EditorEvents = Rc::new({
+        let cfg = cfg.clone();
+        let editor = editor.clone();
+        let hscrollbar = hscrollbar.clone();
+        let layout_panes = layout_panes.clone();
+        let gutter = gutter.clone();
+        let blink_state = blink_state.clone();
+        let blink_paused = blink_paused.clone();
+        let interval = cfg.borrow().theme.cursor_flash_interval;
+        let blink_timeout_handle = blink_timeout_handle.clone();
+        let blink_callback = blink_callback.clone();
+        let wind_ptr = Rc::as_ptr(&wind) as *mut RefCell<Window>;
+        let header_ptr = Rc::as_ptr(&header) as *mut RefCell<Frame>;
+        let editor_clone = editor_clone.clone();
+        let status_label = status_label.clone();
+        let status_dot = status_dot_clone;
+        let tab_bar = tab_bar.clone();
+        let last_cursor_pos = last_cursor_pos_clone;
+        let top_line = top_line.clone();
+        let scrollbar = scrollbar.clone();
+        let save_file = save_file.clone();
+        let save_to = save_to.clone();
+        let confirm_all_unsaved = confirm_all_unsaved.clone();
+        let document = document.clone();
+        let wind = wind.clone();
+        let file_info_label = file_info_label.clone();
+        let line_index = line_index.clone();
+        let undo = undo.clone();
+        let apply_undo = apply_undo.clone();
+        let apply_widget_theme = apply_widget_theme.clone();
+        let find_bar = find_bar.clone();
+        let refresh_find = refresh_find.clone();
+        let hide_find = hide_find.clone();
+        let find_action = find_action.clone();
+        let open_document = open_document.clone();
+        let run_project_search = run_project_search.clone();
+        let tabs = tabs.clone();
+        let switch_tab = switch_tab.clone();
+        let close_tab = close_tab.clone();
+        let panes = panes.clone();
+        let focus_pane = focus_pane.clone();
+        let split_pane = split_pane.clone();
+        let close_pane = close_pane.clone();
+        let chord = chord.clone();
+        let key_hint = key_hint.clone();
+
+        move |w: &mut TextEditor, ev: fltk::enums::Event| {
+            // The focused editor may be borrowed while it takes focus, and then it's the one asking.
+            let focused = editor.try_borrow().map(|ed| *ed == *w).unwrap_or(true);
+            if !focused {
+                let index = panes.borrow().index_of(w);
+                match ev {
+                    // Scrolling a pane doesn't focus it.
+                    fltk::enums::Event::MouseWheel => {
+                        let step = cfg.borrow().editor.scroll_multiplier.max(1);
+                        let mut panes = panes.borrow_mut();
+                        if let Some(pane) = index.and_then(|index| panes.pane_mut(index)) {
+                            let top = scroll_by_wheel(&mut pane.editor, &mut pane.scrollbar, &mut pane.hscrollbar, step);
+                            pane.gutter.set_top(top);
                         }
-                        fltk::app::MouseWheel::Down => {
-                            top = (top + scroll_multiplier).min(max_top);
+                        return true;
+                    }
+                    fltk::enums::Event::Push | fltk::enums::Event::Focus => {
+                        if let Some(index) = index {
+                            focus_pane(index);
                         }
-                        _ => {}
                     }
+                    _ => return false,
+                }
+            }
+            if ev == fltk::enums::Event::KeyDown
+                && app::event_key() == fltk::enums::Key::Escape
+                && find_bar.borrow().group.visible()
+            {
+                hide_find();
+                return true;
+            }
+            match ev {
+                fltk::enums::Event::KeyDown | fltk::enums::Event::KeyUp => {
+                    pause_blink(
+                        blink_paused.clone(),
+                        editor.clone(),
+                        blink_state.clone(),
+                        interval,
+                        blink_timeout_handle.clone(),
+                        blink_callback.clone(),
+                    );
 
+                    update_status_label(
+                        &editor.borrow(),
+                        &mut status_label.borrow_mut(),
+                        &last_cursor_pos,
+                        &line_index.borrow(),
+                    );
+                    if ev == fltk::enums::Event::KeyUp && !document.borrow().large {
+                        refresh_find();
+                    }
+                }
+                fltk::enums::Event::Push | fltk::enums::Event::Drag | fltk::enums::Event::Released => {
+                    let editor = editor.clone();
+                    let last_cursor_pos = last_cursor_pos.clone();
+                    update_status_label(
+                        &editor.borrow(),
+                        &mut status_label.borrow_mut(),
+                        &last_cursor_pos,
+                        &line_index.borrow(),
+                    );
+                }
+                fltk::enums::Event::MouseWheel => {
+                    let mut ed = editor.borrow_mut();
+                    let mut sb = scrollbar.borrow_mut();
+                    get_max_top(&ed, &mut sb, &line_index.borrow());
+                    let scroll_multiplier = cfg.borrow().editor.scroll_multiplier.max(1);
+                    let top = scroll_by_wheel(&mut ed, &mut sb, &mut hscrollbar.borrow_mut(), scroll_multiplier);
                     *top_line.borrow_mut() = top;
-                    ed.scroll(top, 0);
-                    scrollbar.borrow_mut().set_value(top);
+                    gutter.borrow_mut().set_top(top);
 
                     return true;
                 }
@@ -495,163 +2558,262 @@ fn main() {
             }
 
             if let fltk::enums::Event::KeyDown = ev {
-                let bindings: Vec<(Binding, String)> = {
-                    let cfg_borrow = cfg.borrow();
-                    cfg_borrow.bindings.iter().map(|(a, b)| (a.clone(), b.clone())).collect()
-                };
-                for (binding, cmd) in bindings {
-                    let matched = config::Config::shortcut_matches(&cmd);
-                    if matched {
-                        println!("Shortcut matched: {:?} -> {}", binding, cmd);
-                        match binding {
-                            Binding::Save => {
-                                if let Some(parent) = std::path::Path::new(&file_path).parent() {
-                                    if !parent.exists() {
-                                        std::fs::create_dir_all(parent).expect("Failed to create parent directory");
-                                    }
-                                }
-                                std::fs::write(&file_path, buf.borrow().text()).expect("write failed");
-                                if !*file_exists.borrow() {
-                                    if let Some(mut dot) = status_dot.borrow_mut().take() {
-                                        update_status_dot(Some(&mut dot), StatusDotState::Hidden, cfg.borrow().theme.font_size, &cfg.borrow().theme);
-                                    }
-                                }
-                                if let Some(dot) = status_dot.borrow_mut().as_mut() {
-                                    show_status_dot_timed(
-                                        Some(dot),
-                                        StatusDotState::Positive,
-                                        cfg.borrow().theme.font_size,
-                                        &cfg.borrow().theme,
-                                        1.0,
-                                        status_dot.clone(),
-                                    );
+                let step = chord.borrow_mut().press(&cfg.borrow().bindings);
+                let binding = match step {
+                    ChordStep::Run(binding) => binding,
+                    ChordStep::Wait => {
+                        let (prefix, continuations) = {
+                            let chord = chord.borrow();
+                            (chord.prefix(), chord.continuations(&cfg.borrow().bindings))
+                        };
+                        key_hint.borrow_mut().show_hints(&prefix, &continuations, (w.x(), w.y(), w.w(), w.h()));
+                        let timeout = cfg.borrow().editor.chord_timeout;
+                        if timeout > 0.0 {
+                            let handle = app::add_timeout3(timeout, {
+                                let chord = chord.clone();
+                                let key_hint = key_hint.clone();
+                                move |_| {
+                                    chord.borrow_mut().cancel();
+                                    key_hint.borrow_mut().hide();
                                 }
+                            });
+                            chord.borrow_mut().set_timeout(handle);
+                        }
+                        return true;
+                    }
+                    ChordStep::Cancel => {
+                        key_hint.borrow_mut().hide();
+                        return true;
+                    }
+                    ChordStep::Pass => return false,
+                };
+                key_hint.borrow_mut().hide();
+                println!("Shortcut matched: {:?}", binding);
+                match binding {
+                    Binding::Save => {
+                        save_file();
+                    }
+                    Binding::SaveAs => {
+                        let current = document.borrow().path.clone();
+                        if let Some(path) = system_save_file_chooser(&current).filter(|p| !p.is_empty()) {
+                            save_to(&path);
+                        }
+                    }
+                    Binding::Quit => {
+                        if confirm_all_unsaved() {
+                            println!("Quitting app");
+                            app::quit()
+                        }
+                    },
+                    Binding::Reload => {
+                        unsafe {
+                            load_config_and_apply(
+                                &cfg,
+                                &editor,
+                                &mut (*wind_ptr).get_mut(),
+                                &mut (*header_ptr).get_mut(),
+                                blink_state.clone(),
+                                blink_paused.clone(),
+                                blink_timeout_handle.clone(),
+                                blink_callback.clone(),
+                                editor_clone.clone(),
+                                &mut status_label.borrow_mut(),
+                                &mut file_info_label.borrow_mut(),
+                                status_dot.borrow_mut().as_mut(),
+                                Some(&mut *scrollbar.borrow_mut()),
+                            );
+                        }
+                        apply_widget_theme();
+                        layout_panes();
+                        println!("Config reloaded");
+                    }
+                    Binding::MoveLineUp => {
+                        let mut ed = editor.borrow_mut();
+                        if let Some(mut buf) = ed.buffer() {
+                            let pos = ed.insert_position();
+                            let (line, start, end, prev_start) = {
+                                let index = line_index.borrow();
+                                let line = index.line_of(pos);
+                                (line, index.line_start(line), index.line_end(line), index.line_start(line - 1))
+                            };
+                            if line > 0 {
+                                let current = buf.text_range(start, end).unwrap_or_default();
+                                let prev = buf.text_range(prev_start, start - 1).unwrap_or_default();
+                                buf.replace(prev_start, end, &format!("{}\n{}", current, prev));
+                                ed.set_insert_position(prev_start + (pos - start));
                             }
-                            Binding::Quit => {
-                                println!("Quitting app");
-                                app::quit()
-                            },
-                            Binding::Reload => {
-                                unsafe {
-                                    load_config_and_apply(
-                                        &cfg,
-                                        &editor,
-                                        &mut (*wind_ptr).get_mut(),
-                                        &mut (*header_ptr).get_mut(),
-                                        blink_state.clone(),
-                                        blink_paused.clone(),
-                                        blink_timeout_handle.clone(),
-                                        blink_callback.clone(),
-                                        editor_clone.clone(),
-                                        &mut *status_label_ptr,
-                                        status_dot.borrow_mut().as_mut(),
-                                        Some(&mut *scrollbar.borrow_mut()),
-                                    );
-                                }
-                                println!("Config reloaded");
+                        }
+                    },
+                    Binding::MoveLineDown => {
+                        let mut ed = editor.borrow_mut();
+                        if let Some(mut buf) = ed.buffer() {
+                            let pos = ed.insert_position();
+                            let (line, start, end, next_end, line_count) = {
+                                let index = line_index.borrow();
+                                let line = index.line_of(pos);
+                                (line, index.line_start(line), index.line_end(line), index.line_end(line + 1), index.line_count())
+                            };
+                            if line + 1 < line_count {
+                                let current = buf.text_range(start, end).unwrap_or_default();
+                                let next = buf.text_range(end + 1, next_end).unwrap_or_default();
+                                buf.replace(start, next_end, &format!("{}\n{}", next, current));
+                                ed.set_insert_position(start + next.len() as i32 + 1 + (pos - start));
                             }
-                            Binding::MoveLineUp => {
-                                let mut ed = editor.borrow_mut();
-                                if let Some(mut buf) = ed.buffer().map(|b| b.clone()) {
-                                    let pos = ed.insert_position() as usize;
-                                    let text = buf.text();
-                                    let lines: Vec<&str> = text.split('\n').collect();
-
-                                    let mut char_count = 0;
-                                    let mut curr_idx = 0;
-                                    let mut col = 0;
-                                    for (i, line) in lines.iter().enumerate() {
-                                        if pos < char_count + line.len() + 1 {
-                                            curr_idx = i;
-                                            col = pos - char_count;
-                                            break;
-                                        }
-                                        char_count += line.len() + 1;
-                                    }
-
-                                    if curr_idx > 0 {
-                                        let mut new_lines = lines.clone();
-                                        new_lines.swap(curr_idx, curr_idx - 1);
-                                        buf.set_text(&new_lines.join("\n"));
-                                        let mut new_char_count = 0;
-                                        for i in 0..curr_idx - 1 {
-                                            new_char_count += new_lines[i].len() + 1;
-                                        }
-                                        let new_pos = new_char_count + col.min(new_lines[curr_idx - 1].len());
-                                        ed.set_insert_position(new_pos as i32);
-                                    }
-                                }
-                            },
-                            Binding::MoveLineDown => {
-                                let mut ed = editor.borrow_mut();
-                                if let Some(mut buf) = ed.buffer().map(|b| b.clone()) {
-                                    let pos = ed.insert_position() as usize;
-                                    let text = buf.text();
-                                    let lines: Vec<&str> = text.split('\n').collect();
-
-                                    let mut char_count = 0;
-                                    let mut curr_idx = 0;
-                                    let mut col = 0;
-                                    for (i, line) in lines.iter().enumerate() {
-                                        if pos < char_count + line.len() + 1 {
-                                            curr_idx = i;
-                                            col = pos - char_count;
-                                            break;
-                                        }
-                                        char_count += line.len() + 1;
-                                    }
-
-                                    if curr_idx + 1 < lines.len() {
-                                        let mut new_lines = lines.clone();
-                                        new_lines.swap(curr_idx, curr_idx + 1);
-                                        buf.set_text(&new_lines.join("\n"));
-                                        let mut new_char_count = 0;
-                                        for i in 0..curr_idx + 1 {
-                                            new_char_count += new_lines[i].len() + 1;
-                                        }
-                                        let new_pos = new_char_count + col.min(new_lines[curr_idx + 1].len());
-                                        ed.set_insert_position(new_pos as i32);
-                                    }
+                        }
+                    },
+                    Binding::ConvertEncoding => {
+                        let labels: Vec<&'static str> = Encoding::ALL.iter().map(|e| e.label()).collect();
+                        if let Some(idx) = popup_choice(&labels) {
+                            let encoding = Encoding::ALL[idx];
+                            if document.borrow().encoding != encoding {
+                                document.borrow_mut().encoding = encoding;
+                                set_modified(&document, true, &wind, &status_dot, &tab_bar, &cfg.borrow().theme);
+                                file_info_label.borrow_mut().set_label(&document.borrow().file_info());
+                            }
+                        }
+                    }
+                    Binding::ConvertLineEndings => {
+                        let labels: Vec<&'static str> = LineEnding::ALL.iter().map(|e| e.label()).collect();
+                        if let Some(idx) = popup_choice(&labels) {
+                            let line_ending = LineEnding::ALL[idx];
+                            let Some(mut buf) = editor.borrow().buffer() else { return true };
+                            let text = buf.text();
+                            let changed = {
+                                let doc = document.borrow();
+                                doc.line_ending != line_ending || doc.mixed_line_endings || text.contains('\r')
+                            };
+                            if changed {
+                                // Line endings kept as they were, and any stray `\r`, become
+                                // plain line breaks written in the chosen style.
+                                if text.contains('\r') {
+                                    replace_changed(&mut buf, &text, &LineEnding::normalize(&text));
                                 }
-                            },
-                            Binding::OpenFile => {
-                                if let Some(path) = system_file_chooser() {
-                                    if !path.is_empty() {
-                                        let mut buf = TextBuffer::default();
-                                        if let Ok(contents) = std::fs::read_to_string(&path) {
-                                            buf.set_text(&contents);
-                                        }
-                                        editor.borrow_mut().set_buffer(buf.clone());
-
-                                        let file_name = std::path::Path::new(&path)
-                                            .file_name()
-                                            .and_then(|n| n.to_str())
-                                            .unwrap_or("untitled");
-                                        let abs_path = std::fs::canonicalize(&path)
-                                            .map(|p| p.display().to_string())
-                                            .unwrap_or(path.clone());
-                                        unsafe {
-                                            (*wind_ptr).get_mut().set_label(&format!("Skrift - {}", file_name));
-                                            (*header_ptr).get_mut().set_label(&abs_path);
-                                        }
-
-                                        unsafe {
-                                            update_status_label(&editor.borrow(), &mut *status_label_ptr, &last_cursor_pos);
-                                        }
-                                    }
+                                {
+                                    let mut doc = document.borrow_mut();
+                                    doc.line_ending = line_ending;
+                                    doc.mixed_line_endings = false;
                                 }
+                                set_modified(&document, true, &wind, &status_dot, &tab_bar, &cfg.borrow().theme);
+                                file_info_label.borrow_mut().set_label(&document.borrow().file_info());
                             }
                         }
-                        return true;
+                    }
+                    Binding::FileHistory => {
+                        let path = document.borrow().path.clone();
+                        let snapshots = history::list(&path);
+                        if snapshots.is_empty() {
+                            fltk::dialog::alert_default(&format!(
+                                "No saved history for {}.",
+                                document.borrow().file_name()
+                            ));
+                            return true;
+                        }
+                        let Some(mut buf) = editor.borrow().buffer() else { return true };
+                        let current = buf.text();
+                        let theme = cfg.borrow().theme.clone();
+                        let file_name = document.borrow().file_name();
+                        if let Some(text) = history_browser(&file_name, &snapshots, &current, &theme) {
+                            let pos = editor.borrow().insert_position();
+                            buf.set_text(&text);
+                            editor.borrow_mut().set_insert_position(pos.min(buf.length()));
+                        }
+                    }
+                    Binding::Undo => {
+                        let step = undo.borrow_mut().undo();
+                        if let Some(step) = step {
+                            apply_undo(vec![step]);
+                        }
+                    }
+                    Binding::Redo => {
+                        let step = undo.borrow_mut().redo();
+                        if let Some(step) = step {
+                            apply_undo(vec![step]);
+                        }
+                    }
+                    Binding::UndoTree => {
+                        let entries = undo.borrow().entries();
+                        let theme = cfg.borrow().theme.clone();
+                        if let Some(target) = undo_tree_browser(&entries, &theme) {
+                            let steps = undo.borrow_mut().jump(target);
+                            apply_undo(steps);
+                        }
+                    }
+                    Binding::Find
+                    | Binding::Replace
+                    | Binding::FindNext
+                    | Binding::FindPrevious
+                    | Binding::ReplaceNext
+                    | Binding::ReplaceAll
+                    | Binding::ToggleRegex
+                    | Binding::ToggleCase
+                    | Binding::ToggleWholeWord
+                    | Binding::ToggleInSelection => {
+                        find_action(&binding);
+                    }
+                    Binding::ProjectSearch => run_project_search(),
+                    Binding::OpenFile => {
+                        if let Some(path) = system_file_chooser().filter(|p| !p.is_empty()) {
+                            open_document(&path);
+                        }
+                    }
+                    Binding::NextBuffer | Binding::PreviousBuffer => {
+                        let (active, count) = {
+                            let tabs = tabs.borrow();
+                            (tabs.active(), tabs.count())
+                        };
+                        let step = if binding == Binding::NextBuffer { 1 } else { count - 1 };
+                        switch_tab((active + step) % count);
+                    }
+                    Binding::CloseBuffer => {
+                        let active = tabs.borrow().active();
+                        close_tab(active);
+                    }
+                    Binding::BufferSwitcher => {
+                        let (entries, active) = {
+                            let tabs = tabs.borrow();
+                            let doc = document.borrow();
+                            let entries: Vec<(String, String)> = (0..tabs.count())
+                                .map(|i| {
+                                    let open = tabs.document(i, &doc);
+                                    (open.file_name(), open.abs_path())
+                                })
+                                .collect();
+                            (entries, tabs.active())
+                        };
+                        let theme = cfg.borrow().theme.clone();
+                        if let Some(index) = buffer_switcher(&entries, active, &theme) {
+                            switch_tab(index);
+                        }
+                    }
+                    Binding::SplitHorizontal => split_pane(Split::Horizontal),
+                    Binding::SplitVertical => split_pane(Split::Vertical),
+                    Binding::ClosePane => close_pane(),
+                    Binding::NextPane | Binding::PreviousPane => {
+                        let step = if binding == Binding::NextPane { 1 } else { -1 };
+                        let next = {
+                            let panes = panes.borrow();
+                            panes.neighbour(panes.focused(), step)
+                        };
+                        focus_pane(next);
                     }
                 }
-                return false;
+                return true;
             }
             false
         }
     });
+    editor.borrow_mut().handle({
+        let editor_events = editor_events.clone();
+        move |w, ev| editor_events(w, ev)
+    });
+    *pane_events.borrow_mut() = Some(editor_events);
 
-    update_status_label(&editor.borrow(), &mut status_label, &last_cursor_pos);
+    update_status_label(
+        &editor.borrow(),
+        &mut status_label.borrow_mut(),
+        &last_cursor_pos,
+        &line_index.borrow(),
+    );
 
     let config_path = {
         let mut path = dirs::home_dir().unwrap_or(std::path::PathBuf::from("."));
@@ -668,7 +2830,7 @@ fn main() {
     let blink_timeout_handle_ptr = blink_timeout_handle.clone();
     let blink_callback_ptr = blink_callback.clone();
     let editor_clone_ptr = editor_clone.clone();
-    let status_label_ptr = &mut status_label as *mut Frame;
+    let status_label_ptr = status_label.clone();
     let status_dot_ptr = status_dot.clone();
     let wind_ptr = wind.clone();
     let header_ptr = header.clone();
@@ -698,22 +2860,23 @@ fn main() {
             }
 
             if config_changed {
-                unsafe {
-                    load_config_and_apply(
-                        &cfg_ptr,
-                        &editor_ptr,
-                        &mut wind_ptr_cb.borrow_mut(),
-                        &mut header_ptr_cb.borrow_mut(),
-                        blink_state_ptr.clone(),
-                        blink_paused_ptr.clone(),
-                        blink_timeout_handle_ptr.clone(),
-                        blink_callback_ptr.clone(),
-                        editor_clone_ptr.clone(),
-                        &mut *status_label_ptr,
-                        status_dot_ptr.borrow_mut().as_mut(),
-                        Some(&mut *scrollbar.borrow_mut()),
-                    );
-                }
+                load_config_and_apply(
+                    &cfg_ptr,
+                    &editor_ptr,
+                    &mut wind_ptr_cb.borrow_mut(),
+                    &mut header_ptr_cb.borrow_mut(),
+                    blink_state_ptr.clone(),
+                    blink_paused_ptr.clone(),
+                    blink_timeout_handle_ptr.clone(),
+                    blink_callback_ptr.clone(),
+                    editor_clone_ptr.clone(),
+                    &mut status_label_ptr.borrow_mut(),
+                    &mut file_info_label.borrow_mut(),
+                    status_dot_ptr.borrow_mut().as_mut(),
+                    Some(&mut *scrollbar.borrow_mut()),
+                );
+                apply_widget_theme();
+                layout_panes();
             }
 
             app::repeat_timeout3(config_check_interval, handle);
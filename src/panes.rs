@@ -0,0 +1,244 @@
+use fltk::text::TextEditor;
+
+use crate::widgets::gutter::Gutter;
+use crate::widgets::scrollbar::ScrollBar;
+
+/// Space left between two panes.
+const GAP: i32 = 4;
+
+/// `x`, `y`, `w`, `h` of a pane.
+pub type Rect = (i32, i32, i32, i32);
+
+/// The direction of the dividing line: a horizontal split stacks the two panes, a
+/// vertical one puts them side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Horizontal,
+    Vertical,
+}
+
+/// How the editor area is divided. Every split shares its space equally.
+enum Layout {
+    Pane(usize),
+    Split(Split, Box<Layout>, Box<Layout>),
+}
+
+impl Layout {
+    fn rects(&self, x: i32, y: i32, w: i32, h: i32, out: &mut Vec<(usize, Rect)>) {
+        match self {
+            Layout::Pane(index) => out.push((*index, (x, y, w, h))),
+            Layout::Split(Split::Horizontal, first, second) => {
+                let top = (h - GAP) / 2;
+                first.rects(x, y, w, top, out);
+                second.rects(x, y + top + GAP, w, h - top - GAP, out);
+            }
+            Layout::Split(Split::Vertical, first, second) => {
+                let left = (w - GAP) / 2;
+                first.rects(x, y, left, h, out);
+                second.rects(x + left + GAP, y, w - left - GAP, h, out);
+            }
+        }
+    }
+
+    fn order(&self, out: &mut Vec<usize>) {
+        match self {
+            Layout::Pane(index) => out.push(*index),
+            Layout::Split(_, first, second) => {
+                first.order(out);
+                second.order(out);
+            }
+        }
+    }
+
+    /// Replaces the leaf for `target` with a split of it and `added`.
+    fn split(&mut self, target: usize, split: Split, added: usize) -> bool {
+        match self {
+            Layout::Pane(index) if *index == target => {
+                *self = Layout::Split(split, Box::new(Layout::Pane(target)), Box::new(Layout::Pane(added)));
+                true
+            }
+            Layout::Pane(_) => false,
+            Layout::Split(_, first, second) => first.split(target, split, added) || second.split(target, split, added),
+        }
+    }
+
+    /// Removes the leaf for `target`, letting its sibling take the space of their split.
+    fn remove(&mut self, target: usize) -> bool {
+        let Layout::Split(_, first, second) = self else { return false };
+        let sibling = match (&**first, &**second) {
+            (Layout::Pane(index), _) if *index == target => std::mem::replace(&mut **second, Layout::Pane(0)),
+            (_, Layout::Pane(index)) if *index == target => std::mem::replace(&mut **first, Layout::Pane(0)),
+            _ => return first.remove(target) || second.remove(target),
+        };
+        *self = sibling;
+        true
+    }
+
+    /// Shifts indices down after the pane at `removed` is gone.
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Pane(index) if *index > removed => *index -= 1,
+            Layout::Pane(_) => {}
+            Layout::Split(_, first, second) => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+}
+
+/// A pane while another one has focus.
+pub struct Pane {
+    pub editor: TextEditor,
+    pub scrollbar: ScrollBar,
+    pub hscrollbar: ScrollBar,
+    pub gutter: Gutter,
+}
+
+/// The panes of the editor area. The focused pane's editor, scrollbars and gutter are the ones
+/// the rest of the editor works with, so its slot is empty; every other slot holds a pane.
+pub struct Panes<P = Pane> {
+    slots: Vec<Option<P>>,
+    layout: Layout,
+    focused: usize,
+}
+
+impl<P> Panes<P> {
+    pub fn new() -> Self {
+        Panes { slots: vec![None], layout: Layout::Pane(0), focused: 0 }
+    }
+
+    pub fn count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    /// The bounds of every pane when the whole area is `x`, `y`, `w`, `h`.
+    pub fn rects(&self, x: i32, y: i32, w: i32, h: i32) -> Vec<(usize, Rect)> {
+        let mut out = Vec::with_capacity(self.slots.len());
+        self.layout.rects(x, y, w, h, &mut out);
+        out
+    }
+
+    /// Splits the focused pane, putting `pane` in the new half, and returns its index.
+    pub fn split(&mut self, split: Split, pane: P) -> usize {
+        let added = self.slots.len();
+        self.slots.push(Some(pane));
+        self.layout.split(self.focused, split, added);
+        added
+    }
+
+    /// Removes an unfocused pane and hands it back so its widgets can be deleted.
+    pub fn remove(&mut self, index: usize) -> Option<P> {
+        if index == self.focused || index >= self.slots.len() {
+            return None;
+        }
+        self.layout.remove(index);
+        self.layout.renumber(index);
+        if self.focused > index {
+            self.focused -= 1;
+        }
+        self.slots.remove(index)
+    }
+
+    /// The pane `step` places away from `index` in layout order, wrapping around.
+    pub fn neighbour(&self, index: usize, step: isize) -> usize {
+        let mut order = Vec::with_capacity(self.slots.len());
+        self.layout.order(&mut order);
+        let at = order.iter().position(|&i| i == index).unwrap_or(0) as isize;
+        order[(at + step).rem_euclid(order.len() as isize) as usize]
+    }
+
+    pub fn pane_mut(&mut self, index: usize) -> Option<&mut P> {
+        self.slots.get_mut(index).and_then(Option::as_mut)
+    }
+
+    /// Every unfocused pane with its index.
+    pub fn others_mut(&mut self) -> impl Iterator<Item = (usize, &mut P)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_mut().map(|pane| (index, pane)))
+    }
+}
+
+impl Panes {
+    /// Moves focus to the pane at `index`, swapping its widgets with the focused ones in
+    /// `editor`, `scrollbar`, `hscrollbar` and `gutter`.
+    pub fn focus(
+        &mut self,
+        index: usize,
+        editor: &mut TextEditor,
+        scrollbar: &mut ScrollBar,
+        hscrollbar: &mut ScrollBar,
+        gutter: &mut Gutter,
+    ) {
+        let Some(mut pane) = self.slots[index].take() else { return };
+        std::mem::swap(&mut pane.editor, editor);
+        std::mem::swap(&mut pane.scrollbar, scrollbar);
+        std::mem::swap(&mut pane.hscrollbar, hscrollbar);
+        std::mem::swap(&mut pane.gutter, gutter);
+        self.slots[self.focused] = Some(pane);
+        self.focused = index;
+    }
+
+    /// The unfocused pane showing `editor`.
+    pub fn index_of(&self, editor: &TextEditor) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|pane| pane.editor == *editor))
+    }
+}
+
+impl<P> Default for Panes<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pane 0 on the left, with 1 above 2 on the right.
+    fn three_panes(focused: usize) -> Panes<()> {
+        let mut layout = Layout::Pane(0);
+        assert!(layout.split(0, Split::Vertical, 1));
+        assert!(layout.split(1, Split::Horizontal, 2));
+        assert!(!layout.split(3, Split::Horizontal, 4));
+        Panes { slots: (0..3).map(|_| None).collect(), layout, focused }
+    }
+
+    #[test]
+    fn splits_share_their_space() {
+        assert_eq!(Panes::<()>::new().rects(10, 20, 100, 50), [(0, (10, 20, 100, 50))]);
+        assert_eq!(
+            three_panes(0).rects(0, 0, 100, 51),
+            [(0, (0, 0, 48, 51)), (1, (52, 0, 48, 23)), (2, (52, 27, 48, 24))]
+        );
+    }
+
+    #[test]
+    fn neighbours_wrap_around() {
+        let panes = three_panes(0);
+        assert_eq!(panes.neighbour(0, 1), 1);
+        assert_eq!(panes.neighbour(2, 1), 0);
+        assert_eq!(panes.neighbour(0, -1), 2);
+    }
+
+    #[test]
+    fn removing_gives_the_space_to_the_sibling() {
+        let mut panes = three_panes(2);
+        assert!(panes.remove(2).is_none() && panes.count() == 3, "the focused pane stays");
+        assert!(panes.remove(3).is_none() && panes.count() == 3);
+        panes.remove(1);
+        assert_eq!((panes.count(), panes.focused()), (2, 1));
+        assert_eq!(panes.rects(0, 0, 100, 51), [(0, (0, 0, 48, 51)), (1, (52, 0, 48, 51))]);
+        panes.focused = 0;
+        panes.remove(1);
+        assert_eq!(panes.rects(0, 0, 100, 51), [(0, (0, 0, 100, 51))]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::document::Document;
+use crate::file_io;
+use crate::gitignore::Gitignore;
+use crate::search::{MATCH_LIMIT, Search};
+
+/// Files larger than this are skipped; they are almost never source files.
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+/// The most files one search looks at, so a huge tree can't stall the editor.
+const MAX_FILES: usize = 20_000;
+/// The most text one search reads across all files.
+const MAX_SEARCH_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct LineMatch {
+    /// Zero-based line of the match.
+    pub line: usize,
+    /// Byte range of the match in the file text.
+    pub start: usize,
+    pub end: usize,
+    pub preview: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+/// A file's contents before and after a project-wide replace.
+#[derive(Debug, Clone)]
+pub struct FileReplace {
+    pub path: PathBuf,
+    pub original: String,
+    pub replaced: String,
+    pub count: usize,
+}
+
+/// The directory to search: the configured root if set, otherwise the directory of the open file.
+pub fn search_root(path: &str, configured: &str) -> PathBuf {
+    if !configured.is_empty() {
+        let root = file_io::expand_home(configured);
+        return fs::canonicalize(&root).unwrap_or(root);
+    }
+    let absolute = PathBuf::from(file_io::absolute_path(path));
+    absolute
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Text files under `root` that no `.gitignore` excludes, in a stable order, and
+/// whether the walk stopped at `MAX_FILES` before seeing them all.
+pub fn project_files(root: &Path) -> (Vec<PathBuf>, bool) {
+    let mut files = Vec::new();
+    let mut ignore = Gitignore::for_root(root);
+    let complete = walk(root, &mut ignore, &mut files);
+    (files, !complete)
+}
+
+/// Collects the files under `dir`; false once `MAX_FILES` is reached.
+fn walk(dir: &Path, ignore: &mut Gitignore, files: &mut Vec<PathBuf>) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else { return true };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    let saved = ignore.rule_count();
+    ignore.add_dir(dir);
+    for entry in entries {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_symlink() || ignore.is_ignored(&path, file_type.is_dir()) {
+            continue;
+        }
+        if files.len() >= MAX_FILES {
+            ignore.truncate(saved);
+            return false;
+        }
+        if file_type.is_dir() {
+            if !walk(&path, ignore, files) {
+                ignore.truncate(saved);
+                return false;
+            }
+        } else if entry.metadata().is_ok_and(|meta| meta.len() <= MAX_FILE_BYTES) {
+            files.push(path);
+        }
+    }
+    ignore.truncate(saved);
+    true
+}
+
+/// Reads `path` as the editor would, or `None` for unreadable files and ones that don't
+/// decode exactly, such as binaries, which a replace could not write back intact.
+fn read_text(path: &Path) -> Option<String> {
+    let mut document = Document::new(&path.to_string_lossy());
+    let text = document.read().ok()?;
+    (!document.lossy).then_some(text)
+}
+
+/// Searches every project file under `root`. `open` is the path and buffer text of
+/// the file in the editor, which is searched instead of its possibly stale copy on disk.
+/// Also returns whether the search was cut short by the file, byte or match limits, or
+/// by a pattern too costly to finish in some file.
+pub fn search(root: &Path, search: &Search, open: Option<(&Path, &str)>) -> (Vec<FileMatches>, bool) {
+    let mut results = Vec::new();
+    let mut total = 0;
+    let mut read = 0;
+    let (files, mut cut_short) = project_files(root);
+    for path in files {
+        if read >= MAX_SEARCH_BYTES {
+            cut_short = true;
+            break;
+        }
+        let text = match open {
+            Some((open_path, text)) if open_path == path => text.to_string(),
+            _ => match read_text(&path) {
+                Some(text) => text,
+                None => continue,
+            },
+        };
+        read += text.len();
+        let (found, stopped) = search.find_all(&text, (0, text.len()));
+        cut_short |= stopped;
+        if found.is_empty() {
+            continue;
+        }
+        let (mut line, mut counted) = (0, 0);
+        let matches = found
+            .into_iter()
+            .take(MATCH_LIMIT - total)
+            .map(|(start, end)| {
+                line += text[counted..start].matches('\n').count();
+                counted = start;
+                let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+                let line_end = text[start..].find('\n').map(|i| start + i).unwrap_or(text.len());
+                LineMatch {
+                    line,
+                    start,
+                    end,
+                    preview: text[line_start..line_end].trim().chars().take(200).collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+        total += matches.len();
+        results.push(FileMatches { path, matches });
+        if total >= MATCH_LIMIT {
+            cut_short = true;
+            break;
+        }
+    }
+    (results, cut_short)
+}
+
+/// Works out the new contents of every file in `results` without touching the disk.
+pub fn plan_replace(
+    results: &[FileMatches],
+    search: &Search,
+    template: &str,
+    open: Option<(&Path, &str)>,
+) -> Vec<FileReplace> {
+    results
+        .iter()
+        .filter_map(|file| {
+            let original = match open {
+                Some((open_path, text)) if open_path == file.path => text.to_string(),
+                _ => read_text(&file.path)?,
+            };
+            let (matches, _) = search.find_all(&original, (0, original.len()));
+            let (first, last) = (matches.first()?.0, matches.last()?.1);
+            let replaced = format!(
+                "{}{}{}",
+                &original[..first],
+                search.replace_matches(&original, &matches, template),
+                &original[last..]
+            );
+            Some(FileReplace {
+                path: file.path.clone(),
+                original,
+                replaced,
+                count: matches.len(),
+            })
+        })
+        .collect()
+}
+
+/// Writes `text` to `path`, keeping the file's encoding and line endings.
+fn write_text(path: &Path, text: &str) -> io::Result<()> {
+    let mut document = Document::new(&path.to_string_lossy());
+    document.read()?;
+    file_io::write_atomic(path, &document.encode(text)?)
+}
+
+/// Replaces the contents of `path` with `to`, but only while the file still holds
+/// `from`, so edits made elsewhere in the meantime are never overwritten.
+pub fn swap_contents(path: &Path, from: &str, to: &str) -> Result<(), String> {
+    if read_text(path).is_none_or(|current| current != from) {
+        return Err("changed on disk in the meantime".to_string());
+    }
+    write_text(path, to).map_err(|err| file_io::describe_error(&err))
+}
@@ -0,0 +1,781 @@
+//! A small backtracking regular expression engine for the find bar and syntax grammars.
+//!
+//! Supports literals, `.`, character classes with ranges and negation, the
+//! `\d \w \s` shorthands (and their negations), `^`/`$` line anchors, `\b`/`\B`,
+//! capturing and non-capturing groups, alternation, and greedy or lazy
+//! `* + ? {n} {n,} {n,m}` quantifiers.
+//!
+//! Patterns are compiled to a small program that runs with its own backtracking stack,
+//! so neither long input nor many repetitions grow the call stack.
+
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+/// Matching steps allowed at each start position before giving up, so a pathological
+/// pattern can't freeze the editor.
+const STEP_LIMIT: usize = 2_000_000;
+
+/// Matching steps allowed for one search over all its start positions.
+const SEARCH_STEP_LIMIT: usize = 50_000_000;
+
+/// Instructions a compiled pattern may have; `{n,m}` repeats copy their body.
+const PROGRAM_LIMIT: usize = 100_000;
+
+/// A search gave up before it could tell whether the pattern matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepLimit;
+
+#[derive(Debug, Clone, Copy)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+    /// Registers for the start of each unbounded loop's current iteration.
+    loops: usize,
+    /// The character every match starts with, when the pattern begins with a literal.
+    first_char: Option<char>,
+    case_insensitive: bool,
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Escapes every character that has a meaning in a pattern.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    groups: usize,
+}
+
+impl Parser<'_> {
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.concat()?];
+        while self.chars.peek() == Some(&'|') {
+            self.chars.next();
+            alts.push(self.concat()?);
+        }
+        Ok(if alts.len() == 1 { alts.pop().unwrap() } else { Node::Alt(alts) })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.chars.next().ok_or("unexpected end of pattern")?;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::LineStart,
+            '$' => Node::LineEnd,
+            '[' => self.class()?,
+            '(' => {
+                let index = if self.chars.peek() == Some(&'?') {
+                    self.chars.next();
+                    if self.chars.next() != Some(':') {
+                        return Err("only (?:...) groups are supported".into());
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let inner = self.alternation()?;
+                if self.chars.next() != Some(')') {
+                    return Err("unclosed group".into());
+                }
+                Node::Group(Box::new(inner), index)
+            }
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before '{}'", c)),
+            '\\' => {
+                let e = self.chars.next().ok_or("pattern ends with '\\'")?;
+                match e {
+                    'b' => Node::WordBoundary(true),
+                    'B' => Node::WordBoundary(false),
+                    _ => match perl_class(e) {
+                        Some((perl, negated)) => Node::Class(vec![ClassItem::Perl(perl, false)], negated),
+                        None if e.is_ascii_digit() => return Err("backreferences are not supported".into()),
+                        None => Node::Char(escaped_char(e)),
+                    },
+                }
+            }
+            c => Node::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.chars.peek() == Some(&'^');
+        if negated {
+            self.chars.next();
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.chars.next().ok_or("unclosed character class")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let start = if c == '\\' {
+                let e = self.chars.next().ok_or("unclosed character class")?;
+                if let Some((perl, negated)) = perl_class(e) {
+                    items.push(ClassItem::Perl(perl, negated));
+                    continue;
+                }
+                escaped_char(e)
+            } else {
+                c
+            };
+            let mut lookahead = self.chars.clone();
+            if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|&n| n != ']') {
+                self.chars.next();
+                let mut end = self.chars.next().ok_or("unclosed character class")?;
+                if end == '\\' {
+                    end = escaped_char(self.chars.next().ok_or("unclosed character class")?);
+                }
+                if end < start {
+                    return Err(format!("invalid range {}-{}", start, end));
+                }
+                items.push(ClassItem::Range(start, end));
+            } else {
+                items.push(ClassItem::Range(start, start));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+
+    fn quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.chars.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.braces() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        self.chars.next();
+        if matches!(atom, Node::LineStart | Node::LineEnd | Node::WordBoundary(_)) {
+            return Err("cannot repeat an anchor".into());
+        }
+        let greedy = if self.chars.peek() == Some(&'?') {
+            self.chars.next();
+            false
+        } else {
+            true
+        };
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`, leaving the `}` for the caller to consume.
+    /// Returns `None` without consuming anything when the braces are literal text.
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+        let mut body = String::new();
+        loop {
+            match lookahead.next()? {
+                '}' => break,
+                c if c.is_ascii_digit() || c == ',' => body.push(c),
+                _ => return None,
+            }
+        }
+        let bounds = match body.split_once(',') {
+            None => {
+                let n = body.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => {
+                let (min, max): (usize, usize) = (min.parse().ok()?, max.parse().ok()?);
+                if max < min {
+                    return None;
+                }
+                (min, Some(max))
+            }
+        };
+        // Leave the closing brace so `quantifier` consumes it like a single-char quantifier.
+        while self.chars.peek() != Some(&'}') {
+            self.chars.next();
+        }
+        Some(bounds)
+    }
+}
+
+fn perl_class(c: char) -> Option<(Perl, bool)> {
+    match c {
+        'd' => Some((Perl::Digit, false)),
+        'D' => Some((Perl::Digit, true)),
+        'w' => Some((Perl::Word, false)),
+        'W' => Some((Perl::Word, true)),
+        's' => Some((Perl::Space, false)),
+        'S' => Some((Perl::Space, true)),
+        _ => None,
+    }
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+/// One step of a compiled pattern.
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    /// Repeats the single-character instruction inside in one go, so `.*` and `\w+`
+    /// cost one backtrack entry instead of one per character.
+    RepeatOne {
+        inst: Box<Inst>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+    /// Records the position in a capture slot: `2 * group` for the start, one more for the end.
+    Save(usize),
+    /// Carries on at the first target, coming back to the second if that fails.
+    Split(usize, usize),
+    Jump(usize),
+    /// Records where an iteration of an unbounded loop starts.
+    LoopStart(usize),
+    /// Fails an iteration that consumed nothing, which would loop forever.
+    LoopProgress(usize),
+    Match,
+}
+
+/// Where to pick up, or what to undo, when the current attempt fails.
+enum Backtrack {
+    Resume { pc: usize, pos: usize },
+    /// A greedy `RepeatOne` ending at `pos` that can give back characters down to `floor`.
+    GiveBack { pc: usize, floor: usize, pos: usize },
+    /// A lazy `RepeatOne` at `pc` that has taken `count` characters up to `pos`.
+    TakeMore { pc: usize, pos: usize, count: usize },
+    RestoreSlot { slot: usize, old: Option<usize> },
+    RestoreLoop { register: usize, old: usize },
+}
+
+/// What a search keeps between start positions so it allocates once.
+struct State {
+    slots: Vec<Option<usize>>,
+    loops: Vec<usize>,
+    stack: Vec<Backtrack>,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+    loops: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= PROGRAM_LIMIT {
+            return Err("pattern is too large once its repetitions are expanded".into());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    /// Points the `Split` or `Jump` at `at` to `target`; the `Split` goes to its own
+    /// body first when `body_first` is set.
+    fn patch(&mut self, at: usize, target: usize, body_first: bool) {
+        self.program[at] = match self.program[at] {
+            Inst::Split(..) if body_first => Inst::Split(at + 1, target),
+            Inst::Split(..) => Inst::Split(target, at + 1),
+            _ => Inst::Jump(target),
+        };
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(_) | Node::Any | Node::Class(..) | Node::LineStart | Node::LineEnd | Node::WordBoundary(_) => {
+                self.emit(plain_inst(node))?;
+            }
+            Node::Group(inner, None) => self.node(inner)?,
+            Node::Group(inner, Some(i)) => {
+                self.emit(Inst::Save(2 * i))?;
+                self.node(inner)?;
+                self.emit(Inst::Save(2 * i + 1))?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.node(node)?;
+                }
+            }
+            Node::Alt(alts) => {
+                let mut jumps = Vec::new();
+                for (i, alt) in alts.iter().enumerate() {
+                    if i + 1 == alts.len() {
+                        self.node(alt)?;
+                        break;
+                    }
+                    let split = self.emit(Inst::Split(0, 0))?;
+                    self.node(alt)?;
+                    jumps.push(self.emit(Inst::Jump(0))?);
+                    let next = self.program.len();
+                    self.patch(split, next, true);
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.patch(jump, end, true);
+                }
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                if let Node::Char(_) | Node::Any | Node::Class(..) = **node {
+                    let inst = Box::new(plain_inst(node));
+                    self.emit(Inst::RepeatOne { inst, min: *min, max: *max, greedy: *greedy })?;
+                    return Ok(());
+                }
+                for _ in 0..*min {
+                    self.node(node)?;
+                }
+                match max {
+                    None => {
+                        let register = self.loops;
+                        self.loops += 1;
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.emit(Inst::LoopStart(register))?;
+                        self.node(node)?;
+                        self.emit(Inst::LoopProgress(register))?;
+                        self.emit(Inst::Jump(split))?;
+                        let end = self.program.len();
+                        self.patch(split, end, *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.node(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.patch(split, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The instruction for a node that is a single instruction on its own.
+fn plain_inst(node: &Node) -> Inst {
+    match node {
+        Node::Char(c) => Inst::Char(*c),
+        Node::Class(items, negated) => Inst::Class(items.clone(), *negated),
+        Node::LineStart => Inst::LineStart,
+        Node::LineEnd => Inst::LineEnd,
+        Node::WordBoundary(expected) => Inst::WordBoundary(*expected),
+        _ => Inst::Any,
+    }
+}
+
+fn char_at(text: &str, pos: usize) -> Option<char> {
+    text.get(pos..)?.chars().next()
+}
+
+fn char_before(text: &str, pos: usize) -> Option<char> {
+    text.get(..pos)?.chars().next_back()
+}
+
+impl Regex {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, String> {
+        let mut parser = Parser { chars: pattern.chars().peekable(), groups: 0 };
+        let root = parser.alternation()?;
+        if parser.chars.next().is_some() {
+            return Err("unmatched ')'".into());
+        }
+        let mut compiler = Compiler { program: Vec::new(), loops: 0 };
+        compiler.node(&root)?;
+        compiler.emit(Inst::Match)?;
+        let first_char = compiler
+            .program
+            .iter()
+            .find(|inst| !matches!(inst, Inst::LineStart | Inst::WordBoundary(_) | Inst::Save(_)))
+            .and_then(|inst| match inst {
+                Inst::Char(c) if !case_insensitive || c.to_lowercase().eq(c.to_uppercase()) => Some(*c),
+                _ => None,
+            });
+        Ok(Regex {
+            program: compiler.program,
+            groups: parser.groups,
+            loops: compiler.loops,
+            first_char,
+            case_insensitive,
+        })
+    }
+
+    /// Finds the first match starting at or after byte offset `start`. The result holds
+    /// the whole match followed by every capture group. Fails when matching takes too
+    /// long to tell, rather than passing over a match it didn't finish looking for.
+    pub fn captures_at(&self, text: &str, start: usize) -> Result<Option<Captures>, StepLimit> {
+        let mut state = State {
+            slots: vec![None; 2 * (self.groups + 1)],
+            loops: vec![0; self.loops],
+            stack: Vec::new(),
+        };
+        let mut search_steps = SEARCH_STEP_LIMIT;
+        let mut pos = start;
+        loop {
+            if let Some(c) = self.first_char {
+                match text.get(pos..).and_then(|rest| rest.find(c)) {
+                    Some(skip) => pos += skip,
+                    None => return Ok(None),
+                }
+            }
+            let mut steps = STEP_LIMIT.min(search_steps);
+            let found = self.run(text, pos, &mut state, &mut steps);
+            search_steps -= STEP_LIMIT.min(search_steps) - steps;
+            if let Some(end) = found? {
+                let mut caps: Captures = state.slots.chunks(2).map(|s| s[0].zip(s[1])).collect();
+                caps[0] = Some((pos, end));
+                return Ok(Some(caps));
+            }
+            match char_at(text, pos) {
+                Some(c) => pos += c.len_utf8(),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    pub fn find_at(&self, text: &str, start: usize) -> Result<Option<(usize, usize)>, StepLimit> {
+        Ok(self.captures_at(text, start)?.and_then(|caps| caps[0]))
+    }
+
+    /// Expands `$0`..`$9`, `${n}` and `$$` in `template` with the groups of a match.
+    pub fn expand(&self, text: &str, caps: &Captures, template: &str) -> String {
+        let group = |n: usize| caps.get(n).copied().flatten().map(|(s, e)| &text[s..e]).unwrap_or("");
+        let mut out = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                out.push(c);
+                continue;
+            }
+            match chars.peek().copied() {
+                Some('$') => {
+                    chars.next();
+                    out.push('$');
+                }
+                Some(d) if d.is_ascii_digit() => {
+                    chars.next();
+                    out.push_str(group(d as usize - '0' as usize));
+                }
+                Some('{') => {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    let digits: String = lookahead.by_ref().take_while(|c| *c != '}').collect();
+                    match digits.parse() {
+                        Ok(n) => {
+                            out.push_str(group(n));
+                            chars = lookahead;
+                        }
+                        Err(_) => out.push('$'),
+                    }
+                }
+                _ => out.push('$'),
+            }
+        }
+        out
+    }
+
+    fn char_eq(&self, a: char, b: char) -> bool {
+        a == b || (self.case_insensitive && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn single(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Char(expected) => self.char_eq(*expected, c),
+            Inst::Any => c != '\n',
+            Inst::Class(items, negated) => {
+                let hit = |c: char| {
+                    items.iter().any(|item| match item {
+                        ClassItem::Range(lo, hi) => (*lo..=*hi).contains(&c),
+                        ClassItem::Perl(perl, negated) => perl.matches(c) != *negated,
+                    })
+                };
+                let found = hit(c)
+                    || (self.case_insensitive
+                        && (c.to_lowercase().any(|l| l != c && hit(l)) || c.to_uppercase().any(|u| u != c && hit(u))));
+                found != *negated
+            }
+            _ => false,
+        }
+    }
+
+    /// The length of the character at `pos` if `inst` matches it.
+    fn single_at(&self, inst: &Inst, text: &str, pos: usize) -> Option<usize> {
+        char_at(text, pos).filter(|&c| self.single(inst, c)).map(char::len_utf8)
+    }
+
+    /// Runs the program from `start`, returning where the match ends. Every instruction
+    /// costs one of `steps`.
+    fn run(&self, text: &str, start: usize, state: &mut State, steps: &mut usize) -> Result<Option<usize>, StepLimit> {
+        state.slots.fill(None);
+        state.stack.clear();
+        let (mut pc, mut pos) = (0, start);
+        loop {
+            if *steps == 0 {
+                return Err(StepLimit);
+            }
+            *steps -= 1;
+            let ok = match &self.program[pc] {
+                Inst::Match => return Ok(Some(pos)),
+                inst @ (Inst::Char(_) | Inst::Any | Inst::Class(..)) => match self.single_at(inst, text, pos) {
+                    Some(len) => {
+                        pos += len;
+                        true
+                    }
+                    None => false,
+                },
+                Inst::LineStart => pos == 0 || char_before(text, pos) == Some('\n'),
+                Inst::LineEnd => pos == text.len() || char_at(text, pos) == Some('\n'),
+                Inst::WordBoundary(expected) => {
+                    let before = char_before(text, pos).is_some_and(is_word_char);
+                    let after = char_at(text, pos).is_some_and(is_word_char);
+                    (before != after) == *expected
+                }
+                Inst::RepeatOne { inst, min, max, greedy } => {
+                    let mut count = 0;
+                    while count < *min
+                        && let Some(len) = self.single_at(inst, text, pos)
+                    {
+                        pos += len;
+                        count += 1;
+                    }
+                    if count < *min {
+                        false
+                    } else if *greedy {
+                        let floor = pos;
+                        while max.is_none_or(|max| count < max)
+                            && let Some(len) = self.single_at(inst, text, pos)
+                        {
+                            pos += len;
+                            count += 1;
+                        }
+                        if pos > floor {
+                            state.stack.push(Backtrack::GiveBack { pc: pc + 1, floor, pos });
+                        }
+                        true
+                    } else {
+                        state.stack.push(Backtrack::TakeMore { pc, pos, count });
+                        true
+                    }
+                }
+                Inst::Save(slot) => {
+                    state.stack.push(Backtrack::RestoreSlot { slot: *slot, old: state.slots[*slot] });
+                    state.slots[*slot] = Some(pos);
+                    true
+                }
+                Inst::Split(first, second) => {
+                    state.stack.push(Backtrack::Resume { pc: *second, pos });
+                    pc = *first;
+                    continue;
+                }
+                Inst::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Inst::LoopStart(register) => {
+                    state.stack.push(Backtrack::RestoreLoop { register: *register, old: state.loops[*register] });
+                    state.loops[*register] = pos;
+                    true
+                }
+                Inst::LoopProgress(register) => state.loops[*register] != pos,
+            };
+            if ok {
+                pc += 1;
+                continue;
+            }
+            (pc, pos) = loop {
+                match state.stack.pop() {
+                    None => return Ok(None),
+                    Some(Backtrack::Resume { pc, pos }) => break (pc, pos),
+                    Some(Backtrack::GiveBack { pc, floor, pos }) => {
+                        let back = pos - char_before(text, pos).map_or(1, char::len_utf8);
+                        if back > floor {
+                            state.stack.push(Backtrack::GiveBack { pc, floor, pos: back });
+                        }
+                        break (pc, back);
+                    }
+                    Some(Backtrack::TakeMore { pc, pos, count }) => {
+                        let Inst::RepeatOne { inst, max, .. } = &self.program[pc] else { continue };
+                        if max.is_some_and(|max| count >= max) {
+                            continue;
+                        }
+                        if let Some(len) = self.single_at(inst, text, pos) {
+                            state.stack.push(Backtrack::TakeMore { pc, pos: pos + len, count: count + 1 });
+                            break (pc + 1, pos + len);
+                        }
+                    }
+                    Some(Backtrack::RestoreSlot { slot, old }) => state.slots[slot] = old,
+                    Some(Backtrack::RestoreLoop { register, old }) => state.loops[register] = old,
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern, false).unwrap().find_at(text, 0).unwrap()
+    }
+
+    #[test]
+    fn literals_classes_and_anchors() {
+        assert_eq!(find("needle", "haystack needle"), Some((9, 15)));
+        assert_eq!(find(r"\d+", "abc 123 def"), Some((4, 7)));
+        assert_eq!(find("[^a-c]", "abcd"), Some((3, 4)));
+        assert_eq!(find("^b", "a\nb"), Some((2, 3)));
+        assert_eq!(find("a$", "ab\na"), Some((3, 4)));
+        assert_eq!(find(r"\bcat\b", "concat cat"), Some((7, 10)));
+        assert_eq!(find("x", "abc"), None);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let regex = Regex::new("straße", true).unwrap();
+        assert_eq!(regex.find_at("STRAßE", 0).unwrap(), Some((0, 7)));
+        let regex = Regex::new("[a-z]+", true).unwrap();
+        assert_eq!(regex.find_at("ABC", 0).unwrap(), Some((0, 3)));
+    }
+
+    #[test]
+    fn greedy_and_lazy() {
+        assert_eq!(find("<.*>", "<a><b>"), Some((0, 6)));
+        assert_eq!(find("<.*?>", "<a><b>"), Some((0, 3)));
+        assert_eq!(find("(?:ab)+?", "ababab"), Some((0, 2)));
+        assert_eq!(find("(?:ab)+", "ababab"), Some((0, 6)));
+        assert_eq!(find("a{2,3}", "aaaa"), Some((0, 3)));
+        assert_eq!(find("a{2,3}?", "aaaa"), Some((0, 2)));
+        assert_eq!(find("(?:ab){2}", "abababab"), Some((0, 4)));
+        assert_eq!(find("a{2,}", "a aaaaa"), Some((2, 7)));
+    }
+
+    #[test]
+    fn alternation_backtracks() {
+        assert_eq!(find("a(?:b|bc)d", "abcd"), Some((0, 4)));
+        assert_eq!(find("(?:x|xy)+z", "xyxz"), Some((0, 4)));
+        assert_eq!(find(r"\w+\d", "abc1"), Some((0, 4)));
+    }
+
+    #[test]
+    fn empty_iterations_end_the_loop() {
+        assert_eq!(find("(?:a*)*b", "aab"), Some((0, 3)));
+        assert_eq!(find("(a?)+", "b"), Some((0, 0)));
+    }
+
+    #[test]
+    fn captures() {
+        let regex = Regex::new(r"(\w+)@(\w+)(x)?", false).unwrap();
+        let caps = regex.captures_at("mail: me@host", 0).unwrap().unwrap();
+        assert_eq!(caps, vec![Some((6, 13)), Some((6, 8)), Some((9, 13)), None]);
+        // A group repeated keeps its last iteration.
+        let regex = Regex::new("(?:(a)|b)+", false).unwrap();
+        let caps = regex.captures_at("ab", 0).unwrap().unwrap();
+        assert_eq!(caps, vec![Some((0, 2)), Some((0, 1))]);
+        assert_eq!(regex.expand("ab", &caps, "[$1$$${0}]"), "[a$ab]");
+    }
+
+    #[test]
+    fn repeated_groups_on_long_input() {
+        let text = format!("{}c", "ab".repeat(200_000));
+        assert_eq!(find("(ab)*c", &text), Some((0, text.len())));
+        let text = "word ".repeat(100_000);
+        assert_eq!(find(r"(?:\w+\s)+", &text), Some((0, text.len())));
+    }
+
+    #[test]
+    fn each_start_position_gets_its_own_budget() {
+        let text = format!("{}needle", "x".repeat(1_000_000));
+        assert_eq!(find("needle", &text), Some((1_000_000, 1_000_006)));
+        assert_eq!(find("ne+dle", &text), Some((1_000_000, 1_000_006)));
+    }
+
+    #[test]
+    fn costly_patterns_report_the_limit() {
+        let regex = Regex::new("(?:a|aa)*b", false).unwrap();
+        assert_eq!(regex.find_at(&"a".repeat(60), 0), Err(StepLimit));
+    }
+
+    #[test]
+    fn bad_patterns() {
+        assert!(Regex::new("(a", false).is_err());
+        assert!(Regex::new("a)", false).is_err());
+        assert!(Regex::new("*a", false).is_err());
+        assert!(Regex::new("^*", false).is_err());
+        assert!(Regex::new(r"(a)\1", false).is_err());
+        assert!(Regex::new("(?:(?:ab){1000}){1000}", false).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::regex::{self, Regex, StepLimit};
+
+/// Searches stop after this many matches so huge buffers stay responsive.
+pub const MATCH_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub in_selection: bool,
+}
+
+pub struct Search {
+    regex: Regex,
+    options: SearchOptions,
+}
+
+impl Search {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, String> {
+        let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+        let pattern = if options.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+        Ok(Search {
+            regex: Regex::new(&pattern, !options.case_sensitive)?,
+            options,
+        })
+    }
+
+    /// All non-empty matches that lie within `range`, up to `MATCH_LIMIT`, and whether
+    /// the search was cut short by a pattern too costly to match.
+    pub fn find_all(&self, text: &str, range: (usize, usize)) -> (Vec<(usize, usize)>, bool) {
+        let mut matches = Vec::new();
+        let mut pos = range.0;
+        while matches.len() < MATCH_LIMIT {
+            let (start, end) = match self.regex.find_at(text, pos) {
+                Ok(Some(found)) => found,
+                Ok(None) => break,
+                Err(StepLimit) => return (matches, true),
+            };
+            if end > range.1 {
+                break;
+            }
+            if end > start {
+                matches.push((start, end));
+                pos = end;
+            } else {
+                match text[end..].chars().next() {
+                    Some(c) => pos = end + c.len_utf8(),
+                    None => break,
+                }
+            }
+        }
+        (matches, false)
+    }
+
+    /// The text from the first to the end of the last of `matches` with every match replaced.
+    pub fn replace_matches(&self, text: &str, matches: &[(usize, usize)], template: &str) -> String {
+        let mut replaced = String::new();
+        let Some(&(mut pos, _)) = matches.first() else { return replaced };
+        for &(start, end) in matches {
+            replaced.push_str(&text[pos..start]);
+            replaced.push_str(&self.replacement(text, start, template));
+            pos = end;
+        }
+        replaced
+    }
+
+    /// The text that replaces the match starting at `start`. Regex searches expand
+    /// `$1`-style group references; plain searches insert `template` as is.
+    pub fn replacement(&self, text: &str, start: usize, template: &str) -> String {
+        if !self.options.regex {
+            return template.to_string();
+        }
+        match self.regex.captures_at(text, start) {
+            Ok(Some(caps)) => self.regex.expand(text, &caps, template),
+            _ => template.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FindState {
+    pub matches: Vec<(usize, usize)>,
+    /// The selection captured when "in selection" was switched on.
+    pub scope: Option<(usize, usize)>,
+    /// Whether the search gave up before the end, so there may be more matches.
+    pub cut_short: bool,
+}
+
+impl FindState {
+    /// Label for the match count, naming the match that `selection` covers if any.
+    pub fn count_label(&self, selection: (usize, usize)) -> String {
+        let total = if self.matches.len() >= MATCH_LIMIT || self.cut_short {
+            format!("{}+", self.matches.len())
+        } else {
+            self.matches.len().to_string()
+        };
+        match self.matches.iter().position(|&m| m == selection) {
+            Some(i) => format!("{} of {}", i + 1, total),
+            None if self.cut_short && self.matches.is_empty() => "Pattern too complex".to_string(),
+            None if self.matches.is_empty() => "No results".to_string(),
+            None => format!("{} matches", total),
+        }
+    }
+
+    /// The match to jump to from `selection`, wrapping around the ends.
+    pub fn step(&self, selection: (usize, usize), forward: bool) -> Option<(usize, usize)> {
+        if forward {
+            let from = if self.matches.contains(&selection) { selection.1 } else { selection.0 };
+            self.matches.iter().find(|m| m.0 >= from).or(self.matches.first()).copied()
+        } else {
+            self.matches.iter().rev().find(|m| m.0 < selection.0).or(self.matches.last()).copied()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, options: SearchOptions, text: &str, range: (usize, usize)) -> Vec<(usize, usize)> {
+        let (matches, cut_short) = Search::new(query, options).unwrap().find_all(text, range);
+        assert!(!cut_short);
+        matches
+    }
+
+    fn regex() -> SearchOptions {
+        SearchOptions { regex: true, ..Default::default() }
+    }
+
+    #[test]
+    fn plain_and_whole_word() {
+        let text = "Cat concat cat";
+        assert_eq!(find("cat", SearchOptions::default(), text, (0, text.len())), [(0, 3), (7, 10), (11, 14)]);
+        let exact = SearchOptions { case_sensitive: true, whole_word: true, ..Default::default() };
+        assert_eq!(find("cat", exact, text, (0, text.len())), [(11, 14)]);
+        assert_eq!(find("a.c", SearchOptions::default(), "abc a.c", (0, 7)), [(4, 7)]);
+    }
+
+    #[test]
+    fn empty_matches_are_skipped() {
+        assert_eq!(find("x*", regex(), "axxbx", (0, 5)), [(1, 3), (4, 5)]);
+        assert_eq!(find("^", regex(), "one\ntwo", (0, 7)), []);
+        assert_eq!(find("é*", regex(), "aéé", (0, 5)), [(1, 5)]);
+    }
+
+    #[test]
+    fn matches_stay_within_the_range() {
+        let text = "a a a a";
+        assert_eq!(find("a", regex(), text, (2, 5)), [(2, 3), (4, 5)]);
+        assert_eq!(find("a a", regex(), text, (1, 6)), [(2, 5)]);
+        assert_eq!(find("a", regex(), text, (3, 4)), []);
+    }
+
+    #[test]
+    fn searches_stop_at_the_limit() {
+        let text = "a".repeat(MATCH_LIMIT + 5);
+        let (matches, cut_short) = Search::new("a", regex()).unwrap().find_all(&text, (0, text.len()));
+        assert_eq!(matches.len(), MATCH_LIMIT);
+        assert!(!cut_short);
+        let state = FindState { matches, ..Default::default() };
+        assert_eq!(state.count_label((0, 0)), format!("{}+ matches", MATCH_LIMIT));
+    }
+
+    #[test]
+    fn count_labels() {
+        let mut state = FindState::default();
+        assert_eq!(state.count_label((0, 0)), "No results");
+        state.cut_short = true;
+        assert_eq!(state.count_label((0, 0)), "Pattern too complex");
+        state.matches = vec![(0, 1), (4, 5)];
+        assert_eq!(state.count_label((4, 5)), "2 of 2+");
+        state.cut_short = false;
+        assert_eq!(state.count_label((4, 5)), "2 of 2");
+        assert_eq!(state.count_label((4, 4)), "2 matches");
+    }
+
+    #[test]
+    fn stepping_wraps_around() {
+        let state = FindState { matches: vec![(2, 4), (6, 8), (10, 12)], ..Default::default() };
+        assert_eq!(state.step((0, 0), true), Some((2, 4)));
+        assert_eq!(state.step((2, 4), true), Some((6, 8)));
+        assert_eq!(state.step((7, 7), true), Some((10, 12)));
+        assert_eq!(state.step((10, 12), true), Some((2, 4)));
+        assert_eq!(state.step((6, 8), false), Some((2, 4)));
+        assert_eq!(state.step((2, 4), false), Some((10, 12)));
+        assert_eq!(state.step((20, 20), false), Some((10, 12)));
+        assert_eq!(FindState::default().step((0, 0), true), None);
+    }
+}
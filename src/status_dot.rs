@@ -7,6 +7,7 @@ pub enum StatusDotState {
     Hidden,
     Negative,
     Positive,
+    Modified,
 }
 
 pub fn update_status_dot(dot: Option<&mut Dot>, state: StatusDotState, font_size: i32, theme: &config::Theme) {
@@ -26,6 +27,10 @@ pub fn update_status_dot(dot: Option<&mut Dot>, state: StatusDotState, font_size
                 dot.set_color(Color::Green);
                 dot.show();
             }
+            StatusDotState::Modified => {
+                dot.set_color(theme.color_from_str(&theme.modified_color));
+                dot.show();
+            }
         }
     }
 }
@@ -0,0 +1,78 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use fltk::text::TextBuffer;
+
+use crate::file_io;
+
+thread_local! {
+    // The buffer and swap file to flush if the UI thread panics.
+    static CRASH_TARGET: RefCell<Option<(TextBuffer, PathBuf)>> = const { RefCell::new(None) };
+}
+
+pub fn swap_path_for(path: &str) -> PathBuf {
+    file_io::data_dir("swap").join(format!("{}.swp", file_io::path_key(path)))
+}
+
+pub fn write_swap(path: &str, text: &str) -> io::Result<()> {
+    file_io::write_atomic(&swap_path_for(path), text.as_bytes())
+}
+
+pub fn read_swap(path: &str) -> Option<String> {
+    fs::read_to_string(swap_path_for(path)).ok()
+}
+
+pub fn remove_swap(path: &str) {
+    let _ = fs::remove_file(swap_path_for(path));
+}
+
+pub fn set_crash_target(buf: &TextBuffer, path: &str) {
+    let swap_path = swap_path_for(path);
+    CRASH_TARGET.with(|target| *target.borrow_mut() = Some((buf.clone(), swap_path)));
+}
+
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = CRASH_TARGET.try_with(|target| {
+            if let Ok(Some((buf, swap_path))) = target.try_borrow().as_deref() {
+                match file_io::write_atomic(swap_path, buf.text().as_bytes()) {
+                    Ok(()) => eprintln!("Unsaved text written to {}", swap_path.display()),
+                    Err(err) => eprintln!("Failed to write {}: {}", swap_path.display(), err),
+                }
+            }
+        });
+        default_hook(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_paths() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("skrift-swap-notes.txt");
+        let swap = swap_path_for(&path.to_string_lossy());
+        assert!(swap.starts_with(file_io::data_dir("swap")));
+        let name = swap.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("skrift-swap-notes.txt.") && name.ends_with(".swp"), "{}", name);
+        assert_ne!(swap, swap_path_for(&dir.join("skrift-swap-other.txt").to_string_lossy()));
+    }
+
+    #[test]
+    fn write_read_and_remove() {
+        let path = std::env::temp_dir().join(format!("skrift-swap-{}.txt", std::process::id()));
+        let path = path.to_string_lossy();
+        assert_eq!(read_swap(&path), None);
+        write_swap(&path, "unsaved").unwrap();
+        assert_eq!(read_swap(&path).as_deref(), Some("unsaved"));
+        write_swap(&path, "newer").unwrap();
+        assert_eq!(read_swap(&path).as_deref(), Some("newer"));
+        remove_swap(&path);
+        assert_eq!(read_swap(&path), None);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config::SyntaxTheme;
+
+/// The kinds of text a grammar tells apart. Each has its own colour in `[theme.syntax]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Function,
+    Constant,
+    Operator,
+    Attribute,
+}
+
+impl Token {
+    pub const ALL: [Token; 10] = [
+        Token::Plain,
+        Token::Comment,
+        Token::String,
+        Token::Number,
+        Token::Keyword,
+        Token::Type,
+        Token::Function,
+        Token::Constant,
+        Token::Operator,
+        Token::Attribute,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(Token::Plain),
+            "comment" => Some(Token::Comment),
+            "string" => Some(Token::String),
+            "number" => Some(Token::Number),
+            "keyword" => Some(Token::Keyword),
+            "type" => Some(Token::Type),
+            "function" => Some(Token::Function),
+            "constant" => Some(Token::Constant),
+            "operator" => Some(Token::Operator),
+            "attribute" => Some(Token::Attribute),
+            _ => None,
+        }
+    }
+
+    /// The colour of this token, or `None` for plain text, which uses the foreground.
+    pub fn color(self, theme: &SyntaxTheme) -> Option<&str> {
+        match self {
+            Token::Plain => None,
+            Token::Comment => Some(&theme.comment),
+            Token::String => Some(&theme.string),
+            Token::Number => Some(&theme.number),
+            Token::Keyword => Some(&theme.keyword),
+            Token::Type => Some(&theme.type_name),
+            Token::Function => Some(&theme.function),
+            Token::Constant => Some(&theme.constant),
+            Token::Operator => Some(&theme.operator),
+            Token::Attribute => Some(&theme.attribute),
+        }
+    }
+
+    /// The style buffer byte for this token, with or without the find match background.
+    pub fn style(self, matched: bool) -> u8 {
+        let style = b'A' + self as u8;
+        if matched { matched_style(style) } else { style }
+    }
+}
+
+const TOKEN_COUNT: u8 = Token::ALL.len() as u8;
+
+/// The same style with the find match background.
+pub fn matched_style(style: u8) -> u8 {
+    if style < b'A' + TOKEN_COUNT { style + TOKEN_COUNT } else { style }
+}
+
+/// The same style without the find match background.
+pub fn unmatched_style(style: u8) -> u8 {
+    if style >= b'A' + TOKEN_COUNT { style - TOKEN_COUNT } else { style }
+}
+
+/// What a grammar carries from the end of one line to the start of the next, such as
+/// being inside a block comment. Grammars may use it as a stack of open contexts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineState(pub Vec<u32>);
+
+/// A language definition. Other kinds of grammars, such as TextMate syntaxes or
+/// tree-sitter parsers, plug in by implementing this and registering with the registry.
+pub trait Grammar {
+    fn name(&self) -> &str;
+
+    /// Splits `line`, without its line break, into tokens given the state the previous
+    /// line ended in, and leaves `state` as this line ends. Each span is the byte
+    /// offset its token ends at; spans are in order and cover the whole line.
+    fn highlight_line(&self, line: &str, state: &mut LineState, spans: &mut Vec<(usize, Token)>);
+}
@@ -0,0 +1,133 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use fltk::text::TextBuffer;
+
+use crate::line_index::LineIndex;
+use crate::syntax::grammar::{Grammar, LineState, Token, matched_style};
+
+/// Lines highlighted between checks of the time budget.
+const CHECK_EVERY: usize = 64;
+
+/// Syntax highlighting state for one buffer. The state each line ends in is kept, so
+/// after an edit only the lines from the edit on are redone, stopping as soon as a line
+/// ends in the same state as before.
+pub struct Highlighter {
+    grammar: Option<Rc<dyn Grammar>>,
+    /// The state every line ends in, or `None` where the line changed since.
+    states: Vec<Option<LineState>>,
+    /// The first line whose styles may be out of date.
+    dirty: Option<usize>,
+}
+
+impl Highlighter {
+    pub fn new(grammar: Option<Rc<dyn Grammar>>) -> Self {
+        let dirty = grammar.as_ref().map(|_| 0);
+        Highlighter { grammar, states: Vec::new(), dirty }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.grammar.as_deref().map(|g| g.name())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Forgets every line so the whole buffer is highlighted again.
+    pub fn reset(&mut self) {
+        self.states.clear();
+        self.dirty = self.grammar.as_ref().map(|_| 0);
+    }
+
+    /// Records an edit on `line` that removed `removed` line breaks and added `added`.
+    pub fn edit(&mut self, line: usize, removed: usize, added: usize) {
+        if self.grammar.is_none() {
+            return;
+        }
+        if line < self.states.len() {
+            let end = (line + removed + 1).min(self.states.len());
+            self.states.splice(line..end, std::iter::repeat_n(None, added + 1));
+        }
+        self.dirty = Some(self.dirty.map_or(line, |d| d.min(line)));
+    }
+
+    /// Highlights from the first dirty line into `style` until the styles are up to date
+    /// or `budget` runs out. Text inside `matches` keeps the find match background.
+    pub fn run(&mut self, text: &TextBuffer, style: &mut TextBuffer, lines: &LineIndex, matches: &[(usize, usize)], budget: Duration) {
+        let (Some(grammar), Some(first)) = (self.grammar.clone(), self.dirty) else { return };
+        if style.length() != text.length() {
+            self.dirty = None;
+            return;
+        }
+        let started = Instant::now();
+        let line_count = lines.line_count() as usize;
+        self.states.resize(line_count, None);
+        if first >= line_count {
+            self.dirty = None;
+            return;
+        }
+
+        let mut state = match first {
+            0 => LineState::default(),
+            _ => self.states[first - 1].clone().unwrap_or_default(),
+        };
+        let from = lines.line_start(first as i32) as usize;
+        let mut styles = Vec::new();
+        let mut spans = Vec::new();
+        let mut matches = matches[matches.partition_point(|m| m.1 <= from)..].iter().peekable();
+        let mut line = first;
+        self.dirty = loop {
+            let start = lines.line_start(line as i32) as usize;
+            let end = lines.line_end(line as i32) as usize;
+            let source = text.text_range(start as i32, end as i32).unwrap_or_default();
+            spans.clear();
+            grammar.highlight_line(&source, &mut state, &mut spans);
+            let mut at = 0;
+            for &(span_end, token) in &spans {
+                let span_end = span_end.min(source.len());
+                styles.extend(std::iter::repeat_n(token.style(false), span_end.saturating_sub(at)));
+                at = at.max(span_end);
+            }
+            styles.extend(std::iter::repeat_n(Token::Plain.style(false), source.len() - at));
+            if line + 1 < line_count {
+                styles.push(Token::Plain.style(false));
+            }
+
+            let unchanged = self.states[line].as_ref() == Some(&state);
+            self.states[line] = Some(state.clone());
+            line += 1;
+            if line >= line_count {
+                break None;
+            }
+            if unchanged && self.states[line].is_some() {
+                // Everything after ends up as before, unless another edit is further on.
+                break self.states[line..].iter().position(Option::is_none).map(|i| line + i);
+            }
+            if (line - first) % CHECK_EVERY == 0 && started.elapsed() >= budget {
+                break Some(line);
+            }
+        };
+
+        let to = from + styles.len();
+        while let Some(&&(s, e)) = matches.peek() {
+            if s >= to {
+                break;
+            }
+            for b in &mut styles[s.max(from) - from..e.min(to) - from] {
+                *b = matched_style(*b);
+            }
+            matches.next();
+        }
+        // Every style byte is ASCII.
+        let styles = String::from_utf8(styles).unwrap_or_default();
+        style.replace(from as i32, to as i32, &styles);
+    }
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
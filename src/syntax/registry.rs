@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::syntax::grammar::Grammar;
+use crate::syntax::rules::RuleGrammar;
+
+const BUILTIN: &[&str] = &[
+    include_str!("grammars/rust.toml"),
+    include_str!("grammars/python.toml"),
+    include_str!("grammars/javascript.toml"),
+    include_str!("grammars/c.toml"),
+    include_str!("grammars/shell.toml"),
+    include_str!("grammars/json.toml"),
+    include_str!("grammars/toml.toml"),
+    include_str!("grammars/markdown.toml"),
+];
+
+/// A grammar and what files it applies to.
+struct Entry {
+    grammar: Rc<dyn Grammar>,
+    extensions: Vec<String>,
+    file_names: Vec<String>,
+    shebangs: Vec<String>,
+}
+
+/// The known grammars, and picking one for a file.
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    /// The built-in grammars plus every `*.toml` in `~/.config/skrift/syntax`. A user
+    /// grammar with the same name as a built-in one replaces it.
+    pub fn load() -> Self {
+        let mut registry = Registry { entries: Vec::new() };
+        for text in BUILTIN {
+            match RuleGrammar::from_toml(text) {
+                Ok(grammar) => registry.add_rules(grammar),
+                Err(e) => eprintln!("Built-in grammar failed to load: {}", e),
+            }
+        }
+
+        let mut dir = dirs::home_dir().unwrap_or(PathBuf::from("."));
+        dir.push(".config/skrift/syntax");
+        let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+            .map(|entries| entries.flatten().map(|e| e.path()).collect())
+            .unwrap_or_default();
+        paths.retain(|p| p.extension().is_some_and(|e| e == "toml"));
+        paths.sort();
+        for path in paths {
+            let loaded = fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|t| RuleGrammar::from_toml(&t));
+            match loaded {
+                Ok(grammar) => registry.add_rules(grammar),
+                Err(e) => eprintln!("Failed to load grammar {}: {}", path.display(), e),
+            }
+        }
+        registry
+    }
+
+    fn add_rules(&mut self, grammar: RuleGrammar) {
+        let extensions = grammar.extensions.clone();
+        let file_names = grammar.file_names.clone();
+        let shebangs = grammar.shebangs.clone();
+        self.register(Rc::new(grammar), extensions, file_names, shebangs);
+    }
+
+    /// Adds a grammar used for files with one of `extensions`, named one of
+    /// `file_names`, or starting with a `#!` line that runs one of `shebangs`.
+    pub fn register(&mut self, grammar: Rc<dyn Grammar>, extensions: Vec<String>, file_names: Vec<String>, shebangs: Vec<String>) {
+        self.entries.retain(|e| e.grammar.name() != grammar.name());
+        self.entries.push(Entry { grammar, extensions, file_names, shebangs });
+    }
+
+    /// The grammar for the file at `path` whose first line is `first_line`. Later
+    /// grammars win, so user grammars take precedence over built-in ones.
+    pub fn detect(&self, path: &str, first_line: &str) -> Option<Rc<dyn Grammar>> {
+        let path = Path::new(path);
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        let by_name = self.entries.iter().rev().find(|e| {
+            e.file_names.iter().any(|n| n == file_name)
+                || (!extension.is_empty() && e.extensions.iter().any(|x| x.eq_ignore_ascii_case(&extension)))
+        });
+        if let Some(entry) = by_name {
+            return Some(entry.grammar.clone());
+        }
+
+        let interpreter = interpreter(first_line)?;
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| {
+                e.shebangs.iter().any(|s| {
+                    interpreter
+                        .strip_prefix(s.as_str())
+                        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit() || c == '.'))
+                })
+            })
+            .map(|e| e.grammar.clone())
+    }
+}
+
+/// The program a `#!` line runs, looking through `env`: `python3` for both
+/// `#!/usr/bin/python3` and `#!/usr/bin/env python3`.
+fn interpreter(first_line: &str) -> Option<&str> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        words.find(|w| !w.starts_with('-') && !w.contains('='))
+    } else {
+        Some(program)
+    }
+}
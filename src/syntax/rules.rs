@@ -0,0 +1,244 @@
+use crate::regex::Regex;
+use crate::syntax::grammar::{Grammar, LineState, Token};
+
+/// How a rule recognises its text.
+enum Pattern {
+    /// A match within one line. With a group, only that group gets the token.
+    Match { regex: Regex, group: usize },
+    /// Everything from `begin` through `end`, which may span lines. `skip` matches
+    /// escapes inside the region that must not be taken for its end.
+    Region { begin: Regex, end: Regex, skip: Option<Regex> },
+}
+
+/// Where a rule matched: the whole match, and the part its token applies to.
+type Found = ((usize, usize), (usize, usize));
+
+struct Rule {
+    token: Token,
+    pattern: Pattern,
+}
+
+/// A grammar made of regex rules, read from a TOML definition:
+///
+/// ```toml
+/// name = "Rust"
+/// extensions = ["rs"]
+///
+/// [[rule]]
+/// token = "comment"
+/// begin = '/\*'
+/// end = '\*/'
+///
+/// [[rule]]
+/// token = "function"
+/// match = '\b([a-z_]\w*)\s*\('
+/// group = 1
+/// ```
+///
+/// At every position the rule whose match starts first wins, and among those the one
+/// listed first.
+pub struct RuleGrammar {
+    name: String,
+    pub extensions: Vec<String>,
+    pub file_names: Vec<String>,
+    pub shebangs: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+fn strings(value: &toml::Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+impl RuleGrammar {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let value: toml::Value = toml::from_str(text).map_err(|e| e.to_string())?;
+        let name = value.get("name").and_then(|v| v.as_str()).ok_or("missing name")?.to_string();
+        let ignore_case = value.get("ignore_case").and_then(|v| v.as_bool()).unwrap_or(false);
+        let regex = |rule: &toml::Value, key: &str| -> Result<Option<Regex>, String> {
+            rule.get(key)
+                .and_then(|v| v.as_str())
+                .map(|p| Regex::new(p, ignore_case).map_err(|e| format!("{}: {}: {}", name, p, e)))
+                .transpose()
+        };
+
+        let mut rules = Vec::new();
+        for rule in value.get("rule").and_then(|v| v.as_array()).into_iter().flatten() {
+            let token_name = rule.get("token").and_then(|v| v.as_str()).unwrap_or("plain");
+            let token = Token::from_name(token_name)
+                .ok_or_else(|| format!("{}: unknown token '{}'", name, token_name))?;
+            let pattern = match (regex(rule, "match")?, regex(rule, "begin")?, regex(rule, "end")?) {
+                (Some(regex), None, None) => {
+                    let group = rule.get("group").and_then(|v| v.as_integer()).unwrap_or(0).max(0) as usize;
+                    Pattern::Match { regex, group }
+                }
+                (None, Some(begin), Some(end)) => Pattern::Region { begin, end, skip: regex(rule, "skip")? },
+                _ => return Err(format!("{}: a rule needs either 'match' or 'begin' and 'end'", name)),
+            };
+            rules.push(Rule { token, pattern });
+        }
+
+        Ok(RuleGrammar {
+            extensions: strings(&value, "extensions"),
+            file_names: strings(&value, "file_names"),
+            shebangs: strings(&value, "shebangs"),
+            name,
+            rules,
+        })
+    }
+
+    /// Where the region ends on `line` when searching from `from`, past its end match.
+    fn region_end(line: &str, from: usize, end: &Regex, skip: Option<&Regex>) -> Option<usize> {
+        let mut pos = from;
+        loop {
+            let (end_start, end_end) = end.find_at(line, pos).ok()??;
+            match skip.and_then(|s| s.find_at(line, pos).ok().flatten()) {
+                Some((s, e)) if s < end_start && e > s => pos = e,
+                _ => return Some(end_end),
+            }
+        }
+    }
+
+    /// The earliest match of `rule` at or after `pos`.
+    fn find(rule: &Rule, line: &str, pos: usize) -> Option<Found> {
+        match &rule.pattern {
+            Pattern::Match { regex, group } => {
+                let caps = regex.captures_at(line, pos).ok()??;
+                let whole = caps[0]?;
+                Some((whole, caps.get(*group).copied().flatten().unwrap_or(whole)))
+            }
+            Pattern::Region { begin, .. } => begin.find_at(line, pos).ok().flatten().map(|m| (m, m)),
+        }
+    }
+}
+
+fn push(spans: &mut Vec<(usize, Token)>, end: usize, token: Token) {
+    if end <= spans.last().map_or(0, |s| s.0) {
+        return;
+    }
+    match spans.last_mut() {
+        Some((last_end, last)) if *last == token => *last_end = end,
+        _ => spans.push((end, token)),
+    }
+}
+
+impl Grammar for RuleGrammar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn highlight_line(&self, line: &str, state: &mut LineState, spans: &mut Vec<(usize, Token)>) {
+        let mut pos = 0;
+
+        if let Some(&open) = state.0.last() {
+            if let Some(Rule { token, pattern: Pattern::Region { end, skip, .. } }) = self.rules.get(open as usize) {
+                match Self::region_end(line, 0, end, skip.as_ref()) {
+                    Some(e) => {
+                        push(spans, e, *token);
+                        state.0.pop();
+                        pos = e;
+                    }
+                    None => {
+                        push(spans, line.len(), *token);
+                        return;
+                    }
+                }
+            } else {
+                state.0.clear();
+            }
+        }
+
+        // The next match of every rule, kept until the scan passes its start.
+        let mut next: Vec<Option<Found>> = self.rules.iter().map(|rule| Self::find(rule, line, pos)).collect();
+        while pos < line.len() {
+            let mut best: Option<usize> = None;
+            for (i, rule) in self.rules.iter().enumerate() {
+                if next[i].is_some_and(|((s, _), _)| s < pos) {
+                    next[i] = Self::find(rule, line, pos);
+                }
+                if let Some(((s, _), _)) = next[i]
+                    && best.is_none_or(|b| next[b].is_some_and(|((b_start, _), _)| s < b_start))
+                {
+                    best = Some(i);
+                }
+            }
+            let Some(i) = best else { break };
+            let ((start, end), (token_start, token_end)) = next[i].unwrap();
+            let rule = &self.rules[i];
+            push(spans, start, Token::Plain);
+
+            if end == start {
+                // An empty match colours nothing; step past it.
+                pos = start + line[start..].chars().next().map_or(1, char::len_utf8);
+                next[i] = if pos < line.len() { Self::find(rule, line, pos) } else { None };
+            } else if let Pattern::Region { end: region_end, skip, .. } = &rule.pattern {
+                match Self::region_end(line, end, region_end, skip.as_ref()) {
+                    Some(e) => {
+                        push(spans, e, rule.token);
+                        pos = e;
+                    }
+                    None => {
+                        push(spans, line.len(), rule.token);
+                        state.0.push(i as u32);
+                        return;
+                    }
+                }
+            } else {
+                push(spans, token_start, Token::Plain);
+                push(spans, token_end, rule.token);
+                push(spans, end, Token::Plain);
+                pos = end;
+            }
+        }
+        push(spans, line.len(), Token::Plain);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The token of every byte of `line` highlighted with `grammar` from a fresh state.
+    fn highlight(grammar: &str, line: &str) -> Vec<Token> {
+        let grammar = RuleGrammar::from_toml(grammar).unwrap();
+        let mut spans = Vec::new();
+        grammar.highlight_line(line, &mut LineState::default(), &mut spans);
+        let mut tokens = Vec::new();
+        for (end, token) in spans {
+            tokens.resize(end, token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn very_long_strings() {
+        let line = format!("\"{}\"", "x\\\"".repeat(20_000));
+        for grammar in [
+            include_str!("grammars/json.toml"),
+            include_str!("grammars/javascript.toml"),
+            include_str!("grammars/python.toml"),
+            include_str!("grammars/c.toml"),
+            include_str!("grammars/toml.toml"),
+        ] {
+            let tokens = highlight(grammar, &line);
+            assert_eq!(tokens.len(), line.len());
+            assert!(tokens.iter().all(|&t| t == Token::String), "{}", grammar.lines().next().unwrap());
+        }
+        let key = format!("{}: 1", line);
+        let tokens = highlight(include_str!("grammars/json.toml"), &key);
+        assert!(tokens[..line.len()].iter().all(|&t| t == Token::Attribute));
+    }
+
+    #[test]
+    fn unclosed_strings_end_with_their_line() {
+        let grammar = RuleGrammar::from_toml(include_str!("grammars/javascript.toml")).unwrap();
+        let mut state = LineState::default();
+        let mut spans = Vec::new();
+        grammar.highlight_line("x = 'abc", &mut state, &mut spans);
+        assert_eq!(spans.last(), Some(&(8, Token::String)));
+        assert_eq!(state, LineState::default());
+    }
+}
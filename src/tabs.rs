@@ -0,0 +1,160 @@
+use fltk::text::TextBuffer;
+
+use crate::document::Document;
+use crate::file_io;
+use crate::line_index::LineIndex;
+use crate::syntax::highlighter::Highlighter;
+use crate::undo::UndoTree;
+
+/// Everything that belongs to a buffer while another one is shown in the editor.
+pub struct Parked {
+    pub document: Document,
+    pub buffer: TextBuffer,
+    pub line_index: LineIndex,
+    pub undo: UndoTree,
+    pub highlighter: Highlighter,
+    /// The contents of the style buffer, so the buffer doesn't need highlighting again.
+    pub styles: String,
+    pub cursor: i32,
+    pub top_line: i32,
+}
+
+/// The open buffers in tab order. The shown buffer's state lives in the editor and the
+/// shared document, line index, undo tree and highlighter, so its slot is empty; every other slot
+/// holds a parked buffer.
+pub struct Tabs<T = Parked> {
+    slots: Vec<Option<T>>,
+    active: usize,
+}
+
+impl<T> Tabs<T> {
+    pub fn new() -> Self {
+        Tabs { slots: vec![None], active: 0 }
+    }
+
+    pub fn count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Parks `current` in the active slot and takes the buffer at `index` out to be shown.
+    pub fn switch(&mut self, index: usize, current: T) -> T {
+        self.slots[self.active] = Some(current);
+        self.active = index;
+        self.slots[index].take().expect("only the active slot is empty")
+    }
+
+    /// Parks `current` and makes a new, empty slot at the end the active one.
+    pub fn push(&mut self, current: T) {
+        self.slots[self.active] = Some(current);
+        self.slots.push(None);
+        self.active = self.slots.len() - 1;
+    }
+
+    /// Drops the active slot and takes out its neighbour to be shown instead, or
+    /// returns `None` when it is the last tab.
+    pub fn close_active(&mut self) -> Option<T> {
+        if self.slots.len() < 2 {
+            return None;
+        }
+        let next = if self.active + 1 < self.slots.len() { self.active + 1 } else { self.active - 1 };
+        let shown = self.slots[next].take();
+        self.slots.remove(self.active);
+        self.active = if next > self.active { next - 1 } else { next };
+        shown
+    }
+}
+
+impl Tabs {
+    /// The tab that has `path` open, if any.
+    pub fn find(&self, path: &str, active: &Document) -> Option<usize> {
+        let wanted = file_io::absolute_path(path);
+        (0..self.slots.len()).find(|&i| file_io::absolute_path(&self.document(i, active).path) == wanted)
+    }
+
+    /// The parked tab holding `buffer`.
+    pub fn find_buffer(&self, buffer: &TextBuffer) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|parked| parked.buffer == *buffer))
+    }
+
+    /// The document of tab `index`; the active tab's comes from `active`.
+    pub fn document<'a>(&'a self, index: usize, active: &'a Document) -> &'a Document {
+        match &self.slots[index] {
+            Some(parked) => &parked.document,
+            None => active,
+        }
+    }
+
+    /// Tab titles with their modified flags, in tab order.
+    pub fn labels(&self, active: &Document) -> Vec<(String, bool)> {
+        (0..self.slots.len())
+            .map(|i| {
+                let document = self.document(i, active);
+                (document.file_name(), document.modified)
+            })
+            .collect()
+    }
+}
+
+impl<T> Default for Tabs<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tabs whose parked contents are the names of their buffers.
+    fn tabs(names: &[&'static str], active: usize) -> Tabs<&'static str> {
+        let mut tabs = Tabs::new();
+        let last = names.len() - 1;
+        for name in &names[..last] {
+            tabs.push(*name);
+        }
+        if active != last {
+            assert_eq!(tabs.switch(active, names[last]), names[active]);
+        }
+        tabs
+    }
+
+    fn parked(tabs: &Tabs<&'static str>) -> Vec<Option<&'static str>> {
+        tabs.slots.clone()
+    }
+
+    #[test]
+    fn pushing_parks_the_shown_buffer() {
+        let mut tabs = Tabs::new();
+        assert_eq!((tabs.count(), tabs.active()), (1, 0));
+        tabs.push("a");
+        tabs.push("b");
+        assert_eq!((tabs.count(), tabs.active()), (3, 2));
+        assert_eq!(parked(&tabs), [Some("a"), Some("b"), None]);
+    }
+
+    #[test]
+    fn switching_swaps_the_shown_buffer() {
+        let mut tabs = tabs(&["a", "b", "c"], 2);
+        assert_eq!(tabs.switch(0, "c"), "a");
+        assert_eq!(tabs.active(), 0);
+        assert_eq!(parked(&tabs), [None, Some("b"), Some("c")]);
+    }
+
+    #[test]
+    fn closing_shows_the_next_tab_or_the_last() {
+        let mut tabs = tabs(&["a", "b", "c"], 1);
+        assert_eq!(tabs.close_active(), Some("c"));
+        assert_eq!(tabs.active(), 1);
+        assert_eq!(parked(&tabs), [Some("a"), None]);
+        assert_eq!(tabs.close_active(), Some("a"));
+        assert_eq!(tabs.active(), 0);
+        assert_eq!(tabs.close_active(), None, "the last tab stays open");
+        assert_eq!(tabs.count(), 1);
+    }
+}
@@ -0,0 +1,503 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::file_io;
+use crate::history;
+
+/// Typing pauses longer than this start a new undo step.
+const GROUP_TIMEOUT_MS: u64 = 2000;
+
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub pos: i32,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    fn is_typing(&self) -> bool {
+        let single = |s: &str| s.chars().count() == 1;
+        (self.deleted.is_empty() && single(&self.inserted)) || (self.inserted.is_empty() && single(&self.deleted))
+    }
+
+    fn summary(&self) -> String {
+        let (sign, text) = if self.inserted.is_empty() {
+            ('-', &self.deleted)
+        } else {
+            ('+', &self.inserted)
+        };
+        let preview: String = text
+            .chars()
+            .take(24)
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        format!("{}\"{}\"", sign, preview.trim())
+    }
+}
+
+pub enum Step {
+    Undo(Vec<Edit>),
+    Redo(Vec<Edit>),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    redo_child: Option<usize>,
+    edits: Vec<Edit>,
+    timestamp: u64,
+}
+
+/// Branching edit history. Undoing and then editing starts a new branch instead of
+/// discarding the undone steps, so every state of the buffer stays reachable.
+pub struct UndoTree {
+    nodes: Vec<Node>,
+    current: usize,
+    saved: Option<usize>,
+    open: bool,
+    /// Set while undo steps are applied so the resulting buffer changes aren't recorded.
+    pub applying: bool,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        UndoTree {
+            nodes: vec![Node {
+                parent: None,
+                children: Vec::new(),
+                redo_child: None,
+                edits: Vec::new(),
+                timestamp: now_ms(),
+            }],
+            current: 0,
+            saved: Some(0),
+            open: false,
+            applying: false,
+        }
+    }
+
+    pub fn record(&mut self, edit: Edit) {
+        if self.applying {
+            return;
+        }
+        let now = now_ms();
+        if self.merge(&edit, now) {
+            self.nodes[self.current].timestamp = now;
+            return;
+        }
+        let id = self.nodes.len();
+        self.open = edit.is_typing();
+        self.nodes.push(Node {
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo_child: None,
+            edits: vec![edit],
+            timestamp: now,
+        });
+        self.nodes[self.current].children.push(id);
+        self.nodes[self.current].redo_child = Some(id);
+        self.current = id;
+    }
+
+    /// Folds a keystroke into the current step while the user keeps typing the same word.
+    fn merge(&mut self, edit: &Edit, now: u64) -> bool {
+        let node = &mut self.nodes[self.current];
+        if !self.open || !edit.is_typing() || now.saturating_sub(node.timestamp) > GROUP_TIMEOUT_MS {
+            return false;
+        }
+        let Some(last) = node.edits.last_mut() else { return false };
+        if last.deleted.is_empty() && edit.deleted.is_empty() {
+            let end = last.pos + last.inserted.len() as i32;
+            let prev = last.inserted.chars().last().unwrap_or(' ');
+            let next = edit.inserted.chars().next().unwrap_or(' ');
+            if edit.pos != end || (!is_word_char(prev) && is_word_char(next)) {
+                return false;
+            }
+            last.inserted.push_str(&edit.inserted);
+            true
+        } else if last.inserted.is_empty() && edit.inserted.is_empty() {
+            if edit.pos + edit.deleted.len() as i32 == last.pos {
+                last.deleted.insert_str(0, &edit.deleted);
+                last.pos = edit.pos;
+                true
+            } else if edit.pos == last.pos {
+                last.deleted.push_str(&edit.deleted);
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<Step> {
+        let parent = self.nodes[self.current].parent?;
+        let edits = self.nodes[self.current].edits.clone();
+        self.nodes[parent].redo_child = Some(self.current);
+        self.current = parent;
+        self.open = false;
+        Some(Step::Undo(edits))
+    }
+
+    pub fn redo(&mut self) -> Option<Step> {
+        let node = &self.nodes[self.current];
+        let child = node.redo_child.or(node.children.last().copied())?;
+        self.current = child;
+        self.open = false;
+        Some(Step::Redo(self.nodes[child].edits.clone()))
+    }
+
+    /// Steps that move the buffer from the current state to `target`, possibly across branches.
+    pub fn jump(&mut self, target: usize) -> Vec<Step> {
+        let mut steps = Vec::new();
+        if target >= self.nodes.len() {
+            return steps;
+        }
+        let mut path = vec![target];
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        while !path.contains(&self.current) {
+            match self.undo() {
+                Some(step) => steps.push(step),
+                None => break,
+            }
+        }
+        let from = path.iter().position(|&id| id == self.current).unwrap_or(path.len() - 1);
+        for pair in path[..=from].windows(2).rev() {
+            self.nodes[pair[1]].redo_child = Some(pair[0]);
+            if let Some(step) = self.redo() {
+                steps.push(step);
+            }
+        }
+        steps
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.current);
+        self.open = false;
+    }
+
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current)
+    }
+
+    /// Node ids with their depth in the tree and a one-line description, in display order.
+    pub fn entries(&self) -> Vec<(usize, usize, String)> {
+        let now = now_ms();
+        let mut entries = Vec::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.nodes[id];
+            let marker = if id == self.current { ">" } else { " " };
+            let saved = if self.saved == Some(id) { "  (saved)" } else { "" };
+            let what = match node.edits.first() {
+                None => "Original".to_string(),
+                Some(edit) => edit.summary(),
+            };
+            let age = history::format_age(now.saturating_sub(node.timestamp) / 1000);
+            entries.push((id, depth, format!("{} #{}  {}  {}{}", marker, id, age, what, saved)));
+            for &child in node.children.iter().rev() {
+                stack.push((child, depth + 1));
+            }
+        }
+        entries
+    }
+
+    /// Writes the tree for `path`, tagged with the hash of `text` so it is only
+    /// restored when the file still has the same contents.
+    pub fn save(&self, path: &str, text: &str) -> io::Result<()> {
+        file_io::write_atomic(&undo_path_for(path), &self.encode(text))
+    }
+
+    /// Restores the saved tree for `path` if it was written for the same `text`.
+    pub fn load(path: &str, text: &str) -> Option<Self> {
+        Self::decode(&fs::read(undo_path_for(path)).ok()?, text)
+    }
+
+    fn encode(&self, text: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        let opt = |v: Option<usize>| v.map(|v| v.to_string()).unwrap_or("-".into());
+        out.extend(
+            format!(
+                "skrift-undo 1 {:016x} {} {}\n",
+                file_io::fnv1a(text.as_bytes()),
+                self.current,
+                opt(self.saved)
+            )
+            .bytes(),
+        );
+        for node in &self.nodes {
+            out.extend(
+                format!(
+                    "n {} {} {} {}\n",
+                    opt(node.parent),
+                    opt(node.redo_child),
+                    node.timestamp,
+                    node.edits.len()
+                )
+                .bytes(),
+            );
+            for edit in &node.edits {
+                out.extend(format!("e {} {} {}\n", edit.pos, edit.deleted.len(), edit.inserted.len()).bytes());
+                out.extend(edit.deleted.bytes());
+                out.extend(edit.inserted.bytes());
+                out.push(b'\n');
+            }
+        }
+        out
+    }
+
+    fn decode(data: &[u8], text: &str) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let header = reader.line()?;
+        let header: Vec<&str> = header.split(' ').collect();
+        if header.len() != 5 || header[0] != "skrift-undo" || header[1] != "1" {
+            return None;
+        }
+        if u64::from_str_radix(header[2], 16).ok()? != file_io::fnv1a(text.as_bytes()) {
+            return None;
+        }
+        let current: usize = header[3].parse().ok()?;
+        let saved = header[4].parse().ok();
+
+        let mut nodes: Vec<Node> = Vec::new();
+        while !reader.done() {
+            let line = reader.line()?;
+            let fields: Vec<&str> = line.split(' ').collect();
+            if fields.len() != 5 || fields[0] != "n" {
+                return None;
+            }
+            let count: usize = fields[4].parse().ok()?;
+            let mut edits = Vec::with_capacity(count);
+            for _ in 0..count {
+                let line = reader.line()?;
+                let fields: Vec<&str> = line.split(' ').collect();
+                if fields.len() != 4 || fields[0] != "e" {
+                    return None;
+                }
+                let deleted = reader.text(fields[2].parse().ok()?)?;
+                let inserted = reader.text(fields[3].parse().ok()?)?;
+                reader.line()?;
+                edits.push(Edit { pos: fields[1].parse().ok()?, deleted, inserted });
+            }
+            nodes.push(Node {
+                parent: fields[1].parse().ok(),
+                children: Vec::new(),
+                redo_child: fields[2].parse().ok(),
+                edits,
+                timestamp: fields[3].parse().ok()?,
+            });
+        }
+        for id in 1..nodes.len() {
+            let parent = nodes[id].parent.filter(|&p| p < id)?;
+            nodes[parent].children.push(id);
+        }
+        if current >= nodes.len() {
+            return None;
+        }
+        Some(UndoTree { nodes, current, saved, open: false, applying: false })
+    }
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn undo_path_for(path: &str) -> PathBuf {
+    file_io::data_dir("undo").join(format!("{}.undo", file_io::path_key(path)))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn done(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn line(&mut self) -> Option<String> {
+        let rest = self.data.get(self.pos..)?;
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        self.pos += end + 1;
+        String::from_utf8(rest[..end].to_vec()).ok()
+    }
+
+    fn text(&mut self, len: usize) -> Option<String> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(pos: i32, deleted: &str, inserted: &str) -> Edit {
+        Edit { pos, deleted: deleted.to_string(), inserted: inserted.to_string() }
+    }
+
+    /// Records `edit` and makes it in `text`, as the buffer would.
+    fn make(tree: &mut UndoTree, text: &mut String, edit: Edit) {
+        let pos = edit.pos as usize;
+        text.replace_range(pos..pos + edit.deleted.len(), &edit.inserted);
+        tree.record(edit);
+    }
+
+    fn type_text(tree: &mut UndoTree, text: &mut String, at: usize, typed: &str) {
+        for (i, c) in typed.char_indices() {
+            make(tree, text, edit((at + i) as i32, "", &c.to_string()));
+        }
+    }
+
+    fn apply(text: &mut String, step: Option<Step>) {
+        match step.expect("a step to take") {
+            Step::Undo(edits) => {
+                for edit in edits.iter().rev() {
+                    let pos = edit.pos as usize;
+                    text.replace_range(pos..pos + edit.inserted.len(), &edit.deleted);
+                }
+            }
+            Step::Redo(edits) => {
+                for edit in &edits {
+                    let pos = edit.pos as usize;
+                    text.replace_range(pos..pos + edit.deleted.len(), &edit.inserted);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn typing_groups_by_word() {
+        let (mut tree, mut text) = (UndoTree::new(), String::new());
+        type_text(&mut tree, &mut text, 0, "hello world");
+        assert_eq!(text, "hello world");
+        apply(&mut text, tree.undo());
+        assert_eq!(text, "hello ");
+        apply(&mut text, tree.undo());
+        assert_eq!(text, "");
+        assert!(tree.undo().is_none());
+        apply(&mut text, tree.redo());
+        apply(&mut text, tree.redo());
+        assert_eq!(text, "hello world");
+        assert!(tree.redo().is_none());
+    }
+
+    #[test]
+    fn grouping_boundaries() {
+        let (mut tree, mut text) = (UndoTree::new(), String::new());
+        type_text(&mut tree, &mut text, 0, "abc");
+        // Typing somewhere else starts a new step.
+        type_text(&mut tree, &mut text, 0, "x");
+        // So does a paste, and typing right after it.
+        make(&mut tree, &mut text, edit(4, "", "pasted"));
+        type_text(&mut tree, &mut text, 10, "d");
+        assert_eq!(text, "xabcpastedd");
+        // Backspaces and forward deletes each fold into one step.
+        make(&mut tree, &mut text, edit(10, "d", ""));
+        make(&mut tree, &mut text, edit(9, "d", ""));
+        make(&mut tree, &mut text, edit(0, "x", ""));
+        make(&mut tree, &mut text, edit(0, "a", ""));
+        assert_eq!(text, "bcpaste");
+
+        let mut states = vec![text.clone()];
+        while let Some(step) = tree.undo() {
+            apply(&mut text, Some(step));
+            states.push(text.clone());
+        }
+        assert_eq!(states, ["bcpaste", "xabcpaste", "xabcpastedd", "xabcpasted", "xabc", "abc", ""]);
+    }
+
+    #[test]
+    fn saving_closes_the_open_step() {
+        let (mut tree, mut text) = (UndoTree::new(), String::new());
+        type_text(&mut tree, &mut text, 0, "ab");
+        tree.mark_saved();
+        assert!(tree.is_saved());
+        type_text(&mut tree, &mut text, 2, "c");
+        assert!(!tree.is_saved());
+        apply(&mut text, tree.undo());
+        assert_eq!(text, "ab");
+        assert!(tree.is_saved());
+    }
+
+    #[test]
+    fn undo_and_redo_across_branches() {
+        let (mut tree, mut text) = (UndoTree::new(), String::new());
+        make(&mut tree, &mut text, edit(0, "", "one"));
+        make(&mut tree, &mut text, edit(3, "", " two"));
+        apply(&mut text, tree.undo());
+        make(&mut tree, &mut text, edit(3, "", " three"));
+        assert_eq!(text, "one three");
+
+        // Redo follows the branch that was undone last.
+        apply(&mut text, tree.undo());
+        apply(&mut text, tree.redo());
+        assert_eq!(text, "one three");
+
+        // Jumping to the other branch undoes this one first.
+        let two = tree.entries().iter().find(|(_, _, line)| line.contains("two")).map(|e| e.0).unwrap();
+        for step in tree.jump(two) {
+            apply(&mut text, Some(step));
+        }
+        assert_eq!(text, "one two");
+        apply(&mut text, tree.undo());
+        apply(&mut text, tree.redo());
+        assert_eq!(text, "one two", "redo now follows the branch jumped to");
+        for step in tree.jump(0) {
+            apply(&mut text, Some(step));
+        }
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let (mut tree, mut text) = (UndoTree::new(), String::new());
+        type_text(&mut tree, &mut text, 0, "héllo\n");
+        make(&mut tree, &mut text, edit(0, "h", "J"));
+        apply(&mut text, tree.undo());
+        make(&mut tree, &mut text, edit(0, "h", "y"));
+        tree.mark_saved();
+
+        let data = tree.encode(&text);
+        assert!(UndoTree::decode(&data, "other text").is_none(), "only restored for the same text");
+        assert!(UndoTree::decode(&data[..data.len() - 3], &text).is_none());
+        let mut loaded = UndoTree::decode(&data, &text).unwrap();
+        assert!(loaded.is_saved());
+        assert_eq!(loaded.entries().len(), tree.entries().len());
+
+        apply(&mut text, loaded.undo());
+        assert_eq!(text, "héllo\n");
+        apply(&mut text, loaded.redo());
+        assert_eq!(text, "yéllo\n");
+        let j = loaded.entries().iter().find(|(_, _, line)| line.contains("+\"J\"")).map(|e| e.0).unwrap();
+        for step in loaded.jump(j) {
+            apply(&mut text, Some(step));
+        }
+        assert_eq!(text, "Jéllo\n");
+        for step in loaded.jump(0) {
+            apply(&mut text, Some(step));
+        }
+        assert_eq!(text, "");
+    }
+}
@@ -0,0 +1,85 @@
+use fltk::{draw, prelude::*, text::TextEditor};
+
+use crate::line_index::LineIndex;
+
+/// Text in a `TextEditor` starts this far below its top edge, and ends this far above
+/// its bottom edge.
+const TEXT_MARGIN: i32 = 1;
+/// Text in a `TextEditor` starts this far right of its left edge, and ends this far left
+/// of its right edge.
+const SIDE_MARGIN: i32 = 3;
+
+/// The height of one line of text, as the editor lays it out.
+pub fn line_height(editor: &TextEditor) -> i32 {
+    draw::set_font(editor.text_font(), editor.text_size());
+    draw::height().max(1)
+}
+
+/// How many whole lines fit in the editor.
+pub fn visible_lines(editor: &TextEditor) -> i32 {
+    ((editor.h() - TEXT_MARGIN * 2) / line_height(editor)).max(1)
+}
+
+/// The highest first line that still fills the editor, for a buffer of `total_lines`.
+pub fn max_top(editor: &TextEditor, total_lines: i32) -> i32 {
+    (total_lines - visible_lines(editor)).max(0)
+}
+
+/// The width of the area the editor draws text in.
+pub fn text_width(editor: &TextEditor) -> i32 {
+    (editor.w() - SIDE_MARGIN * 2).max(1)
+}
+
+/// Scrolls so that the zero-based `top` is the first line shown, with the text shifted
+/// `left` pixels to the left. `TextEditor::scroll` counts lines from one.
+pub fn scroll_to(editor: &mut TextEditor, top: i32, left: i32) {
+    editor.scroll(top + 1, left.max(0));
+}
+
+/// How far the text is scrolled to the left, and the width of the widest line on screen,
+/// both in pixels, when `top` is the first line shown. The editor only knows how long the
+/// lines it shows are, so the width can change as it scrolls up and down.
+pub fn horizontal_extent(editor: &TextEditor, line_index: &LineIndex, top: i32) -> Option<(i32, i32)> {
+    let last = line_index.line_count() - 1;
+    let (start_x, y) = editor.position_to_xy(line_index.line_start(top.min(last)));
+    if y == 0 {
+        return None;
+    }
+    let widest = (top..=(top + visible_lines(editor)).min(last))
+        .map(|line| editor.position_to_xy(line_index.line_end(line)).0 - start_x)
+        .max()
+        .unwrap_or(0);
+    Some((editor.x() + SIDE_MARGIN - start_x, widest))
+}
+
+/// The zero-based first line `editor` shows, read from where it lays out its text, so it
+/// is right however the editor got there. Only valid once the editor has drawn since it
+/// last scrolled; `hint` is where to start looking, usually the last known first line.
+pub fn first_visible_line(editor: &TextEditor, line_index: &LineIndex, hint: i32) -> Option<i32> {
+    let line_height = line_height(editor);
+    let top_through = |line: i32| -> Option<i32> {
+        let (_, y) = editor.position_to_xy(line_index.line_start(line));
+        // Positions that aren't on screen come back as (0, 0).
+        (y != 0).then(|| line - (y - editor.y() - TEXT_MARGIN) / line_height)
+    };
+
+    let last = line_index.line_count() - 1;
+    let hint = hint.clamp(0, last.max(0));
+    if let Some(top) = top_through(line_index.line_of(editor.insert_position())).or_else(|| top_through(hint)) {
+        return Some(top.max(0));
+    }
+    // Probe outwards from the hint a screenful at a time, so one probe lands on screen.
+    let step = visible_lines(editor);
+    let mut distance = step;
+    while hint - distance >= 0 || hint + distance <= last {
+        for line in [hint + distance, hint - distance] {
+            if (0..=last).contains(&line)
+                && let Some(top) = top_through(line)
+            {
+                return Some(top.max(0));
+            }
+        }
+        distance += step;
+    }
+    None
+}
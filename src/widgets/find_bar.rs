@@ -0,0 +1,165 @@
+use crate::config::Theme;
+use crate::search::SearchOptions;
+use fltk::{
+    button::{Button, ToggleButton},
+    enums::{Align, CallbackTrigger, Font, FrameType},
+    frame::Frame,
+    group::Group,
+    input::Input,
+    prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindOption {
+    Regex,
+    CaseSensitive,
+    WholeWord,
+    InSelection,
+}
+
+pub struct FindBar {
+    pub group: Group,
+    pub find: Input,
+    pub replace: Input,
+    regex: ToggleButton,
+    case_sensitive: ToggleButton,
+    whole_word: ToggleButton,
+    in_selection: ToggleButton,
+    count: Frame,
+    pub prev: Button,
+    pub next: Button,
+    pub replace_one: Button,
+    pub replace_all: Button,
+    pub close: Button,
+}
+
+impl FindBar {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        let mut group = Group::new(x, y, w, h, "");
+        group.set_frame(FrameType::FlatBox);
+        let pad = 4;
+        let ih = h - pad * 2;
+        let iy = y + pad;
+
+        // Fixed-width controls are laid out from the right edge; the two inputs share the rest.
+        let mut right = x + w;
+        let mut take = |width: i32| {
+            right -= width + pad;
+            right
+        };
+        let close = Button::new(take(24), iy, 24, ih, "x");
+        let replace_all = Button::new(take(40), iy, 40, ih, "All");
+        let replace_one = Button::new(take(64), iy, 64, ih, "Replace");
+        let next = Button::new(take(24), iy, 24, ih, "@>");
+        let prev = Button::new(take(24), iy, 24, ih, "@<");
+        let mut count = Frame::new(take(96), iy, 96, ih, "");
+        count.set_align(Align::Inside | Align::Center);
+        let in_selection = ToggleButton::new(take(32), iy, 32, ih, "Sel");
+        let whole_word = ToggleButton::new(take(28), iy, 28, ih, "W");
+        let case_sensitive = ToggleButton::new(take(32), iy, 32, ih, "Aa");
+        let regex = ToggleButton::new(take(28), iy, 28, ih, ".*");
+
+        let inputs = right - x - pad * 2;
+        let find_w = inputs * 3 / 5;
+        let mut find = Input::new(x, iy, find_w, ih, "");
+        find.set_trigger(CallbackTrigger::Changed);
+        let replace = Input::new(x + find_w + pad, iy, inputs - find_w, ih, "");
+
+        group.resizable(&find);
+        group.end();
+        group.hide();
+
+        FindBar {
+            group,
+            find,
+            replace,
+            regex,
+            case_sensitive,
+            whole_word,
+            in_selection,
+            count,
+            prev,
+            next,
+            replace_one,
+            replace_all,
+            close,
+        }
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        SearchOptions {
+            regex: self.regex.value(),
+            case_sensitive: self.case_sensitive.value(),
+            whole_word: self.whole_word.value(),
+            in_selection: self.in_selection.value(),
+        }
+    }
+
+    fn option_button(&mut self, option: FindOption) -> &mut ToggleButton {
+        match option {
+            FindOption::Regex => &mut self.regex,
+            FindOption::CaseSensitive => &mut self.case_sensitive,
+            FindOption::WholeWord => &mut self.whole_word,
+            FindOption::InSelection => &mut self.in_selection,
+        }
+    }
+
+    pub fn toggle(&mut self, option: FindOption) {
+        let button = self.option_button(option);
+        button.set_value(!button.value());
+    }
+
+    /// Runs `cb` with the option that was clicked.
+    pub fn set_on_option<F: Fn(FindOption) + Clone + 'static>(&mut self, cb: F) {
+        for option in [FindOption::Regex, FindOption::CaseSensitive, FindOption::WholeWord, FindOption::InSelection] {
+            let cb = cb.clone();
+            self.option_button(option).set_callback(move |_| cb(option));
+        }
+    }
+
+    pub fn set_count(&mut self, label: &str) {
+        self.count.set_label(label);
+    }
+
+    pub fn apply_theme(&mut self, theme: &Theme) {
+        let background = theme.color_from_str(&theme.background);
+        let foreground = theme.color_from_str(&theme.foreground);
+        let selection = theme.color_from_str(&theme.selection_color);
+        let font = Font::by_name(&theme.font_family);
+        let size = (theme.font_size - 2).max(8);
+
+        self.group.set_color(background);
+        for input in [&mut self.find, &mut self.replace] {
+            input.set_color(background.lighter());
+            input.set_text_color(foreground);
+            input.set_cursor_color(foreground);
+            input.set_selection_color(selection);
+            input.set_text_font(font);
+            input.set_text_size(size);
+            input.set_frame(FrameType::FlatBox);
+        }
+        self.count.set_label_color(foreground);
+        self.count.set_label_size(size);
+        for button in [&mut self.regex, &mut self.case_sensitive, &mut self.whole_word, &mut self.in_selection] {
+            button.set_color(background.lighter());
+            button.set_selection_color(selection);
+            button.set_label_color(foreground);
+            button.set_label_size(size);
+            button.set_frame(FrameType::FlatBox);
+            button.set_down_frame(FrameType::FlatBox);
+        }
+        for button in [
+            &mut self.prev,
+            &mut self.next,
+            &mut self.replace_one,
+            &mut self.replace_all,
+            &mut self.close,
+        ] {
+            button.set_color(background.lighter());
+            button.set_label_color(foreground);
+            button.set_label_size(size);
+            button.set_frame(FrameType::FlatBox);
+        }
+        self.group.redraw();
+    }
+}
@@ -0,0 +1,188 @@
+use fltk::{app, draw, enums::{Align, Color, Event, Font}, prelude::*, text::TextEditor, widget::Widget};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::config::LineNumbers;
+
+/// Space on either side of the numbers.
+const GUTTER_PAD: i32 = 6;
+/// Text in a `TextEditor` starts this far below its top edge.
+const EDITOR_TOP_MARGIN: i32 = 1;
+
+/// The part of a buffer the gutter numbers: the first visible line, the number of
+/// lines and the cursor's line, all zero-based.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GutterView {
+    pub top: i32,
+    pub lines: i32,
+    pub current: i32,
+}
+
+struct State {
+    view: GutterView,
+    mode: LineNumbers,
+    background: Color,
+    foreground: Color,
+    current: Color,
+    font: Font,
+    size: i32,
+    /// The line a click started on, while dragging out a selection.
+    anchor: i32,
+    on_select: Option<Rc<dyn Fn(i32, i32)>>,
+}
+
+impl State {
+    fn line_height(&self) -> i32 {
+        draw::set_font(self.font, self.size);
+        draw::height().max(1)
+    }
+
+    /// The line drawn at window y coordinate `y` in a gutter whose top is `top_y`.
+    fn line_at(&self, top_y: i32, y: i32) -> i32 {
+        let row = (y - top_y - EDITOR_TOP_MARGIN).max(0) / self.line_height();
+        (self.view.top + row).min(self.view.lines - 1).max(0)
+    }
+
+    fn label(&self, line: i32) -> String {
+        let distance = (line - self.view.current).abs();
+        match self.mode {
+            LineNumbers::Absolute => line + 1,
+            LineNumbers::Relative => distance,
+            LineNumbers::Hybrid if distance == 0 => line + 1,
+            LineNumbers::Hybrid => distance,
+        }
+        .to_string()
+    }
+}
+
+/// Line numbers beside an editor, in rows that line up with the editor's. Clicking a
+/// number selects its line and dragging extends the selection by whole lines.
+#[derive(Clone)]
+pub struct Gutter {
+    pub widget: Widget,
+    state: Rc<RefCell<State>>,
+}
+
+impl Gutter {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        let mut widget = Widget::new(x, y, w, h, "");
+        let state = Rc::new(RefCell::new(State {
+            view: GutterView::default(),
+            mode: LineNumbers::Absolute,
+            background: Color::from_u32(0x1e1e1e),
+            foreground: Color::from_u32(0x5c6370),
+            current: Color::from_u32(0xc0c0c0),
+            font: Font::Courier,
+            size: 16,
+            anchor: 0,
+            on_select: None,
+        }));
+
+        widget.draw({
+            let state = state.clone();
+            move |wgt| {
+                let state = state.borrow();
+                draw::set_draw_color(state.background);
+                draw::draw_rectf(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                draw::push_clip(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                let line_height = state.line_height();
+                let mut y = wgt.y() + EDITOR_TOP_MARGIN;
+                let mut line = state.view.top;
+                while y < wgt.y() + wgt.h() && line < state.view.lines {
+                    let color = if line == state.view.current { state.current } else { state.foreground };
+                    draw::set_draw_color(color);
+                    draw::draw_text2(&state.label(line), wgt.x(), y, wgt.w() - GUTTER_PAD, line_height, Align::Right);
+                    y += line_height;
+                    line += 1;
+                }
+                draw::pop_clip();
+            }
+        });
+
+        widget.handle({
+            let state = state.clone();
+            move |wgt, ev| {
+                let (from, to) = match ev {
+                    Event::Push if app::event_mouse_button() == app::MouseButton::Left => {
+                        let mut state = state.borrow_mut();
+                        state.anchor = state.line_at(wgt.y(), app::event_y());
+                        (state.anchor, state.anchor)
+                    }
+                    Event::Drag => {
+                        let state = state.borrow();
+                        (state.anchor, state.line_at(wgt.y(), app::event_y()))
+                    }
+                    Event::Released => return true,
+                    _ => return false,
+                };
+                // Cloned out so the callback can move focus and restyle the gutter.
+                let callback = state.borrow().on_select.clone();
+                if let Some(cb) = callback {
+                    cb(from, to);
+                }
+                true
+            }
+        });
+
+        Gutter { widget, state }
+    }
+
+    pub fn apply_theme(&mut self, theme: &crate::config::Theme, mode: LineNumbers) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.background = theme.color_from_str(&theme.background);
+            state.foreground = theme.color_from_str(&theme.line_number_color);
+            state.current = theme.color_from_str(&theme.current_line_number_color);
+            state.font = Font::by_name(&theme.font_family);
+            state.size = theme.font_size;
+            state.mode = mode;
+        }
+        self.widget.redraw();
+    }
+
+    pub fn view(&self) -> GutterView {
+        self.state.borrow().view
+    }
+
+    pub fn set_view(&mut self, view: GutterView) {
+        if self.state.borrow().view == view {
+            return;
+        }
+        self.state.borrow_mut().view = view;
+        self.widget.redraw();
+    }
+
+    pub fn set_top(&mut self, top: i32) {
+        let view = GutterView { top, ..self.view() };
+        self.set_view(view);
+    }
+
+    /// The width that fits the widest line number, with room for at least three digits.
+    pub fn fitting_width(&self) -> i32 {
+        let state = self.state.borrow();
+        let digits = state.view.lines.max(1).to_string().len().max(3);
+        draw::set_font(state.font, state.size);
+        draw::width(&"0".repeat(digits)) as i32 + GUTTER_PAD * 2
+    }
+
+    /// Resizes the gutter to its fitting width, moving the left edge of `editor`, which
+    /// sits right of it, to match.
+    pub fn fit(&mut self, editor: &mut TextEditor) {
+        let width = self.fitting_width();
+        let grow = width - self.widget.w();
+        if grow == 0 {
+            return;
+        }
+        self.widget.resize(self.widget.x(), self.widget.y(), width, self.widget.h());
+        editor.resize(editor.x() + grow, editor.y(), editor.w() - grow, editor.h());
+        if let Some(mut parent) = self.widget.parent() {
+            parent.redraw();
+        }
+    }
+
+    /// Calls `cb` with the line a click started on and the line under the mouse.
+    pub fn set_on_select<F: Fn(i32, i32) + 'static>(&mut self, cb: F) {
+        self.state.borrow_mut().on_select = Some(Rc::new(cb));
+    }
+}
+
@@ -0,0 +1,66 @@
+use crate::config::{Binding, Theme};
+use fltk::{
+    draw,
+    enums::{Align, Font, FrameType},
+    frame::Frame,
+    prelude::*,
+};
+
+const PAD: i32 = 8;
+
+/// A small box listing the keys that can follow a half-typed chord and what they run.
+pub struct KeyHint {
+    pub frame: Frame,
+}
+
+impl KeyHint {
+    pub fn new() -> Self {
+        let mut frame = Frame::new(0, 0, 0, 0, "");
+        frame.set_frame(FrameType::FlatBox);
+        frame.set_align(Align::Inside | Align::Left | Align::Top);
+        frame.hide();
+        KeyHint { frame }
+    }
+
+    pub fn apply_theme(&mut self, theme: &Theme) {
+        self.frame.set_color(theme.color_from_str(&theme.background).lighter());
+        self.frame.set_label_color(theme.color_from_str(&theme.foreground));
+        self.frame.set_label_font(Font::by_name(&theme.font_family));
+        self.frame.set_label_size((theme.font_size - 2).max(8));
+    }
+
+    /// Shows the keys that can follow `prefix` in the bottom right corner of `area`.
+    pub fn show_hints(&mut self, prefix: &str, continuations: &[(String, Binding)], (x, y, w, h): (i32, i32, i32, i32)) {
+        let key_width = continuations.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+        let mut label = format!("{} ...", prefix);
+        for (keys, binding) in continuations {
+            label.push_str(&format!("\n{:<width$}  {}", keys, binding.name(), width = key_width));
+        }
+        // Keep '@' from being read as a symbol.
+        self.frame.set_label(&label.replace('@', "@@"));
+
+        draw::set_font(self.frame.label_font(), self.frame.label_size());
+        let (text_w, text_h) = draw::measure(&label, false);
+        let (hint_w, hint_h) = ((text_w + PAD * 2).min(w), (text_h + PAD * 2).min(h));
+        self.frame.resize(x + w - hint_w - PAD, y + h - hint_h - PAD, hint_w, hint_h);
+        // Panes added since start up would draw over it, so put it last in its window.
+        if let Some(mut parent) = self.frame.parent() {
+            parent.remove(&self.frame);
+            parent.add(&self.frame);
+        }
+        self.frame.show();
+        if let Some(mut window) = self.frame.window() {
+            window.redraw();
+        }
+    }
+
+    pub fn hide(&mut self) {
+        if !self.frame.visible() {
+            return;
+        }
+        self.frame.hide();
+        if let Some(mut window) = self.frame.window() {
+            window.redraw();
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use fltk::{draw, enums::Color, prelude::*, text::TextEditor};
+
+/// Every line is this many pixels tall.
+const LINE_PX: i32 = 2;
+/// Every character is this many pixels wide.
+const CHAR_PX: i32 = 1;
+
+/// A scaled-down rendering of an editor's buffer, coloured like its text, with the lines
+/// the editor shows picked out. When the buffer is too long to fit, the part drawn
+/// follows the editor's scroll position.
+pub struct Minimap {
+    editor: TextEditor,
+    foreground: Color,
+    view_color: Color,
+    /// The colour of every style in the editor's style buffer, from style `'A'` on.
+    palette: Vec<Color>,
+}
+
+impl Minimap {
+    pub fn new(editor: &TextEditor) -> Self {
+        Minimap {
+            editor: editor.clone(),
+            foreground: Color::from_u32(0xc0c0c0),
+            view_color: Color::from_u32(0x2c313a),
+            palette: Vec::new(),
+        }
+    }
+
+    pub fn set_colors(&mut self, foreground: Color, view_color: Color, palette: Vec<Color>) {
+        self.foreground = foreground;
+        self.view_color = view_color;
+        self.palette = palette;
+    }
+
+    /// The first line drawn in a minimap `h` pixels tall, when the editor shows `page`
+    /// lines from `top` out of `total`.
+    fn first_line(h: i32, top: i32, page: i32, total: i32) -> i32 {
+        let rows = h / LINE_PX;
+        if total <= rows {
+            return 0;
+        }
+        let max_first = total - rows;
+        let max_top = (total - page).max(1);
+        (top as i64 * max_first as i64 / max_top as i64).clamp(0, max_first as i64) as i32
+    }
+
+    /// The line at `y` pixels down a minimap `h` pixels tall.
+    pub fn line_at(y: i32, h: i32, top: i32, page: i32, total: i32) -> i32 {
+        (Self::first_line(h, top, page, total) + y.max(0) / LINE_PX).min(total - 1).max(0)
+    }
+
+    pub fn draw(&self, (x, y, w, h): (i32, i32, i32, i32), top: i32, page: i32, total: i32) {
+        let first = Self::first_line(h, top, page, total);
+        draw::set_draw_color(self.view_color);
+        draw::draw_rectf(x, y + (top - first) * LINE_PX, w, page * LINE_PX);
+
+        let Some(buf) = self.editor.buffer() else { return };
+        let rows = (h / LINE_PX).min(total - first).max(0);
+        let start = self.editor.clone().skip_lines(0, first, true);
+        let end = self.editor.clone().skip_lines(start, rows, true);
+        let text = buf.text_range(start, end).unwrap_or_default();
+        // Styles only line up while the style buffer covers the whole text.
+        let styles = self
+            .editor
+            .style_buffer()
+            .filter(|s| s.length() == buf.length())
+            .and_then(|s| s.text_range(start, end))
+            .unwrap_or_default()
+            .into_bytes();
+        let tab = buf.tab_distance().max(1);
+
+        draw::push_clip(x, y, w, h);
+        let mut offset = 0;
+        for (row, line) in text.split('\n').take(rows as usize).enumerate() {
+            let line_y = y + row as i32 * LINE_PX;
+            let mut column = 0;
+            for (i, c) in line.char_indices() {
+                if column * CHAR_PX >= w {
+                    break;
+                }
+                if c == '\t' {
+                    column += tab - column % tab;
+                    continue;
+                }
+                if !c.is_whitespace() {
+                    let color = styles
+                        .get(offset + i)
+                        .and_then(|&s| self.palette.get(s.wrapping_sub(b'A') as usize))
+                        .copied()
+                        .unwrap_or(self.foreground);
+                    draw::set_draw_color(color);
+                    draw::draw_rectf(x + column * CHAR_PX, line_y, CHAR_PX, LINE_PX - 1);
+                }
+                column += 1;
+            }
+            offset += line.len() + 1;
+        }
+        draw::pop_clip();
+    }
+}
@@ -1,155 +1,522 @@
-use fltk::{app, prelude::*, widget::Widget, enums::Color, draw};
+use fltk::{app, prelude::*, widget::Widget, enums::{Color, Event}, draw};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::widgets::minimap::Minimap;
+
+/// The thumb never gets shorter than this, so it stays easy to grab.
+const MIN_THUMB: i32 = 20;
+/// How long an auto-hiding scrollbar stays visible after it last moved.
+const REVEAL_FOR: Duration = Duration::from_millis(1000);
+/// How wide a vertical scrollbar is while it draws a minimap.
+pub const MINIMAP_WIDTH: i32 = 96;
+/// Markers are at least this long, however little of the range they cover.
+const MIN_MARKER: i32 = 2;
+/// How close to a marker a click has to be to jump to it.
+const MARKER_SLOP: i32 = 2;
+/// How wide the strip down the edge of a minimap that markers are drawn in is.
+const MINIMAP_MARKER_STRIP: i32 = 6;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ScrollBarStyle {
     Boxy,
     Rounded,
+    /// A minimap of the buffer in place of the track and thumb. Horizontal scrollbars
+    /// draw as `Rounded`.
+    Minimap,
 }
 
-pub struct ScrollBar {
-    pub widget: Widget,
-    pub min: i32,
-    pub max: i32,
-    pub value: i32,
-    pub bg_color: Color,
-    pub thumb_color: Color,
-    pub style: ScrollBarStyle,
+/// What a marker stands for. Each kind is drawn in its own lane across the scrollbar:
+/// changes on the left, search matches and bookmarks in the middle, errors on the right.
+// Only search matches are marked so far; the other kinds are there for whatever tracks
+// changes, bookmarks and errors to push.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum MarkerKind {
+    Change,
+    SearchMatch,
+    Bookmark,
+    Error,
+}
+
+impl MarkerKind {
+    fn lane(self) -> i32 {
+        match self {
+            MarkerKind::Change => 0,
+            MarkerKind::SearchMatch | MarkerKind::Bookmark => 1,
+            MarkerKind::Error => 2,
+        }
+    }
+}
+
+/// A range of values, lines for a vertical scrollbar, picked out with a tick in the track.
+/// `end` is exclusive.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub start: i32,
+    pub end: i32,
+    pub color: Color,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Orientation {
+    Vertical,
+    Horizontal,
+}
+
+struct State {
+    min: i32,
+    max: i32,
+    value: i32,
+    /// How much of the range is in view at once.
+    page: i32,
+    bg_color: Color,
+    thumb_color: Color,
+    hover_color: Color,
+    active_color: Color,
+    style: ScrollBarStyle,
+    orientation: Orientation,
+    /// The width, or height if horizontal, the scrollbar was made with.
+    thickness: i32,
+    minimap: Option<Minimap>,
+    /// Kept in kind order, then by where they start, so later kinds draw on top.
+    markers: Vec<Marker>,
+    auto_hide: bool,
+    hovered: bool,
     dragging: bool,
     drag_offset: i32,
-    pub on_change: Option<Box<dyn Fn(i32)>>,
+    /// When the value last changed, so an auto-hiding scrollbar shows while scrolling.
+    moved: Option<Instant>,
+    on_change: Option<Rc<dyn Fn(i32)>>,
+}
+
+impl State {
+    fn shows_minimap(&self) -> bool {
+        self.style == ScrollBarStyle::Minimap && self.orientation == Orientation::Vertical && self.minimap.is_some()
+    }
+
+    /// Everything there is to scroll through, counting the part in view.
+    fn total(&self) -> i32 {
+        self.max - self.min + self.page.max(1)
+    }
+
+    /// The track length along the scrollbar, and the position along it of window
+    /// coordinates `x`, `y`.
+    fn along(&self, wgt: &Widget, x: i32, y: i32) -> (i32, i32) {
+        match self.orientation {
+            Orientation::Vertical => (wgt.h(), y - wgt.y()),
+            Orientation::Horizontal => (wgt.w(), x - wgt.x()),
+        }
+    }
+
+    /// The thumb's offset along the track and its length, with the thumb as long
+    /// relative to the track as the page is to everything there is to scroll through.
+    fn thumb(&self, track: i32) -> (i32, i32) {
+        let range = (self.max - self.min).max(0);
+        let page = self.page.max(1);
+        let size = if range == 0 {
+            track
+        } else {
+            (track as i64 * page as i64 / (range + page) as i64) as i32
+        };
+        let size = size.max(MIN_THUMB).min(track);
+        let offset = if range == 0 {
+            0
+        } else {
+            ((track - size) as f32 * (self.value - self.min) as f32 / range as f32) as i32
+        };
+        (offset, size)
+    }
+
+    /// Whether to draw the thumb: not when there is nothing to scroll, and with auto-hide
+    /// only while the pointer is over the scrollbar or it has just moved.
+    fn thumb_visible(&self) -> bool {
+        self.max > self.min
+            && (!self.auto_hide
+                || self.hovered
+                || self.dragging
+                || self.moved.is_some_and(|at| at.elapsed() < REVEAL_FOR))
+    }
+
+    /// Where `marker` starts along the track and how long it is.
+    fn marker_span(&self, track: i32, marker: &Marker) -> (i32, i32) {
+        let total = self.total().max(1) as i64;
+        let at = |value: i32| ((value - self.min).clamp(0, total as i32) as i64 * track as i64 / total) as i32;
+        let start = at(marker.start).min(track - MIN_MARKER);
+        (start, (at(marker.end) - start).max(MIN_MARKER))
+    }
+
+    /// Where the marker nearest `pos` along the track starts, if a click there is on one.
+    fn marker_at(&self, track: i32, pos: i32) -> Option<i32> {
+        self.markers
+            .iter()
+            .map(|marker| {
+                let (start, size) = self.marker_span(track, marker);
+                let distance = (start - pos).max(pos - (start + size)).max(0);
+                (distance, marker.start)
+            })
+            .filter(|&(distance, _)| distance <= MARKER_SLOP)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, start)| start)
+    }
+
+    /// Whether the pointer is across from the band markers are drawn in.
+    fn in_marker_band(&self, wgt: &Widget) -> bool {
+        let (breadth, across) = match self.orientation {
+            Orientation::Vertical => (wgt.w(), app::event_x() - wgt.x()),
+            Orientation::Horizontal => (wgt.h(), app::event_y() - wgt.y()),
+        };
+        let (band, band_breadth) = self.marker_band(breadth);
+        across >= band && across < band + band_breadth
+    }
+
+    /// The offset across the scrollbar and the breadth of the band markers are drawn in.
+    fn marker_band(&self, breadth: i32) -> (i32, i32) {
+        if self.shows_minimap() {
+            let strip = MINIMAP_MARKER_STRIP.min(breadth);
+            (breadth - strip, strip)
+        } else {
+            (0, breadth)
+        }
+    }
+
+    fn draw_thumb(&self, wgt: &Widget, track: i32) {
+        let (offset, size) = self.thumb(track);
+        let (x, y, w, h) = match self.orientation {
+            Orientation::Vertical => (wgt.x(), wgt.y() + offset, wgt.w(), size),
+            Orientation::Horizontal => (wgt.x() + offset, wgt.y(), size, wgt.h()),
+        };
+        let color = self.thumb_color();
+        draw::set_draw_color(color);
+        match self.style {
+            ScrollBarStyle::Boxy => {
+                draw::draw_rectf(x, y, w, h);
+            }
+            ScrollBarStyle::Rounded | ScrollBarStyle::Minimap => {
+                let radius = (w.min(h) / 2).min(12);
+                draw::draw_rbox(x, y, w, h, radius, true, color);
+            }
+        }
+    }
+
+    fn draw_markers(&self, wgt: &Widget, track: i32) {
+        let breadth = match self.orientation {
+            Orientation::Vertical => wgt.w(),
+            Orientation::Horizontal => wgt.h(),
+        };
+        let (band, band_breadth) = self.marker_band(breadth);
+        // Many markers can land on the same pixels, so only draw each stretch once per kind.
+        let mut drawn_to: Option<(MarkerKind, i32)> = None;
+        for marker in &self.markers {
+            let (start, size) = self.marker_span(track, marker);
+            if drawn_to.is_some_and(|(kind, end)| kind == marker.kind && start + size <= end) {
+                continue;
+            }
+            drawn_to = Some((marker.kind, start + size));
+            let lane = marker.kind.lane();
+            let across = band + band_breadth * lane / 3;
+            let thickness = (band_breadth * (lane + 1) / 3 - band_breadth * lane / 3).max(1);
+            draw::set_draw_color(marker.color);
+            match self.orientation {
+                Orientation::Vertical => draw::draw_rectf(wgt.x() + across, wgt.y() + start, thickness, size),
+                Orientation::Horizontal => draw::draw_rectf(wgt.x() + start, wgt.y() + across, size, thickness),
+            }
+        }
+    }
+
+    fn thumb_color(&self) -> Color {
+        if self.dragging {
+            self.active_color
+        } else if self.hovered {
+            self.hover_color
+        } else {
+            self.thumb_color
+        }
+    }
+}
+
+/// A scrollbar drawn in the theme's colours. The thumb is sized to how much of the range
+/// is in view, dragging it scrolls, and clicking the track either side of it pages.
+/// Markers pick out parts of the range, and clicking one scrolls to it.
+#[derive(Clone)]
+pub struct ScrollBar {
+    pub widget: Widget,
+    state: Rc<RefCell<State>>,
 }
 
 impl ScrollBar {
     pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
-        let widget = Widget::new(x, y, w, h, "");
-        let mut sb = ScrollBar {
-            widget,
+        Self::with_orientation(x, y, w, h, Orientation::Vertical)
+    }
+
+    pub fn horizontal(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self::with_orientation(x, y, w, h, Orientation::Horizontal)
+    }
+
+    fn with_orientation(x: i32, y: i32, w: i32, h: i32, orientation: Orientation) -> Self {
+        let mut widget = Widget::new(x, y, w, h, "");
+        let state = Rc::new(RefCell::new(State {
             min: 0,
             max: 100,
             value: 0,
+            page: 1,
             bg_color: Color::from_u32(0x222222),
             thumb_color: Color::from_u32(0x888888),
+            hover_color: Color::from_u32(0xaaaaaa),
+            active_color: Color::from_u32(0xcccccc),
             style: ScrollBarStyle::Boxy,
+            orientation,
+            thickness: match orientation {
+                Orientation::Vertical => w,
+                Orientation::Horizontal => h,
+            },
+            minimap: None,
+            markers: Vec::new(),
+            auto_hide: false,
+            hovered: false,
             dragging: false,
             drag_offset: 0,
+            moved: None,
             on_change: None,
+        }));
+
+        widget.draw({
+            let state = state.clone();
+            move |wgt| {
+                let state = state.borrow();
+                draw::set_draw_color(state.bg_color);
+                draw::draw_rectf(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                let (track, _) = state.along(wgt, 0, 0);
+                draw::push_clip(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                if let Some(minimap) = state.minimap.as_ref().filter(|_| state.shows_minimap()) {
+                    let bounds = (wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                    minimap.draw(bounds, state.value - state.min, state.page.max(1), state.total());
+                } else if state.thumb_visible() {
+                    state.draw_thumb(wgt, track);
+                }
+                // Markers stay visible while an auto-hiding thumb is hidden.
+                state.draw_markers(wgt, track);
+                draw::pop_clip();
+            }
+        });
+
+        widget.handle({
+            let state = state.clone();
+            move |wgt, ev| {
+                let changed = {
+                    let mut state = state.borrow_mut();
+                    let (track, pos) = state.along(wgt, app::event_x(), app::event_y());
+                    let (offset, size) = state.thumb(track);
+                    let on_thumb = !state.shows_minimap() && pos >= offset && pos < offset + size;
+                    let marker = (ev == Event::Push && !on_thumb && state.in_marker_band(wgt))
+                        .then(|| state.marker_at(track, pos))
+                        .flatten();
+                    match ev {
+                        Event::Enter => {
+                            state.hovered = true;
+                            wgt.redraw();
+                            return true;
+                        }
+                        Event::Leave => {
+                            state.hovered = false;
+                            wgt.redraw();
+                            return true;
+                        }
+                        Event::Move => return true,
+                        // Clicking a marker centres the view on where it starts.
+                        Event::Push if app::event_mouse_button() == app::MouseButton::Left && marker.is_some() => {
+                            marker.map(|start| start - state.page.max(1) / 2)
+                        }
+                        // Clicking or dragging in a minimap centres the view on that line.
+                        Event::Push | Event::Drag if state.shows_minimap() => {
+                            state.dragging = true;
+                            let page = state.page.max(1);
+                            let line = Minimap::line_at(pos, track, state.value - state.min, page, state.total());
+                            Some(state.min + line - page / 2)
+                        }
+                        Event::Push if app::event_mouse_button() == app::MouseButton::Left => {
+                            if on_thumb {
+                                state.dragging = true;
+                                state.drag_offset = pos - offset;
+                                wgt.redraw();
+                                None
+                            } else if pos < offset {
+                                Some(state.value - state.page.max(1))
+                            } else {
+                                Some(state.value + state.page.max(1))
+                            }
+                        }
+                        Event::Drag if state.dragging => {
+                            let span = (track - size).max(1);
+                            let thumb_at = (pos - state.drag_offset).clamp(0, span);
+                            let range = (state.max - state.min).max(0);
+                            Some(state.min + (thumb_at as f32 / span as f32 * range as f32).round() as i32)
+                        }
+                        Event::Released => {
+                            state.dragging = false;
+                            wgt.redraw();
+                            return true;
+                        }
+                        _ => return false,
+                    }
+                };
+                if let Some(value) = changed {
+                    Self::change(&state, wgt, value);
+                }
+                true
+            }
+        });
+
+        ScrollBar { widget, state }
+    }
+
+    /// Moves to `value` because the user scrolled, and reports it.
+    fn change(state: &Rc<RefCell<State>>, wgt: &mut Widget, value: i32) {
+        let callback = {
+            let mut state = state.borrow_mut();
+            let value = value.clamp(state.min, state.max);
+            if value == state.value {
+                return;
+            }
+            state.value = value;
+            state.on_change.clone()
         };
-        sb.update_draw();
-        sb
+        Self::reveal(state, wgt);
+        // Cloned out so the callback can read and set the scrollbar.
+        if let Some(cb) = callback {
+            cb(state.borrow().value);
+        }
+    }
+
+    /// Notes that the scrollbar just moved, so an auto-hiding one shows for a moment.
+    fn reveal(state: &Rc<RefCell<State>>, wgt: &mut Widget) {
+        let auto_hide = {
+            let mut state = state.borrow_mut();
+            state.moved = Some(Instant::now());
+            state.auto_hide
+        };
+        wgt.redraw();
+        if auto_hide {
+            let mut wgt = wgt.clone();
+            app::add_timeout3(REVEAL_FOR.as_secs_f64(), move |_| {
+                if !wgt.was_deleted() {
+                    wgt.redraw();
+                }
+            });
+        }
     }
 
     pub fn set_colors(&mut self, bg: Color, thumb: Color) {
-        self.bg_color = bg;
-        self.thumb_color = thumb;
-        self.update_draw();
+        {
+            let mut state = self.state.borrow_mut();
+            state.bg_color = bg;
+            state.thumb_color = thumb;
+        }
+        self.widget.redraw();
+    }
+
+    /// The thumb's colours while the pointer is over it and while it is being dragged.
+    pub fn set_state_colors(&mut self, hover: Color, active: Color) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.hover_color = hover;
+            state.active_color = active;
+        }
+        self.widget.redraw();
     }
 
+    /// Sets how the scrollbar looks. A vertical scrollbar widens to fit a minimap, so the
+    /// panes need laying out again after switching to or from `Minimap`.
     pub fn set_style(&mut self, style: ScrollBarStyle) {
-        self.style = style;
-        self.update_draw();
+        let width = {
+            let mut state = self.state.borrow_mut();
+            state.style = style;
+            match (state.orientation, style) {
+                (Orientation::Horizontal, _) => None,
+                (Orientation::Vertical, ScrollBarStyle::Minimap) => Some(MINIMAP_WIDTH),
+                (Orientation::Vertical, _) => Some(state.thickness),
+            }
+        };
+        if let Some(width) = width.filter(|&w| w != self.widget.w()) {
+            self.widget.resize(self.widget.x(), self.widget.y(), width, self.widget.h());
+        }
+        self.widget.redraw();
     }
 
-    pub fn set_range(&mut self, min: i32, max: i32) {
-        self.min = min;
-        self.max = max;
-        self.update_draw();
+    /// Gives the scrollbar a minimap to draw when its style is `Minimap`.
+    pub fn set_minimap(&mut self, minimap: Minimap) {
+        self.state.borrow_mut().minimap = Some(minimap);
+        self.widget.redraw();
     }
 
-    pub fn set_value(&mut self, value: i32) {
-        self.value = value.clamp(self.min, self.max);
-        self.update_draw();
-    }
-
-    fn update_draw(&mut self) {
-        let min = self.min;
-        let max = self.max;
-        let value = self.value;
-        let w = self.widget.width();
-        let h = self.widget.height();
-        let bg_color = self.bg_color;
-        let thumb_color = self.thumb_color;
-        let style = self.style.clone();
-
-        self.widget.draw(move |wgt| {
-            draw::set_draw_color(bg_color);
-            draw::draw_rectf(wgt.x(), wgt.y(), w, h);
-
-            let thumb_h = (h as f32 * 0.2).max(20.0) as i32;
-            let range = (max - min).max(1);
-            let y_offset = ((h - thumb_h) as f32 * (value - min) as f32 / range as f32) as i32;
-            draw::set_draw_color(thumb_color);
-
-            draw::push_clip(wgt.x(), wgt.y(), w, h);
-
-            match style {
-                ScrollBarStyle::Boxy => {
-                    draw::draw_rectf(wgt.x(), wgt.y() + y_offset, w, thumb_h);
-                }
-                ScrollBarStyle::Rounded => {
-                    let radius = (w.min(thumb_h) / 2).min(12);
-                    draw::draw_rbox(wgt.x(), wgt.y() + y_offset, w, thumb_h, radius, true, thumb_color);
-                }
-            }
+    /// Colours the minimap: plain text, the lines in view, and each text style.
+    pub fn set_minimap_colors(&mut self, foreground: Color, view_color: Color, palette: Vec<Color>) {
+        if let Some(minimap) = self.state.borrow_mut().minimap.as_mut() {
+            minimap.set_colors(foreground, view_color, palette);
+        }
+        self.widget.redraw();
+    }
 
-            draw::pop_clip();
-        });
+    pub fn shows_minimap(&self) -> bool {
+        self.state.borrow().shows_minimap()
+    }
 
-        let sb_ptr = self as *mut ScrollBar;
-        self.widget.handle(move |w, ev| {
-            let sb = unsafe { &mut *sb_ptr };
-            let mx = app::event_x() - w.x();
-            let my = app::event_y() - w.y();
-            let width = w.width();
-            let height = w.height();
-            let thumb_h = (height as f32 * 0.2).max(20.0) as i32;
-            let range = (sb.max - sb.min).max(1);
-            let thumb_y = ((height - thumb_h) as f32 * (sb.value - sb.min) as f32 / range as f32) as i32;
-
-            match ev {
-                fltk::enums::Event::Push => {
-                    if mx >= 0 && mx < width && my >= thumb_y && my < thumb_y + thumb_h {
-                        sb.dragging = true;
-                        sb.drag_offset = my - thumb_y;
-                        true
-                    } else {
-                        false
-                    }
-                }
-                fltk::enums::Event::Drag => {
-                    if sb.dragging {
-                        let mut new_thumb_y = my - sb.drag_offset;
-                        new_thumb_y = new_thumb_y.clamp(0, h - thumb_h);
-                        let new_value = sb.min + ((new_thumb_y as f32 / (h - thumb_h) as f32) * range as f32).round() as i32;
-                        if new_value != sb.value {
-                            sb.value = new_value.clamp(sb.min, sb.max);
-                            sb.update_draw();
-                            if let Some(ref cb) = sb.on_change {
-                                cb(sb.value);
-                            }
-                        }
-                        true
-                    } else {
-                        false
-                    }
-                }
-                fltk::enums::Event::Released => {
-                    sb.dragging = false;
-                    false
-                }
-                _ => false,
+    /// Only draws the thumb while the pointer is over the scrollbar or just after it moved.
+    pub fn set_auto_hide(&mut self, auto_hide: bool) {
+        self.state.borrow_mut().auto_hide = auto_hide;
+        self.widget.redraw();
+    }
+
+    pub fn set_range(&mut self, min: i32, max: i32) {
+        {
+            let mut state = self.state.borrow_mut();
+            if (state.min, state.max) == (min, max) {
+                return;
             }
-        });
+            state.min = min;
+            state.max = max;
+            state.value = state.value.clamp(min, max.max(min));
+        }
+        self.widget.redraw();
+    }
+
+    /// Sets how much of the range is in view at once, which sizes the thumb and is how far
+    /// a click on the track scrolls.
+    pub fn set_page(&mut self, page: i32) {
+        if self.state.borrow().page == page {
+            return;
+        }
+        self.state.borrow_mut().page = page;
         self.widget.redraw();
     }
 
+    pub fn value(&self) -> i32 {
+        self.state.borrow().value
+    }
+
+    /// Moves the thumb without reporting it.
+    pub fn set_value(&mut self, value: i32) {
+        {
+            let mut state = self.state.borrow_mut();
+            let value = value.clamp(state.min, state.max.max(state.min));
+            if value == state.value {
+                return;
+            }
+            state.value = value;
+        }
+        Self::reveal(&self.state, &mut self.widget);
+    }
+
     pub fn set_pos(&mut self, x: i32, y: i32) {
         self.widget.set_pos(x, y);
-        self.update_draw();
+        self.widget.redraw();
     }
 
     pub fn set_size(&mut self, w: i32, h: i32) {
         self.widget.resize(self.widget.x(), self.widget.y(), w, h);
-        self.update_draw();
+        self.widget.redraw();
     }
 
     pub fn show(&mut self) {
@@ -160,7 +527,31 @@ impl ScrollBar {
         self.widget.hide();
     }
 
+    /// Adds markers alongside the ones already there.
+    pub fn add_markers(&mut self, markers: impl IntoIterator<Item = Marker>) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.markers.extend(markers);
+            state.markers.sort_by_key(|marker| (marker.kind, marker.start));
+        }
+        self.widget.redraw();
+    }
+
+    /// Removes every marker of `kind`, leaving the others, so each subsystem can replace
+    /// its own markers without knowing about anyone else's.
+    pub fn clear_markers(&mut self, kind: MarkerKind) {
+        {
+            let mut state = self.state.borrow_mut();
+            let before = state.markers.len();
+            state.markers.retain(|marker| marker.kind != kind);
+            if state.markers.len() == before {
+                return;
+            }
+        }
+        self.widget.redraw();
+    }
+
     pub fn set_on_change<F: 'static + Fn(i32)>(&mut self, cb: F) {
-        self.on_change = Some(Box::new(cb));
+        self.state.borrow_mut().on_change = Some(Rc::new(cb));
     }
-}
\ No newline at end of file
+}
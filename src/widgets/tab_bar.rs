@@ -0,0 +1,158 @@
+use fltk::{app, draw, enums::{Color, Event, Font}, prelude::*, widget::Widget};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::widgets::dot::draw_filled_circle;
+
+const TAB_PAD: i32 = 12;
+const DOT_SPACE: i32 = 12;
+
+struct State {
+    labels: Vec<(String, bool)>,
+    active: usize,
+    background: Color,
+    foreground: Color,
+    active_background: Color,
+    modified: Color,
+    font: Font,
+    size: i32,
+    on_select: Option<Rc<dyn Fn(usize)>>,
+    on_close: Option<Rc<dyn Fn(usize)>>,
+}
+
+impl State {
+    /// Left edge and width of every tab, shifted so the active tab is in view.
+    fn layout(&self, width: i32) -> Vec<(i32, i32)> {
+        draw::set_font(self.font, self.size);
+        let mut x = 0;
+        let mut tabs = Vec::with_capacity(self.labels.len());
+        for (label, _) in &self.labels {
+            let w = draw::width(label) as i32 + TAB_PAD * 2 + DOT_SPACE;
+            tabs.push((x, w));
+            x += w;
+        }
+        let shift = tabs
+            .get(self.active)
+            .map(|&(x, w)| (x + w - width).max(0))
+            .unwrap_or(0);
+        tabs.iter().map(|&(x, w)| (x - shift, w)).collect()
+    }
+}
+
+/// A strip of tabs, one per open buffer. Clicking a tab selects it and
+/// middle-clicking closes it.
+pub struct TabBar {
+    pub widget: Widget,
+    state: Rc<RefCell<State>>,
+}
+
+impl TabBar {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        let mut widget = Widget::new(x, y, w, h, "");
+        let state = Rc::new(RefCell::new(State {
+            labels: Vec::new(),
+            active: 0,
+            background: Color::from_u32(0x1e1e1e),
+            foreground: Color::from_u32(0xc0c0c0),
+            active_background: Color::from_u32(0x2e2e2e),
+            modified: Color::from_u32(0xe5c07b),
+            font: Font::Courier,
+            size: 14,
+            on_select: None,
+            on_close: None,
+        }));
+
+        widget.draw({
+            let state = state.clone();
+            move |wgt| {
+                let state = state.borrow();
+                draw::set_draw_color(state.background);
+                draw::draw_rectf(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                draw::push_clip(wgt.x(), wgt.y(), wgt.w(), wgt.h());
+                for (i, (x, w)) in state.layout(wgt.w()).into_iter().enumerate() {
+                    let (label, modified) = &state.labels[i];
+                    let x = wgt.x() + x;
+                    if i == state.active {
+                        draw::set_draw_color(state.active_background);
+                        draw::draw_rectf(x, wgt.y(), w, wgt.h());
+                    }
+                    if *modified {
+                        draw_filled_circle(x + TAB_PAD, wgt.y() + wgt.h() / 2, 3, state.modified);
+                    }
+                    draw::set_draw_color(state.foreground);
+                    draw::set_font(state.font, state.size);
+                    draw::draw_text2(label, x + TAB_PAD + DOT_SPACE, wgt.y(), w, wgt.h(), fltk::enums::Align::Left);
+                }
+                draw::pop_clip();
+            }
+        });
+
+        widget.handle({
+            let state = state.clone();
+            move |wgt, ev| {
+                if ev != Event::Push {
+                    return false;
+                }
+                let clicked = {
+                    let state = state.borrow();
+                    let mx = app::event_x() - wgt.x();
+                    state.layout(wgt.w()).iter().position(|&(x, w)| mx >= x && mx < x + w)
+                };
+                let Some(index) = clicked else { return true };
+                // Cloned out so the callback can update the tabs without a borrow conflict.
+                let callback = if app::event_mouse_button() == app::MouseButton::Middle {
+                    state.borrow().on_close.clone()
+                } else {
+                    state.borrow().on_select.clone()
+                };
+                if let Some(cb) = callback {
+                    cb(index);
+                }
+                true
+            }
+        });
+
+        TabBar { widget, state }
+    }
+
+    pub fn set_tabs(&mut self, labels: Vec<(String, bool)>, active: usize) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.labels = labels;
+            state.active = active;
+        }
+        self.widget.redraw();
+    }
+
+    pub fn set_active_modified(&mut self, modified: bool) {
+        {
+            let mut state = self.state.borrow_mut();
+            let active = state.active;
+            if let Some(label) = state.labels.get_mut(active) {
+                label.1 = modified;
+            }
+        }
+        self.widget.redraw();
+    }
+
+    pub fn apply_theme(&mut self, theme: &crate::config::Theme) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.background = theme.color_from_str(&theme.background);
+            state.foreground = theme.color_from_str(&theme.foreground);
+            state.active_background = state.background.lighter();
+            state.modified = theme.color_from_str(&theme.modified_color);
+            state.font = Font::by_name(&theme.font_family);
+            state.size = (theme.font_size - 2).max(8);
+        }
+        self.widget.redraw();
+    }
+
+    pub fn set_on_select<F: Fn(usize) + 'static>(&mut self, cb: F) {
+        self.state.borrow_mut().on_select = Some(Rc::new(cb));
+    }
+
+    pub fn set_on_close<F: Fn(usize) + 'static>(&mut self, cb: F) {
+        self.state.borrow_mut().on_close = Some(Rc::new(cb));
+    }
+}